@@ -0,0 +1,74 @@
+use std::{collections::HashMap, hint::black_box};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use nbt::{Tag, read_nbt, write_nbt};
+
+/// A chunk-shaped compound: 24 sections of packed block states plus heightmaps and a
+/// long list of doubles
+fn chunk_like() -> Tag {
+    let mut sections = Vec::new();
+    for y in 0..24i64 {
+        let mut entries = HashMap::new();
+        entries.insert(
+            "data".to_string(),
+            Tag::new_long_array("data", (0..256).map(|i| i * 0x0101_0101 + y).collect()),
+        );
+        sections.push(Tag::Compound {
+            name: None,
+            entries,
+        });
+    }
+    let mut entries = HashMap::new();
+    entries.insert(
+        "sections".to_string(),
+        Tag::new_list("sections", 10, sections),
+    );
+    entries.insert(
+        "MOTION_BLOCKING".to_string(),
+        Tag::new_long_array("MOTION_BLOCKING", vec![0x0123_4567_89ab_cdef; 37]),
+    );
+    entries.insert(
+        "Biomes".to_string(),
+        Tag::new_int_array("Biomes", (0..1024).collect()),
+    );
+    entries.insert(
+        "Samples".to_string(),
+        Tag::new_list(
+            "Samples",
+            6,
+            (0..4096)
+                .map(|i| Tag::Double {
+                    name: None,
+                    value: i as f64 * 0.25,
+                })
+                .collect(),
+        ),
+    );
+    Tag::Compound {
+        name: Some(String::new()),
+        entries,
+    }
+}
+
+fn bench_io(c: &mut Criterion) {
+    let tag = chunk_like();
+    let mut bytes = Vec::new();
+    write_nbt(&tag, &mut bytes).unwrap();
+
+    let mut group = c.benchmark_group("chunk");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("read", |b| {
+        b.iter(|| read_nbt(black_box(&bytes[..])).unwrap())
+    });
+    group.bench_function("write", |b| {
+        b.iter(|| {
+            let mut out = Vec::with_capacity(bytes.len());
+            write_nbt(black_box(&tag), &mut out).unwrap();
+            out
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_io);
+criterion_main!(benches);
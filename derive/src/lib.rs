@@ -0,0 +1,466 @@
+//! `#[derive(ToNbt, FromNbt)]` for the `nbt` crate. See `nbt::FromNbt` for the
+//! supported attributes.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Expr, Fields, GenericArgument, Ident, LitStr, PathArguments, Result,
+    Type, ext::IdentExt, parse_macro_input, parse_quote,
+};
+
+#[proc_macro_derive(ToNbt, attributes(nbt))]
+pub fn derive_to_nbt(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_nbt(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FromNbt, attributes(nbt))]
+pub fn derive_from_nbt(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_nbt(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ArrayHint {
+    Byte,
+    Int,
+    Long,
+}
+
+enum FieldDefault {
+    None,
+    Trait,
+    Path(syn::Path),
+}
+
+/// A named field (of a struct or struct variant) with its `#[nbt(...)]` options
+struct Field {
+    ident: Ident,
+    key: String,
+    ty: Type,
+    default: FieldDefault,
+    skip: bool,
+    flatten: bool,
+    array: Option<ArrayHint>,
+    /// `Some(T)` when the field is `Option<T>`
+    option_of: Option<Type>,
+}
+
+fn parse_fields(fields: &Fields) -> Result<Vec<Field>> {
+    let Fields::Named(named) = fields else {
+        unreachable!("callers handle tuple and unit fields")
+    };
+    named
+        .named
+        .iter()
+        .map(|f| {
+            let ident = f.ident.clone().unwrap();
+            let mut field = Field {
+                key: ident.unraw().to_string(),
+                ident,
+                ty: f.ty.clone(),
+                default: FieldDefault::None,
+                skip: false,
+                flatten: false,
+                array: None,
+                option_of: option_inner(&f.ty),
+            };
+            for attr in f.attrs.iter().filter(|a| a.path().is_ident("nbt")) {
+                attr.parse_nested_meta(|meta| {
+                    let set_array = |field: &mut Field, hint| {
+                        if field.array.replace(hint).is_some() {
+                            return Err(meta.error("only one array hint is allowed"));
+                        }
+                        Ok(())
+                    };
+                    if meta.path.is_ident("rename") {
+                        field.key = meta.value()?.parse::<LitStr>()?.value();
+                    } else if meta.path.is_ident("default") {
+                        field.default = match meta.value() {
+                            Ok(v) => FieldDefault::Path(v.parse::<LitStr>()?.parse()?),
+                            Err(_) => FieldDefault::Trait,
+                        };
+                    } else if meta.path.is_ident("skip") {
+                        field.skip = true;
+                    } else if meta.path.is_ident("flatten") {
+                        field.flatten = true;
+                    } else if meta.path.is_ident("byte_array") {
+                        set_array(&mut field, ArrayHint::Byte)?;
+                    } else if meta.path.is_ident("int_array") {
+                        set_array(&mut field, ArrayHint::Int)?;
+                    } else if meta.path.is_ident("long_array") {
+                        set_array(&mut field, ArrayHint::Long)?;
+                    } else {
+                        return Err(meta.error("unknown nbt field attribute"));
+                    }
+                    Ok(())
+                })?;
+            }
+            if field.flatten && (field.array.is_some() || field.option_of.is_some()) {
+                return Err(Error::new_spanned(
+                    &f.ty,
+                    "flatten fields cannot be Option or carry an array hint",
+                ));
+            }
+            Ok(field)
+        })
+        .collect()
+}
+
+fn option_inner(ty: &Type) -> Option<Type> {
+    let Type::Path(p) = ty else { return None };
+    let last = p.path.segments.last()?;
+    if last.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(t) => Some(t.clone()),
+        _ => None,
+    }
+}
+
+/// `#[nbt(tag = "...")]` on an enum, defaulting to "type"; `#[nbt(rename)]` on variants
+fn container_tag(input: &DeriveInput) -> Result<String> {
+    let mut tag = "type".to_string();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("nbt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                tag = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unknown nbt container attribute"))
+            }
+        })?;
+    }
+    Ok(tag)
+}
+
+fn variant_name(variant: &syn::Variant) -> Result<String> {
+    let mut name = variant.ident.unraw().to_string();
+    for attr in variant.attrs.iter().filter(|a| a.path().is_ident("nbt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unknown nbt variant attribute"))
+            }
+        })?;
+    }
+    Ok(name)
+}
+
+fn add_bounds(input: &DeriveInput, bound: TokenStream2) -> syn::Generics {
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+// --- ToNbt -----------------------------------------------------------------------
+
+/// Statements inserting each field into `entries`; `access` yields a reference to a field
+fn write_fields(fields: &[Field], access: impl Fn(&Ident) -> TokenStream2) -> TokenStream2 {
+    let mut out = TokenStream2::new();
+    for f in fields.iter().filter(|f| !f.skip && !f.flatten) {
+        let key = &f.key;
+        let value = access(&f.ident);
+        let to = |v: TokenStream2| match f.array {
+            Some(ArrayHint::Byte) => quote!(::nbt::typed::__private::byte_array_to_nbt(#v)),
+            Some(ArrayHint::Int) => quote!(::nbt::typed::__private::int_array_to_nbt(#v)),
+            Some(ArrayHint::Long) => quote!(::nbt::typed::__private::long_array_to_nbt(#v)),
+            None => quote!(::nbt::ToNbt::to_nbt(#v)),
+        };
+        if f.option_of.is_some() {
+            let conv = to(quote!(v));
+            out.extend(quote! {
+                if let ::std::option::Option::Some(v) = #value {
+                    entries.insert(::std::string::String::from(#key), #conv);
+                }
+            });
+        } else {
+            let conv = to(value);
+            out.extend(quote! {
+                entries.insert(::std::string::String::from(#key), #conv);
+            });
+        }
+    }
+    for f in fields.iter().filter(|f| !f.skip && f.flatten) {
+        let value = access(&f.ident);
+        out.extend(quote! {
+            ::nbt::typed::__private::flatten_into(&mut entries, ::nbt::ToNbt::to_nbt(#value));
+        });
+    }
+    out
+}
+
+fn expand_to_nbt(input: &DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = add_bounds(input, quote!(::nbt::ToNbt));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(_) => {
+                let fields = parse_fields(&s.fields)?;
+                let writes = write_fields(&fields, |ident| quote!(&self.#ident));
+                quote! {
+                    #[allow(unused_mut)]
+                    let mut entries = ::std::collections::HashMap::new();
+                    #writes
+                    ::nbt::Tag::from(entries)
+                }
+            }
+            Fields::Unnamed(u) if u.unnamed.len() == 1 => quote!(::nbt::ToNbt::to_nbt(&self.0)),
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "only newtype tuple structs are supported",
+                ));
+            }
+            Fields::Unit => quote!(::nbt::Tag::from(::std::collections::HashMap::new())),
+        },
+        Data::Enum(e) => {
+            let unit_only = e.variants.iter().all(|v| matches!(v.fields, Fields::Unit));
+            let tag_key = container_tag(input)?;
+            let mut arms = TokenStream2::new();
+            for v in &e.variants {
+                let ident = &v.ident;
+                let vname = variant_name(v)?;
+                arms.extend(match &v.fields {
+                    Fields::Unit if unit_only => quote! {
+                        Self::#ident => ::nbt::Tag::from(#vname),
+                    },
+                    Fields::Unit => quote! {
+                        Self::#ident => {
+                            let mut entries = ::std::collections::HashMap::new();
+                            entries.insert(::std::string::String::from(#tag_key), ::nbt::Tag::from(#vname));
+                            ::nbt::Tag::from(entries)
+                        }
+                    },
+                    Fields::Unnamed(u) if u.unnamed.len() == 1 => quote! {
+                        Self::#ident(inner) => {
+                            let mut entries = ::std::collections::HashMap::new();
+                            entries.insert(::std::string::String::from(#tag_key), ::nbt::Tag::from(#vname));
+                            ::nbt::typed::__private::flatten_into(&mut entries, ::nbt::ToNbt::to_nbt(inner));
+                            ::nbt::Tag::from(entries)
+                        }
+                    },
+                    Fields::Unnamed(_) => {
+                        return Err(Error::new_spanned(
+                            v,
+                            "only newtype tuple variants are supported",
+                        ));
+                    }
+                    Fields::Named(_) => {
+                        let fields = parse_fields(&v.fields)?;
+                        let idents = fields.iter().map(|f| &f.ident);
+                        let writes = write_fields(&fields, |ident| quote!(#ident));
+                        quote! {
+                            #[allow(unused_variables)]
+                            Self::#ident { #(#idents),* } => {
+                                let mut entries = ::std::collections::HashMap::new();
+                                entries.insert(::std::string::String::from(#tag_key), ::nbt::Tag::from(#vname));
+                                #writes
+                                ::nbt::Tag::from(entries)
+                            }
+                        }
+                    }
+                });
+            }
+            quote! {
+                match self {
+                    #arms
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(&input.ident, "unions are not supported"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::nbt::ToNbt for #name #ty_generics #where_clause {
+            fn to_nbt(&self) -> ::nbt::Tag {
+                #body
+            }
+        }
+    })
+}
+
+// --- FromNbt ---------------------------------------------------------------------
+
+/// Field initializers reading from a `entries: &HashMap<String, Tag>` in scope. `tag_key`
+/// is an enum variant's tag, which flattened fields mustn't receive either.
+fn read_fields(fields: &[Field], tag_key: Option<&str>) -> TokenStream2 {
+    let claimed: Vec<&str> = fields
+        .iter()
+        .filter(|f| !f.skip && !f.flatten)
+        .map(|f| f.key.as_str())
+        .chain(tag_key)
+        .collect();
+    let mut out = TokenStream2::new();
+    for f in fields {
+        let ident = &f.ident;
+        let key = &f.key;
+        let init = if f.skip {
+            default_expr(&f.default)
+        } else if f.flatten {
+            let ty = &f.ty;
+            quote! {
+                <#ty as ::nbt::FromNbt>::from_nbt(
+                    &::nbt::typed::__private::rest(entries, &[#(#claimed),*]),
+                )?
+            }
+        } else {
+            let ty = f.option_of.as_ref().unwrap_or(&f.ty);
+            let read = match f.array {
+                Some(hint) => {
+                    let conv = match hint {
+                        ArrayHint::Byte => format_ident!("byte_array_from_nbt"),
+                        ArrayHint::Int => format_ident!("int_array_from_nbt"),
+                        ArrayHint::Long => format_ident!("long_array_from_nbt"),
+                    };
+                    quote!(::nbt::typed::__private::array_field(entries, #key, ::nbt::typed::__private::#conv)?)
+                }
+                None => quote!(::nbt::typed::__private::field::<#ty>(entries, #key)?),
+            };
+            if f.option_of.is_some() {
+                read
+            } else {
+                let missing = match f.default {
+                    FieldDefault::None => {
+                        quote!(return ::std::result::Result::Err(::nbt::FromNbtError::missing_field(#key)))
+                    }
+                    ref d => default_expr(d),
+                };
+                quote! {
+                    match #read {
+                        ::std::option::Option::Some(v) => v,
+                        ::std::option::Option::None => #missing,
+                    }
+                }
+            }
+        };
+        out.extend(quote!(#ident: #init,));
+    }
+    out
+}
+
+fn default_expr(default: &FieldDefault) -> TokenStream2 {
+    match default {
+        FieldDefault::Path(path) => quote!(#path()),
+        _ => quote!(::std::default::Default::default()),
+    }
+}
+
+fn expand_from_nbt(input: &DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = add_bounds(input, quote!(::nbt::FromNbt));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(_) => {
+                let fields = parse_fields(&s.fields)?;
+                let inits = read_fields(&fields, None);
+                quote! {
+                    #[allow(unused_variables)]
+                    let entries = ::nbt::typed::__private::entries(tag)?;
+                    ::std::result::Result::Ok(Self { #inits })
+                }
+            }
+            Fields::Unnamed(u) if u.unnamed.len() == 1 => {
+                quote!(::std::result::Result::Ok(Self(::nbt::FromNbt::from_nbt(
+                    tag
+                )?)))
+            }
+            Fields::Unnamed(_) => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "only newtype tuple structs are supported",
+                ));
+            }
+            Fields::Unit => quote! {
+                ::nbt::typed::__private::entries(tag)?;
+                ::std::result::Result::Ok(Self)
+            },
+        },
+        Data::Enum(e) => {
+            let unit_only = e.variants.iter().all(|v| matches!(v.fields, Fields::Unit));
+            let tag_key = container_tag(input)?;
+            let mut arms = TokenStream2::new();
+            for v in &e.variants {
+                let ident = &v.ident;
+                let vname = variant_name(v)?;
+                arms.extend(match &v.fields {
+                    Fields::Unit => quote!(#vname => ::std::result::Result::Ok(Self::#ident),),
+                    Fields::Unnamed(u) if u.unnamed.len() == 1 => quote! {
+                        #vname => ::std::result::Result::Ok(Self::#ident(::nbt::FromNbt::from_nbt(
+                            &::nbt::typed::__private::rest(::nbt::typed::__private::entries(tag)?, &[#tag_key]),
+                        )?)),
+                    },
+                    Fields::Unnamed(_) => {
+                        return Err(Error::new_spanned(
+                            v,
+                            "only newtype tuple variants are supported",
+                        ));
+                    }
+                    Fields::Named(_) => {
+                        let fields = parse_fields(&v.fields)?;
+                        let inits = read_fields(&fields, Some(&tag_key));
+                        quote! {
+                            #vname => {
+                                #[allow(unused_variables)]
+                                let entries = ::nbt::typed::__private::entries(tag)?;
+                                ::std::result::Result::Ok(Self::#ident { #inits })
+                            }
+                        }
+                    }
+                });
+            }
+            let key: Expr = if unit_only {
+                parse_quote!(::nbt::typed::__private::variant_name(tag, "")?)
+            } else {
+                let lit = LitStr::new(&tag_key, Span::call_site());
+                parse_quote!(::nbt::typed::__private::variant_name(tag, #lit)?)
+            };
+            let check = if unit_only {
+                quote! {
+                    if !matches!(tag, ::nbt::Tag::String { .. }) {
+                        return ::std::result::Result::Err(::nbt::FromNbtError::wrong_type(8, tag));
+                    }
+                }
+            } else {
+                quote!()
+            };
+            quote! {
+                #check
+                match #key {
+                    #arms
+                    other => ::std::result::Result::Err(::nbt::typed::__private::unknown_variant(other)),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(&input.ident, "unions are not supported"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::nbt::FromNbt for #name #ty_generics #where_clause {
+            fn from_nbt(tag: &::nbt::Tag) -> ::std::result::Result<Self, ::nbt::FromNbtError> {
+                #body
+            }
+        }
+    })
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Result},
+};
+
+use async_compression::{
+    Level,
+    tokio::{bufread::GzipDecoder, write::GzipEncoder},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::{
+    Tag, TagId,
+    io::{
+        BeNum, NumericList, PREALLOC, as_bytes, as_bytes_mut, check_filled, check_len,
+        from_be_in_place, list_of, numeric_list,
+    },
+    mixed,
+};
+
+/// Async binary reader for NBT format, the `AsyncRead` twin of [`crate::Reader`]
+pub struct AsyncReader<R: AsyncRead + Unpin> {
+    inner: R,
+    unwrap_lists: bool,
+}
+
+// --- AsyncReader gzip ---
+impl<R: AsyncRead + Unpin> AsyncReader<GzipDecoder<BufReader<R>>> {
+    pub fn from_gzip(inner: R) -> Self {
+        AsyncReader {
+            inner: GzipDecoder::new(BufReader::new(inner)),
+            unwrap_lists: false,
+        }
+    }
+}
+impl<W: AsyncWrite + Unpin> AsyncWriter<GzipEncoder<W>> {
+    pub fn to_gzip(inner: W) -> Self {
+        AsyncWriter {
+            inner: GzipEncoder::with_quality(inner, Level::Default),
+        }
+    }
+}
+impl<R: AsyncRead + Unpin + Send> AsyncReader<R> {
+    pub fn new(inner: R) -> Self {
+        AsyncReader {
+            inner,
+            unwrap_lists: false,
+        }
+    }
+
+    /// Unwrap `{"": value}` elements of compound lists, like
+    /// [`crate::Reader::unwrap_mixed_lists`]
+    pub fn unwrap_mixed_lists(mut self, unwrap: bool) -> Self {
+        self.unwrap_lists = unwrap;
+        self
+    }
+
+    /// Read a full tag (ID + name + payload)
+    pub async fn read_tag(&mut self) -> Result<Tag> {
+        let id = self.inner.read_u8().await?;
+        if id == 0 {
+            return Ok(Tag::End);
+        }
+        let name = Some(self.read_string().await?);
+        self.read_payload(id, name).await
+    }
+
+    async fn read_payload(&mut self, id: TagId, name: Option<String>) -> Result<Tag> {
+        match id {
+            1 => Ok(Tag::Byte {
+                name,
+                value: self.inner.read_i8().await?,
+            }),
+            2 => Ok(Tag::Short {
+                name,
+                value: self.inner.read_i16().await?,
+            }),
+            3 => Ok(Tag::Int {
+                name,
+                value: self.inner.read_i32().await?,
+            }),
+            4 => Ok(Tag::Long {
+                name,
+                value: self.inner.read_i64().await?,
+            }),
+            5 => Ok(Tag::Float {
+                name,
+                value: self.inner.read_f32().await?,
+            }),
+            6 => Ok(Tag::Double {
+                name,
+                value: self.inner.read_f64().await?,
+            }),
+            7 => {
+                let len = self.read_len().await?;
+                Ok(Tag::ByteArray {
+                    name,
+                    value: self.read_bytes(len).await?,
+                })
+            }
+            8 => {
+                let s = self.read_string().await?;
+                Ok(Tag::String { name, value: s })
+            }
+            9 => {
+                let elem_id = self.inner.read_u8().await?;
+                let len = self.read_len().await?;
+                let elements = match elem_id {
+                    1 => list_of(self.read_bytes(len).await?, |v| Tag::Byte {
+                        name: None,
+                        value: v as i8,
+                    }),
+                    2 => list_of(self.read_array(len).await?, |value| Tag::Short {
+                        name: None,
+                        value,
+                    }),
+                    3 => list_of(self.read_array(len).await?, |value| Tag::Int {
+                        name: None,
+                        value,
+                    }),
+                    4 => list_of(self.read_array(len).await?, |value| Tag::Long {
+                        name: None,
+                        value,
+                    }),
+                    5 => list_of(self.read_array(len).await?, |v| Tag::Float {
+                        name: None,
+                        value: f32::from_bits(v),
+                    }),
+                    6 => list_of(self.read_array(len).await?, |v| Tag::Double {
+                        name: None,
+                        value: f64::from_bits(v),
+                    }),
+                    _ => {
+                        let mut elements = Vec::with_capacity(len.min(PREALLOC));
+                        for _ in 0..len {
+                            elements.push(Box::pin(self.read_payload(elem_id, None)).await?);
+                        }
+                        elements
+                    }
+                };
+                let (element_id, elements) = match elem_id {
+                    _ if elements.is_empty() => (0, elements),
+                    10 if self.unwrap_lists => mixed::unwrap_elements(elements),
+                    _ => (elem_id, elements),
+                };
+                Ok(Tag::List {
+                    name,
+                    element_id,
+                    elements,
+                })
+            }
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let id = self.inner.read_u8().await?;
+                    if id == 0 {
+                        break;
+                    }
+                    let key = self.read_string().await?;
+                    let tag = Box::pin(self.read_payload(id, Some(key.clone()))).await?;
+                    entries.insert(key, tag);
+                }
+                Ok(Tag::Compound { name, entries })
+            }
+            11 => {
+                let len = self.read_len().await?;
+                Ok(Tag::IntArray {
+                    name,
+                    value: self.read_array(len).await?,
+                })
+            }
+            12 => {
+                let len = self.read_len().await?;
+                Ok(Tag::LongArray {
+                    name,
+                    value: self.read_array(len).await?,
+                })
+            }
+            other => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown tag id {}", other),
+            )),
+        }
+    }
+
+    async fn read_len(&mut self) -> Result<usize> {
+        check_len(self.inner.read_i32().await?)
+    }
+
+    async fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(len.min(PREALLOC));
+        (&mut self.inner)
+            .take(len as u64)
+            .read_to_end(&mut buf)
+            .await?;
+        check_filled(buf.len(), len)?;
+        Ok(buf)
+    }
+
+    async fn read_array<T: BeNum>(&mut self, len: usize) -> Result<Vec<T>> {
+        let mut v = Vec::new();
+        while v.len() < len {
+            let start = v.len();
+            v.resize(start + (len - start).min(PREALLOC), T::default());
+            self.inner.read_exact(as_bytes_mut(&mut v[start..])).await?;
+        }
+        from_be_in_place(&mut v);
+        Ok(v)
+    }
+
+    async fn read_string(&mut self) -> Result<String> {
+        let len = self.inner.read_u16().await? as usize;
+        let mut buf = vec![0u8; len];
+        self.inner.read_exact(&mut buf).await?;
+        String::from_utf8(buf).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+/// Async binary writer for NBT format, the `AsyncWrite` twin of [`crate::Writer`]
+pub struct AsyncWriter<W: AsyncWrite + Unpin> {
+    inner: W,
+}
+
+impl<W: AsyncWrite + Unpin + Send> AsyncWriter<W> {
+    pub fn new(inner: W) -> Self {
+        AsyncWriter { inner }
+    }
+
+    /// Write a full tag (ID + name + payload)
+    pub async fn write_tag(&mut self, tag: &Tag) -> Result<()> {
+        let id = tag.id();
+        self.inner.write_u8(id).await?;
+        if id != 0 {
+            self.write_string(tag.name().unwrap_or("")).await?;
+            self.write_payload(tag).await?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered data and finish the stream (writes the gzip trailer for `to_gzip`)
+    pub async fn shutdown(&mut self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    /// Consume the writer, returning the wrapped stream
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    async fn write_payload(&mut self, tag: &Tag) -> Result<()> {
+        match tag {
+            Tag::End => {}
+            Tag::Byte { value, .. } => self.inner.write_i8(*value).await?,
+            Tag::Short { value, .. } => self.inner.write_i16(*value).await?,
+            Tag::Int { value, .. } => self.inner.write_i32(*value).await?,
+            Tag::Long { value, .. } => self.inner.write_i64(*value).await?,
+            Tag::Float { value, .. } => self.inner.write_f32(*value).await?,
+            Tag::Double { value, .. } => self.inner.write_f64(*value).await?,
+            Tag::ByteArray { value, .. } => {
+                self.inner.write_i32(value.len() as i32).await?;
+                self.inner.write_all(value).await?;
+            }
+            Tag::String { value, .. } => {
+                self.write_string(value).await?;
+            }
+            Tag::List {
+                element_id,
+                elements,
+                ..
+            } => {
+                let element_id = mixed::write_element_id(*element_id, elements)?;
+                if mixed::needs_wrapping(elements) {
+                    self.inner.write_u8(10).await?;
+                    self.inner.write_i32(elements.len() as i32).await?;
+                    for elem in elements {
+                        if mixed::is_wrapped(elem) {
+                            self.inner.write_u8(elem.id()).await?;
+                            self.write_string("").await?;
+                            Box::pin(self.write_payload(elem)).await?;
+                            self.inner.write_u8(0).await?;
+                        } else {
+                            Box::pin(self.write_payload(elem)).await?;
+                        }
+                    }
+                    return Ok(());
+                }
+                self.inner.write_u8(element_id).await?;
+                self.inner.write_i32(elements.len() as i32).await?;
+                if !self.write_numeric_list(element_id, elements).await? {
+                    for elem in elements {
+                        Box::pin(self.write_payload(elem)).await?; // lists omit names
+                    }
+                }
+            }
+            Tag::Compound { entries, .. } => {
+                for (key, entry) in entries {
+                    self.inner.write_u8(entry.id()).await?;
+                    self.write_string(key).await?;
+                    Box::pin(self.write_payload(entry)).await?;
+                }
+                self.inner.write_u8(0).await?; // TAG_End
+            }
+            Tag::IntArray { value, .. } => {
+                self.inner.write_i32(value.len() as i32).await?;
+                self.write_array(value).await?;
+            }
+            Tag::LongArray { value, .. } => {
+                self.inner.write_i32(value.len() as i32).await?;
+                self.write_array(value).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// See [`crate::Writer`]'s numeric list fast path
+    async fn write_numeric_list(&mut self, element_id: TagId, elements: &[Tag]) -> Result<bool> {
+        match numeric_list(element_id, elements) {
+            Some(NumericList::Bytes(v)) => self.inner.write_all(&v).await?,
+            Some(NumericList::Shorts(v)) => self.write_array(&v).await?,
+            Some(NumericList::Ints(v)) => self.write_array(&v).await?,
+            Some(NumericList::Longs(v)) => self.write_array(&v).await?,
+            Some(NumericList::Floats(v)) => self.write_array(&v).await?,
+            Some(NumericList::Doubles(v)) => self.write_array(&v).await?,
+            None => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    async fn write_array<T: BeNum>(&mut self, v: &[T]) -> Result<()> {
+        let swapped: Vec<T> = v.iter().map(|x| x.native_to_be()).collect();
+        self.inner.write_all(as_bytes(&swapped)).await
+    }
+
+    async fn write_string(&mut self, s: &str) -> Result<()> {
+        let bytes = s.as_bytes();
+        if bytes.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "string too long"));
+        }
+        self.inner.write_u16(bytes.len() as u16).await?;
+        self.inner.write_all(bytes).await
+    }
+}
+
+/// Read an NBT Tag from any async reader
+pub async fn read_nbt_async<R: AsyncRead + Unpin + Send>(reader: R) -> Result<Tag> {
+    AsyncReader::new(reader).read_tag().await
+}
+
+/// Write an NBT Tag to any async writer
+pub async fn write_nbt_async<W: AsyncWrite + Unpin + Send>(tag: &Tag, writer: W) -> Result<()> {
+    let mut w = AsyncWriter::new(writer);
+    w.write_tag(tag).await?;
+    w.inner.flush().await
+}
@@ -0,0 +1,495 @@
+//! `nbt`: inspect and edit NBT files from the command line
+
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use nbt::{
+    Compression, JsonMode, NbtPath, PathSegment, Reader, Region, Tag, Writer, region::ChunkInfo,
+    write_nbt,
+};
+
+const USAGE: &str = "\
+usage: nbt <command> ...
+
+commands:
+  dump <file> [--json|--typed]        print a file as SNBT, or as JSON
+  convert <input> <output> [--compression=gzip|zlib|none] [--typed]
+                                      convert between binary NBT, .snbt and .json files
+  get <file> <path> [--json|--typed]  print the value at an NBT path, like Data.Player.Pos[0]
+  set <file> <path> <snbt> [--no-backup] [--typed]
+                                      replace or add the value at an NBT path in place,
+                                      first copying the file to <file>.bak
+
+  region list <file.mca>              list chunks with their size, compression and save time
+  region extract <file.mca> <x> <z> <output> [--compression=gzip|zlib|none] [--typed]
+                                      save one chunk as a standalone file
+  region replace <file.mca> <x> <z> <input> [--no-backup] [--typed]
+                                      store a chunk read from a file
+  region delete <file.mca> <x> <z> [<x> <z> ...] [--no-backup]
+                                      remove chunks from the header
+  region check <file.mca>             report corrupt, overlapping or unreadable chunks
+
+--json writes plain JSON, which loses the exact number and array types. --typed reads and
+writes JSON where every value carries its NBT type, so files convert back unchanged.
+
+Chunk coordinates may be world chunk coordinates or 0..32 within the region.
+
+Binary files may be gzipped, zlib-compressed or raw; the compression is detected when
+reading and kept when writing back.";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (flags, args): (Vec<&str>, Vec<&str>) = args
+        .iter()
+        .map(String::as_str)
+        .partition(|a| a.starts_with("--"));
+    let result = match args.as_slice() {
+        ["dump", file] => dump(file.as_ref(), &flags),
+        ["convert", input, output] => convert(input.as_ref(), output.as_ref(), &flags),
+        ["get", file, path] => get(file.as_ref(), path, &flags),
+        ["set", file, path, value] => set(file.as_ref(), path, value, &flags),
+        ["region", "list", file] => region_list(file.as_ref(), &flags),
+        ["region", "extract", file, x, z, output] => {
+            region_extract(file.as_ref(), (x, z), output.as_ref(), &flags)
+        }
+        ["region", "replace", file, x, z, input] => {
+            region_replace(file.as_ref(), (x, z), input.as_ref(), &flags)
+        }
+        ["region", "delete", file, coords @ ..] if !coords.is_empty() && coords.len() % 2 == 0 => {
+            region_delete(file.as_ref(), coords, &flags)
+        }
+        ["region", "check", file] => region_check(file.as_ref(), &flags),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("nbt: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// How a file is stored on disk, so it can be written back the same way
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Format {
+    Binary(Compression),
+    Snbt,
+    Json(JsonMode),
+}
+
+impl Format {
+    /// By extension for text formats; binary otherwise
+    fn of(path: &Path, flags: &[&str]) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("snbt") => Format::Snbt,
+            Some("json") => Format::Json(json_mode(flags)),
+            _ => Format::Binary(Compression::Gzip),
+        }
+    }
+}
+
+fn read_file(path: &Path, flags: &[&str]) -> Result<(Tag, Format)> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let format = match Format::of(path, flags) {
+        Format::Binary(_) => Format::Binary(Compression::detect(&bytes)),
+        text => text,
+    };
+    // Unwrapped mixed lists are wrapped again on write, so files come back unchanged
+    let tag = match format {
+        Format::Binary(Compression::Gzip) => Reader::from_gzip(&bytes[..])
+            .unwrap_mixed_lists(true)
+            .read_tag()?,
+        Format::Binary(Compression::Zlib) => Reader::from_zlib(&bytes[..])
+            .unwrap_mixed_lists(true)
+            .read_tag()?,
+        Format::Binary(Compression::None) => Reader::new(&bytes[..])
+            .unwrap_mixed_lists(true)
+            .read_tag()?,
+        Format::Snbt => std::str::from_utf8(&bytes)?.parse()?,
+        Format::Json(mode) => Tag::from_json(std::str::from_utf8(&bytes)?, mode)?,
+    };
+    Ok((tag, format))
+}
+
+fn write_file(path: &Path, tag: &Tag, format: Format) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    match format {
+        Format::Binary(Compression::Gzip) => {
+            let mut writer = Writer::to_gzip(&mut out);
+            writer.write_tag(tag)?;
+            writer.into_inner().finish()?;
+        }
+        Format::Binary(Compression::Zlib) => {
+            let mut writer = Writer::to_zlib(&mut out);
+            writer.write_tag(tag)?;
+            writer.into_inner().finish()?;
+        }
+        Format::Binary(Compression::None) => write_nbt(tag, &mut out)?,
+        Format::Snbt => writeln!(out, "{:#}", tag)?,
+        Format::Json(mode) => writeln!(out, "{}", tag.to_json_pretty(mode))?,
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(())
+}
+
+fn print(tag: &Tag, flags: &[&str]) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    if flags.contains(&"--typed") {
+        writeln!(stdout, "{}", tag.to_json_pretty(JsonMode::Typed))?;
+    } else if flags.contains(&"--json") {
+        writeln!(stdout, "{}", tag.to_json_pretty(JsonMode::Natural))?;
+    } else {
+        writeln!(stdout, "{:#}", tag)?;
+    }
+    Ok(())
+}
+
+fn dump(file: &Path, flags: &[&str]) -> Result<()> {
+    check_flags(flags, &["--json", "--typed"])?;
+    let (tag, _) = read_file(file, flags)?;
+    print(&tag, flags)
+}
+
+fn convert(input: &Path, output: &Path, flags: &[&str]) -> Result<()> {
+    let (mut tag, from) = read_file(input, flags)?;
+    let mut to = Format::of(output, flags);
+    if let Format::Binary(_) = to {
+        let compression = compression_flag(flags)?.unwrap_or(match from {
+            Format::Binary(compression) => compression,
+            _ => Compression::Gzip,
+        });
+        to = Format::Binary(compression);
+        // SNBT and natural JSON don't carry the root name
+        if tag.name().is_none() {
+            tag.set_name(Some(String::new()));
+        }
+    }
+    check_flags(flags, &["--compression=", "--typed"])?;
+    write_file(output, &tag, to)
+}
+
+fn get(file: &Path, path: &str, flags: &[&str]) -> Result<()> {
+    check_flags(flags, &["--json", "--typed"])?;
+    let path: NbtPath = path.parse()?;
+    let (tag, _) = read_file(file, flags)?;
+    match tag.get_path(&path) {
+        Some(value) => print(value, flags),
+        None => Err(format!("nothing at {}", path).into()),
+    }
+}
+
+fn set(file: &Path, path: &str, value: &str, flags: &[&str]) -> Result<()> {
+    check_flags(flags, &["--no-backup", "--typed"])?;
+    let path: NbtPath = path.parse()?;
+    let value: Tag = value.parse()?;
+    let (mut tag, format) = read_file(file, flags)?;
+    set_path(&mut tag, &path, value)?;
+
+    backup(file, flags)?;
+    // Write next to the file and rename over it, so a failed write leaves it intact
+    let tmp = with_suffix(file, ".tmp");
+    write_file(&tmp, &tag, format)?;
+    fs::rename(&tmp, file)?;
+    Ok(())
+}
+
+/// Replace the value at `path`, or add it as a new key of an existing compound
+fn set_path(tag: &mut Tag, path: &NbtPath, value: Tag) -> Result<()> {
+    let Some((parent, last)) = path.split_last() else {
+        return Err("cannot replace the root".into());
+    };
+    match (tag.get_path_mut(&parent), last) {
+        (Some(Tag::Compound { entries, .. }), PathSegment::Key(key)) => {
+            entries.insert(key.clone(), value.with_name(key.as_str()));
+        }
+        (Some(Tag::List { elements, .. }), PathSegment::Index(i)) if *i < elements.len() => {
+            elements[*i] = value;
+        }
+        _ => return Err(format!("nothing at {}", path).into()),
+    }
+    Ok(())
+}
+
+fn region_list(file: &Path, flags: &[&str]) -> Result<()> {
+    check_flags(flags, &[])?;
+    let mut region = Region::new(File::open(file)?)?;
+    let positions: Vec<_> = region.chunk_positions().collect();
+    let mut stdout = io::stdout().lock();
+    writeln!(
+        stdout,
+        "{:>2} {:>2} {:>6} {:>7} {:>8}  {:<11} saved",
+        "x", "z", "sector", "sectors", "bytes", "compression"
+    )?;
+    for (x, z) in positions {
+        match region.info(x as i32, z as i32) {
+            Ok(Some(info)) => writeln!(
+                stdout,
+                "{:>2} {:>2} {:>6} {:>7} {:>8}  {:<11} {}",
+                x,
+                z,
+                info.sector,
+                info.sectors,
+                info.length,
+                compression_name(&info),
+                format_time(info.timestamp)
+            )?,
+            Ok(None) => {}
+            Err(e) => writeln!(stdout, "{:>2} {:>2} unreadable: {}", x, z, e)?,
+        }
+    }
+    Ok(())
+}
+
+fn region_extract(file: &Path, (x, z): (&str, &str), output: &Path, flags: &[&str]) -> Result<()> {
+    let (x, z) = (coord(x)?, coord(z)?);
+    let mut to = Format::of(output, flags);
+    if let Format::Binary(_) = to {
+        let compression = compression_flag(flags)?.unwrap_or(Compression::Gzip);
+        to = Format::Binary(compression);
+    }
+    check_flags(flags, &["--compression=", "--typed"])?;
+    let mut region = Region::new(File::open(file)?)?;
+    match region.read_chunk(x, z)? {
+        Some(chunk) => write_file(output, &chunk, to),
+        None => Err(format!("no chunk at {}, {}", x, z).into()),
+    }
+}
+
+fn region_replace(file: &Path, (x, z): (&str, &str), input: &Path, flags: &[&str]) -> Result<()> {
+    check_flags(flags, &["--no-backup", "--typed"])?;
+    let (x, z) = (coord(x)?, coord(z)?);
+    let (mut chunk, _) = read_file(input, flags)?;
+    if chunk.name().is_none() {
+        chunk.set_name(Some(String::new()));
+    }
+    backup(file, flags)?;
+    edit_region(file)?.write_chunk(x, z, &chunk)?;
+    Ok(())
+}
+
+fn region_delete(file: &Path, coords: &[&str], flags: &[&str]) -> Result<()> {
+    check_flags(flags, &["--no-backup"])?;
+    let coords = coords
+        .chunks(2)
+        .map(|pair| Ok((coord(pair[0])?, coord(pair[1])?)))
+        .collect::<Result<Vec<_>>>()?;
+    backup(file, flags)?;
+    let mut region = edit_region(file)?;
+    for (x, z) in coords {
+        if !region.delete_chunk(x, z)? {
+            eprintln!("nbt: no chunk at {}, {}", x, z);
+        }
+    }
+    Ok(())
+}
+
+fn region_check(file: &Path, flags: &[&str]) -> Result<()> {
+    check_flags(flags, &[])?;
+    let mut region = Region::new(File::open(file)?)?;
+    let problems = region.check()?;
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    match problems.len() {
+        0 => Ok(()),
+        n => Err(format!("{} problem(s) found", n).into()),
+    }
+}
+
+fn coord(s: &str) -> Result<i32> {
+    s.parse()
+        .map_err(|_| format!("invalid chunk coordinate '{}'", s).into())
+}
+
+fn compression_flag(flags: &[&str]) -> Result<Option<Compression>> {
+    match flags.iter().find_map(|f| f.strip_prefix("--compression=")) {
+        Some("gzip") => Ok(Some(Compression::Gzip)),
+        Some("zlib") => Ok(Some(Compression::Zlib)),
+        Some("none") => Ok(Some(Compression::None)),
+        Some(other) => Err(format!("unknown compression '{}'", other).into()),
+        None => Ok(None),
+    }
+}
+
+fn compression_name(info: &ChunkInfo) -> String {
+    let name = match info.compression & 0x7f {
+        1 => "gzip".to_string(),
+        2 => "zlib".to_string(),
+        3 => "none".to_string(),
+        4 => "lz4".to_string(),
+        127 => "custom".to_string(),
+        other => format!("unknown {}", other),
+    };
+    if info.is_external() {
+        format!("{} (.mcc)", name)
+    } else {
+        name
+    }
+}
+
+/// Seconds since the epoch as a UTC `YYYY-MM-DD hh:mm:ss`
+fn format_time(secs: u32) -> String {
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil-from-days, counting in 400-year eras starting at 0000-03-01
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Copy `file`, if it exists, to `<file>.bak` unless `--no-backup` was given
+/// Open an existing region file for writing. Unlike [`Region::open`], a mistyped path
+/// is an error rather than a new empty file.
+fn edit_region(file: &Path) -> Result<Region<File>> {
+    let file = File::options().read(true).write(true).open(file)?;
+    Ok(Region::new(file)?)
+}
+
+fn backup(file: &Path, flags: &[&str]) -> Result<()> {
+    if !flags.contains(&"--no-backup") && file.exists() {
+        fs::copy(file, with_suffix(file, ".bak"))?;
+    }
+    Ok(())
+}
+
+/// `file` with `suffix` appended to its name
+fn with_suffix(file: &Path, suffix: &str) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Typed JSON with `--typed`, natural JSON otherwise
+fn json_mode(flags: &[&str]) -> JsonMode {
+    if flags.contains(&"--typed") {
+        JsonMode::Typed
+    } else {
+        JsonMode::Natural
+    }
+}
+
+/// Reject flags the command doesn't take; entries ending in `=` take a value
+fn check_flags(flags: &[&str], allowed: &[&str]) -> Result<()> {
+    for flag in flags {
+        let known = allowed.iter().any(|a| match a.strip_suffix('=') {
+            Some(_) => flag.starts_with(a),
+            None => flag == a,
+        });
+        if !known {
+            return Err(format!("unknown option '{}'", flag).into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set(tag: &mut Tag, path: &str, value: &str) -> Result<()> {
+        set_path(tag, &path.parse()?, value.parse()?)
+    }
+
+    #[test]
+    fn set_replaces_and_adds() {
+        let mut tag: Tag = "{Data:{Pos:[0.0d,1.0d],Difficulty:1b}}".parse().unwrap();
+        set(&mut tag, "Data.Difficulty", "2b").unwrap();
+        set(&mut tag, "Data.Pos[1]", "64.0d").unwrap();
+        set(&mut tag, "Data.New", "\"x\"").unwrap();
+        let expected: Tag = "{Data:{Pos:[0.0d,64.0d],Difficulty:2b,New:\"x\"}}"
+            .parse()
+            .unwrap();
+        assert_eq!(tag, expected);
+        assert_eq!(tag["Data"]["New"].name(), Some("New"));
+        let pos = tag.get_path(&"Data.Pos[1]".parse().unwrap()).unwrap();
+        assert_eq!(pos.as_double(), Some(64.0));
+    }
+
+    #[test]
+    fn set_rejects_missing_parents() {
+        let mut tag: Tag = "{Data:{Pos:[0.0d]}}".parse().unwrap();
+        let before = tag.clone();
+        assert!(set(&mut tag, "Data.Pos[1]", "1.0d").is_err());
+        assert!(set(&mut tag, "Missing.Key", "1b").is_err());
+        assert!(set(&mut tag, "Data.Pos.Key", "1b").is_err());
+        assert_eq!(tag, before);
+    }
+
+    #[test]
+    fn mixed_lists_written_back_unchanged() {
+        // `{l: [1, "a"]}` as written by 1.21.5
+        #[rustfmt::skip]
+        let vanilla = [
+            10, 0, 0,
+            9, 0, 1, b'l', 10, 0, 0, 0, 2,
+            3, 0, 0, 0, 0, 0, 1, 0,
+            8, 0, 0, 0, 1, b'a', 0,
+            0,
+        ];
+        let dir = std::env::temp_dir().join(format!("nbt-cli-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("mixed.nbt");
+        fs::write(&file, vanilla).unwrap();
+
+        let (tag, format) = read_file(&file, &[]).unwrap();
+        assert_eq!(format, Format::Binary(Compression::None));
+        assert!(tag["l"].is_mixed_list());
+        write_file(&file, &tag, format).unwrap();
+        let written = fs::read(&file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, vanilla);
+    }
+
+    #[test]
+    fn region_edits_need_an_existing_file() {
+        let dir = std::env::temp_dir().join(format!("nbt-cli-region-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("r.0.0.mca");
+        let input = dir.join("chunk.nbt");
+        write_file(
+            &input,
+            &Tag::new_compound(""),
+            Format::Binary(Compression::None),
+        )
+        .unwrap();
+
+        assert!(region_delete(&file, &["0", "0"], &["--no-backup"]).is_err());
+        assert!(region_replace(&file, ("0", "0"), &input, &["--no-backup"]).is_err());
+        let created = file.exists();
+
+        fs::write(&file, []).unwrap();
+        region_replace(&file, ("0", "0"), &input, &["--no-backup"]).unwrap();
+        region_delete(&file, &["0", "0"], &["--no-backup"]).unwrap();
+        let left = Region::new(File::open(&file).unwrap())
+            .unwrap()
+            .contains(0, 0);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!created);
+        assert!(!left);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+};
+
+use crate::Tag;
+
+impl Tag {
+    /// Total equality: floats compare by bit pattern (so NaN equals itself), compound
+    /// entries compare regardless of order, and tag names are ignored.
+    pub fn canonical_eq(&self, other: &Tag) -> bool {
+        match (self, other) {
+            (Tag::End, Tag::End) => true,
+            (Tag::Byte { value: x, .. }, Tag::Byte { value: y, .. }) => x == y,
+            (Tag::Short { value: x, .. }, Tag::Short { value: y, .. }) => x == y,
+            (Tag::Int { value: x, .. }, Tag::Int { value: y, .. }) => x == y,
+            (Tag::Long { value: x, .. }, Tag::Long { value: y, .. }) => x == y,
+            (Tag::Float { value: x, .. }, Tag::Float { value: y, .. }) => {
+                x.to_bits() == y.to_bits()
+            }
+            (Tag::Double { value: x, .. }, Tag::Double { value: y, .. }) => {
+                x.to_bits() == y.to_bits()
+            }
+            (Tag::ByteArray { value: x, .. }, Tag::ByteArray { value: y, .. }) => x == y,
+            (Tag::String { value: x, .. }, Tag::String { value: y, .. }) => x == y,
+            (Tag::IntArray { value: x, .. }, Tag::IntArray { value: y, .. }) => x == y,
+            (Tag::LongArray { value: x, .. }, Tag::LongArray { value: y, .. }) => x == y,
+            // Equal elements make `element_id` equal too, except for empty lists, which
+            // are all End lists once written
+            (Tag::List { elements: x, .. }, Tag::List { elements: y, .. }) => {
+                x.len() == y.len() && x.iter().zip(y).all(|(x, y)| x.canonical_eq(y))
+            }
+            (Tag::Compound { entries: x, .. }, Tag::Compound { entries: y, .. }) => {
+                x.len() == y.len()
+                    && x.iter()
+                        .all(|(k, v)| y.get(k).is_some_and(|w| v.canonical_eq(w)))
+            }
+            _ => false,
+        }
+    }
+
+    /// Hash consistent with [`Tag::canonical_eq`]. Compound keys are hashed in sorted
+    /// order, so equal compounds hash the same whatever their insertion order.
+    pub fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.id());
+        match self {
+            Tag::End => {}
+            Tag::Byte { value, .. } => value.hash(state),
+            Tag::Short { value, .. } => value.hash(state),
+            Tag::Int { value, .. } => value.hash(state),
+            Tag::Long { value, .. } => value.hash(state),
+            Tag::Float { value, .. } => value.to_bits().hash(state),
+            Tag::Double { value, .. } => value.to_bits().hash(state),
+            Tag::ByteArray { value, .. } => value.hash(state),
+            Tag::String { value, .. } => value.hash(state),
+            Tag::IntArray { value, .. } => value.hash(state),
+            Tag::LongArray { value, .. } => value.hash(state),
+            Tag::List { elements, .. } => {
+                state.write_usize(elements.len());
+                for elem in elements {
+                    elem.canonical_hash(state);
+                }
+            }
+            Tag::Compound { entries, .. } => {
+                let mut keys: Vec<&String> = entries.keys().collect();
+                keys.sort();
+                state.write_usize(keys.len());
+                for key in keys {
+                    key.hash(state);
+                    entries[key].canonical_hash(state);
+                }
+            }
+        }
+    }
+}
+
+/// Wrapper giving a `Tag` (or `&Tag`) `Eq` and `Hash` through [`Tag::canonical_eq`] and
+/// [`Tag::canonical_hash`], so tags can be used as `HashMap` keys or deduplicated.
+#[derive(Clone, Debug)]
+pub struct Canonical<T: Borrow<Tag> = Tag>(pub T);
+
+impl<T: Borrow<Tag>> Canonical<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Borrow<Tag>> PartialEq for Canonical<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.borrow().canonical_eq(other.0.borrow())
+    }
+}
+
+impl<T: Borrow<Tag>> Eq for Canonical<T> {}
+
+impl<T: Borrow<Tag>> Hash for Canonical<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.borrow().canonical_hash(state)
+    }
+}
+
+impl From<Tag> for Canonical {
+    fn from(tag: Tag) -> Self {
+        Canonical(tag)
+    }
+}
@@ -0,0 +1,554 @@
+//! Views over chunk NBT that decode the packed formats in place
+
+use crate::{
+    FromNbtError, Tag,
+    packed::{Layout, PackedArray, Packing, ceil_log2},
+};
+
+/// Where a section keeps one palette plus its packed indices
+#[derive(Clone, Copy)]
+struct Container {
+    /// Compound inside the section holding both, or `None` for the section itself
+    holder: Option<&'static str>,
+    palette: &'static str,
+    data: &'static str,
+    len: usize,
+    /// Narrowest width once there is any data
+    min_bits: u32,
+    /// Whether `data` is left out when the palette has a single entry
+    omit_single: bool,
+}
+
+/// 1.18+ block states, 16x16x16
+const BLOCKS: Container = Container {
+    holder: Some("block_states"),
+    palette: "palette",
+    data: "data",
+    len: 4096,
+    min_bits: 4,
+    omit_single: true,
+};
+
+/// 1.13 to 1.17 block states, kept directly in the section
+const LEGACY_BLOCKS: Container = Container {
+    holder: None,
+    palette: "Palette",
+    data: "BlockStates",
+    len: 4096,
+    min_bits: 4,
+    omit_single: false,
+};
+
+/// 1.18+ biomes, one per 4x4x4 cell
+const BIOMES: Container = Container {
+    holder: Some("biomes"),
+    palette: "palette",
+    data: "data",
+    len: 64,
+    min_bits: 0,
+    omit_single: true,
+};
+
+impl Container {
+    fn holder(self, section: &Tag) -> Option<&Tag> {
+        match self.holder {
+            Some(key) => section.get(key),
+            None => Some(section),
+        }
+    }
+
+    fn holder_mut(self, section: &mut Tag) -> &mut Tag {
+        match self.holder {
+            Some(key) => &mut section[key],
+            None => section,
+        }
+    }
+
+    fn palette(self, section: &Tag) -> &[Tag] {
+        match self.holder(section).and_then(|h| h.get(self.palette)) {
+            Some(Tag::List { elements, .. }) => elements,
+            _ => &[],
+        }
+    }
+
+    fn data(self, section: &Tag) -> Option<&[i64]> {
+        match self.holder(section).and_then(|h| h.get(self.data)) {
+            Some(Tag::LongArray { value, .. }) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn bits_for(self, palette_len: usize) -> u32 {
+        if self.omit_single && palette_len <= 1 {
+            0
+        } else {
+            ceil_log2(palette_len).max(self.min_bits)
+        }
+    }
+
+    fn packing(self, section: &Tag, layout: Layout) -> Packing {
+        Packing {
+            bits: self.bits_for(self.palette(section).len()),
+            layout,
+        }
+    }
+
+    fn check(self, section: &Tag, layout: Layout) -> Result<(), FromNbtError> {
+        let in_holder = |e: FromNbtError| match self.holder {
+            Some(key) => e.at_key(key),
+            None => e,
+        };
+        let palette = self.palette(section);
+        if palette.is_empty() {
+            return Err(in_holder(
+                FromNbtError::custom("empty palette").at_key(self.palette),
+            ));
+        }
+        // Without data every entry is the first in the palette, so there can only be one
+        let Some(data) = self.data(section) else {
+            if palette.len() > 1 {
+                return Err(in_holder(FromNbtError::missing_field(self.data)));
+            }
+            return Ok(());
+        };
+        let found = data.len();
+        let expected = self.packing(section, layout).longs_needed(self.len);
+        if found != expected {
+            return Err(in_holder(
+                FromNbtError::custom(format!(
+                    "expected {} longs for a palette of {}, found {}",
+                    expected,
+                    palette.len(),
+                    found
+                ))
+                .at_key(self.data),
+            ));
+        }
+        let packing = self.packing(section, layout);
+        if let Some(bad) = self
+            .indices(section, packing)
+            .into_iter()
+            .find(|&i| i as usize >= palette.len())
+        {
+            return Err(in_holder(
+                FromNbtError::custom(format!(
+                    "index {} is outside the palette of {}",
+                    bad,
+                    palette.len()
+                ))
+                .at_key(self.data),
+            ));
+        }
+        Ok(())
+    }
+
+    fn index(self, section: &Tag, layout: Layout, i: usize) -> usize {
+        match self.data(section) {
+            Some(data) => self.packing(section, layout).get(data, i) as usize,
+            None => 0,
+        }
+    }
+
+    fn indices(self, section: &Tag, packing: Packing) -> Vec<u64> {
+        match self.data(section) {
+            Some(data) => (0..self.len).map(|i| packing.get(data, i)).collect(),
+            None => vec![0; self.len],
+        }
+    }
+
+    /// Point entry `i` at `value`, adding it to the palette and widening the data as needed
+    fn set(self, section: &mut Tag, layout: Layout, i: usize, mut value: Tag) {
+        let palette = self.palette(section);
+        let index = match palette.iter().position(|p| p.canonical_eq(&value)) {
+            Some(index) => index,
+            None => {
+                value.set_name(None);
+                let index = palette.len();
+                let old = self.packing(section, layout);
+                self.holder_mut(section)[self.palette].push(value);
+                let bits = self.bits_for(index + 1);
+                if bits != old.bits || self.data(section).is_none() {
+                    let indices = self.indices(section, old);
+                    self.store(section, layout, &indices, bits);
+                }
+                index
+            }
+        };
+        let packing = self.packing(section, layout);
+        // No data is left only for a single-entry palette, where every entry is `value`
+        if let Tag::Compound { entries, .. } = self.holder_mut(section)
+            && let Some(Tag::LongArray { value, .. }) = entries.get_mut(self.data)
+        {
+            packing.set(value, i, index as u64);
+        }
+    }
+
+    /// Drop unused palette entries and repack at the smallest width
+    fn compact(self, section: &mut Tag, layout: Layout) {
+        let indices = self.indices(section, self.packing(section, layout));
+        let palette = self.palette(section);
+        let mut used = vec![false; palette.len()];
+        for &i in &indices {
+            used[i as usize] = true;
+        }
+        let mut remap = vec![0; palette.len()];
+        let mut kept = Vec::new();
+        for (old, value) in palette.iter().enumerate() {
+            if used[old] {
+                remap[old] = kept.len() as u64;
+                kept.push(value.clone());
+            }
+        }
+        let indices: Vec<u64> = indices.iter().map(|&i| remap[i as usize]).collect();
+        let bits = self.bits_for(kept.len());
+        self.holder_mut(section)
+            .insert(self.palette.into(), Tag::from(kept).with_name(self.palette));
+        self.store(section, layout, &indices, bits);
+    }
+
+    /// Replace the packed data with `indices` at `bits` per entry
+    fn store(self, section: &mut Tag, layout: Layout, indices: &[u64], bits: u32) {
+        let holder = self.holder_mut(section);
+        if bits == 0 {
+            if let Tag::Compound { entries, .. } = holder {
+                entries.remove(self.data);
+            }
+            return;
+        }
+        let data = PackedArray::from_values(indices, bits, layout).into_longs();
+        holder.insert(self.data.into(), Tag::new_long_array(self.data, data));
+    }
+}
+
+/// Read-write view of one chunk section's blocks and biomes. Changes are written
+/// straight through to the underlying tag.
+///
+/// Block states are palette compounds like `{Name: "minecraft:oak_stairs", Properties:
+/// {facing: "east", ...}}`. Block coordinates are relative to the section, each in
+/// `0..16`; biome coordinates address 4x4x4 cells, each in `0..4`.
+pub struct ChunkSection<'a> {
+    section: &'a mut Tag,
+    blocks: Container,
+    layout: Layout,
+}
+
+impl<'a> ChunkSection<'a> {
+    /// View a section of a chunk saved at `data_version`, which decides whether packed
+    /// entries span longs. Fails if the packed data doesn't match its palette.
+    pub fn new(section: &'a mut Tag, data_version: i32) -> Result<Self, FromNbtError> {
+        let layout = Layout::for_data_version(data_version);
+        let blocks = section_blocks(section)?;
+        blocks.check(section, layout)?;
+        if section.get("biomes").is_some() {
+            BIOMES.check(section, layout)?;
+        }
+        Ok(ChunkSection {
+            section,
+            blocks,
+            layout,
+        })
+    }
+
+    /// The section's `Y` index
+    pub fn y(&self) -> Option<i8> {
+        i8::try_from(self.section.get("Y")?).ok()
+    }
+
+    /// The block state palette
+    pub fn palette(&self) -> &[Tag] {
+        self.blocks.palette(self.section)
+    }
+
+    /// Palette index of the block at `x, y, z`
+    pub fn palette_index(&self, x: usize, y: usize, z: usize) -> usize {
+        self.blocks
+            .index(self.section, self.layout, block_index(x, y, z))
+    }
+
+    /// The block state at `x, y, z`
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> &Tag {
+        &self.palette()[self.palette_index(x, y, z)]
+    }
+
+    /// Set the block at `x, y, z`, adding `state` to the palette and widening the packed
+    /// data if needed. Unused palette entries are kept; see [`ChunkSection::compact`].
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, state: Tag) {
+        self.blocks
+            .set(self.section, self.layout, block_index(x, y, z), state)
+    }
+
+    /// Drop block and biome palette entries nothing uses and repack at the smallest width
+    pub fn compact(&mut self) {
+        self.blocks.compact(self.section, self.layout);
+        if self.section.get("biomes").is_some() {
+            BIOMES.compact(self.section, self.layout);
+        }
+    }
+
+    /// The biome of cell `x, y, z`, or `None` if the section predates per-section
+    /// biomes (1.18)
+    pub fn get_biome(&self, x: usize, y: usize, z: usize) -> Option<&str> {
+        self.section.get("biomes")?;
+        let palette = BIOMES.palette(self.section);
+        let index = BIOMES.index(self.section, self.layout, biome_index(x, y, z));
+        <&str>::try_from(&palette[index]).ok()
+    }
+
+    /// Set the biome of cell `x, y, z`. A section without biomes gets a palette filled
+    /// with `biome` first.
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: &str) {
+        if self.section.get("biomes").is_none() {
+            let mut biomes = Tag::new_compound("biomes");
+            biomes.insert("palette".into(), Tag::new_list("palette", 8, Vec::new()));
+            self.section.insert("biomes".into(), biomes);
+        }
+        BIOMES.set(
+            self.section,
+            self.layout,
+            biome_index(x, y, z),
+            Tag::from(biome),
+        )
+    }
+
+    /// All 64 biome cells in index order (`y << 4 | z << 2 | x`)
+    pub fn biomes(&self) -> Option<Vec<&str>> {
+        (0..4)
+            .flat_map(|y| (0..4).flat_map(move |z| (0..4).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| self.get_biome(x, y, z))
+            .collect()
+    }
+}
+
+/// Which block container a section uses
+fn section_blocks(section: &Tag) -> Result<Container, FromNbtError> {
+    match section {
+        Tag::Compound { entries, .. } if entries.contains_key("block_states") => Ok(BLOCKS),
+        Tag::Compound { entries, .. } if entries.contains_key("Palette") => Ok(LEGACY_BLOCKS),
+        Tag::Compound { .. } => Err(FromNbtError::missing_field("block_states")),
+        other => Err(FromNbtError::wrong_type(10, other)),
+    }
+}
+
+/// Index of `x, y, z` within a section: YZX order
+pub(crate) fn block_index(x: usize, y: usize, z: usize) -> usize {
+    assert!(
+        x < 16 && y < 16 && z < 16,
+        "({}, {}, {}) is outside a section",
+        x,
+        y,
+        z
+    );
+    y << 8 | z << 4 | x
+}
+
+fn biome_index(x: usize, y: usize, z: usize) -> usize {
+    assert!(
+        x < 4 && y < 4 && z < 4,
+        "({}, {}, {}) is outside a section's biome cells",
+        x,
+        y,
+        z
+    );
+    y << 4 | z << 2 | x
+}
+
+/// The standard heightmaps in a chunk's `Heightmaps` compound
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum HeightmapKind {
+    /// Highest non-air block
+    WorldSurface,
+    /// Highest block that blocks motion
+    OceanFloor,
+    /// Highest block that blocks motion or holds a fluid
+    MotionBlocking,
+    /// As `MotionBlocking`, ignoring leaves
+    MotionBlockingNoLeaves,
+    /// `WorldSurface` while the chunk is still generating
+    WorldSurfaceWg,
+    /// `OceanFloor` while the chunk is still generating
+    OceanFloorWg,
+}
+
+impl HeightmapKind {
+    /// The heightmaps vanilla saves for a fully generated chunk
+    pub const SAVED: [HeightmapKind; 4] = [
+        HeightmapKind::MotionBlocking,
+        HeightmapKind::MotionBlockingNoLeaves,
+        HeightmapKind::OceanFloor,
+        HeightmapKind::WorldSurface,
+    ];
+
+    /// Key in the `Heightmaps` compound
+    pub fn key(self) -> &'static str {
+        match self {
+            HeightmapKind::WorldSurface => "WORLD_SURFACE",
+            HeightmapKind::OceanFloor => "OCEAN_FLOOR",
+            HeightmapKind::MotionBlocking => "MOTION_BLOCKING",
+            HeightmapKind::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+            HeightmapKind::WorldSurfaceWg => "WORLD_SURFACE_WG",
+            HeightmapKind::OceanFloorWg => "OCEAN_FLOOR_WG",
+        }
+    }
+}
+
+/// One heightmap: for each of the 256 columns, one above the highest matching block
+/// counted from the bottom of the world, or 0 for an empty column
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Heightmap {
+    /// Number of blocks between the world's lowest and highest y
+    pub world_height: u32,
+    values: Vec<u32>,
+}
+
+impl Heightmap {
+    /// An all-zero heightmap
+    pub fn new(world_height: u32) -> Self {
+        Heightmap {
+            world_height,
+            values: vec![0; 256],
+        }
+    }
+
+    /// Bits per column: 9 for both the old 256 and the 1.18 384 block heights
+    pub fn bits(world_height: u32) -> u32 {
+        ceil_log2(world_height as usize + 1)
+    }
+
+    /// Decode a heightmap LongArray
+    pub fn decode(tag: &Tag, world_height: u32, layout: Layout) -> Result<Self, FromNbtError> {
+        let longs = <&[i64]>::try_from(tag)?;
+        let packed =
+            PackedArray::from_longs(longs.to_vec(), Heightmap::bits(world_height), 256, layout)
+                .map_err(FromNbtError::custom)?;
+        Ok(Heightmap {
+            world_height,
+            values: packed.iter().map(|v| v as u32).collect(),
+        })
+    }
+
+    /// Encode as an unnamed LongArray
+    pub fn encode(&self, layout: Layout) -> Tag {
+        let values: Vec<u64> = self.values.iter().map(|&v| v as u64).collect();
+        let bits = Heightmap::bits(self.world_height);
+        Tag::from(PackedArray::from_values(&values, bits, layout).into_longs())
+    }
+
+    /// Column `x, z`, each in `0..16`
+    pub fn get(&self, x: usize, z: usize) -> u32 {
+        self.values[column_index(x, z)]
+    }
+
+    pub fn set(&mut self, x: usize, z: usize, value: u32) {
+        assert!(
+            value <= self.world_height,
+            "height {} above the world",
+            value
+        );
+        self.values[column_index(x, z)] = value;
+    }
+
+    /// The y just above the highest matching block in column `x, z`, given the world's
+    /// lowest y (-64 for a 1.18+ overworld)
+    pub fn top_y(&self, x: usize, z: usize, min_y: i32) -> i32 {
+        min_y + self.get(x, z) as i32
+    }
+}
+
+fn column_index(x: usize, z: usize) -> usize {
+    assert!(x < 16 && z < 16, "({}, {}) is outside a chunk", x, z);
+    z << 4 | x
+}
+
+/// Recompute `kinds` in a chunk's `Heightmaps` from its block data. The chunk may be in
+/// the 1.18+ layout or still wrapped in `Level`. `blocks(kind, state)` decides whether a
+/// palette entry counts for a heightmap; this crate has no block registry to know which
+/// blocks are solid, leaves or waterlogged. Every section is checked before anything is
+/// computed, so a corrupt one fails the call and leaves the chunk unchanged.
+pub fn recompute_heightmaps(
+    chunk: &mut Tag,
+    data_version: i32,
+    min_y: i32,
+    world_height: u32,
+    kinds: &[HeightmapKind],
+    blocks: impl Fn(HeightmapKind, &Tag) -> bool,
+) -> Result<(), FromNbtError> {
+    let layout = Layout::for_data_version(data_version);
+    let level = if chunk.get("Level").is_some() {
+        &mut chunk["Level"]
+    } else {
+        chunk
+    };
+    let sections_key = if level.get("sections").is_some() {
+        "sections"
+    } else {
+        "Sections"
+    };
+    let mut sections = Vec::new();
+    if let Some(Tag::List { elements, .. }) = level.get(sections_key) {
+        for (i, section) in elements.iter().enumerate() {
+            let Ok(container) = section_blocks(section) else {
+                // Sections without blocks only hold light data
+                continue;
+            };
+            let at = |e: FromNbtError| e.at_index(i).at_key(sections_key);
+            container.check(section, layout).map_err(at)?;
+            let y = i8::try_from(&section["Y"])
+                .map_err(|e| FromNbtError::from(e).at_key("Y"))
+                .map_err(at)?;
+            sections.push((y as i32, container, section));
+        }
+    }
+    sections.sort_by_key(|&(y, ..)| std::cmp::Reverse(y));
+
+    let mut maps = Vec::new();
+    for &kind in kinds {
+        let mut map = Heightmap::new(world_height);
+        let mut remaining = 256;
+        'sections: for &(section_y, container, section) in &sections {
+            let counts: Vec<bool> = container
+                .palette(section)
+                .iter()
+                .map(|state| blocks(kind, state))
+                .collect();
+            if !counts.contains(&true) {
+                continue;
+            }
+            for y in (0..16).rev() {
+                let height = section_y * 16 + y as i32 - min_y + 1;
+                if height <= 0 || height > world_height as i32 {
+                    continue;
+                }
+                for z in 0..16 {
+                    for x in 0..16 {
+                        if map.get(x, z) != 0 {
+                            continue;
+                        }
+                        let index = container.index(section, layout, block_index(x, y, z));
+                        if counts[index] {
+                            map.set(x, z, height as u32);
+                            remaining -= 1;
+                            if remaining == 0 {
+                                break 'sections;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        maps.push((kind, map));
+    }
+
+    let heightmaps = level.get_or_insert_compound("Heightmaps");
+    for (kind, map) in maps {
+        heightmaps.insert(kind.key().into(), map.encode(layout).with_name(kind.key()));
+    }
+    Ok(())
+}
+
+/// Whether a block state is one of the air blocks, the only ones `WORLD_SURFACE` skips
+pub fn is_air(state: &Tag) -> bool {
+    matches!(
+        state.get("Name").and_then(|n| <&str>::try_from(n).ok()),
+        Some("minecraft:air" | "minecraft:cave_air" | "minecraft:void_air")
+    )
+}
@@ -0,0 +1,793 @@
+//! Composable codecs in the style of Mojang's DataFixerUpper. A [`Codec`] both encodes
+//! a value to a [`Tag`] and decodes it back, and combinators build codecs for bigger
+//! structures out of smaller ones:
+//!
+//! ```
+//! use nbt::codec::{self, Codec, MapCodec, record};
+//!
+//! #[derive(Clone, Debug, PartialEq)]
+//! struct Effect {
+//!     id: String,
+//!     amplifier: i8,
+//!     duration: i32,
+//! }
+//!
+//! let effect = record((
+//!     codec::STRING.field_of("id").for_getter(|e: &Effect| e.id.clone()),
+//!     codec::BYTE.optional_field_or("amplifier", 0).for_getter(|e: &Effect| e.amplifier),
+//!     codec::INT.field_of("duration").for_getter(|e: &Effect| e.duration),
+//! ))
+//! .apply(|(id, amplifier, duration)| Effect { id, amplifier, duration });
+//!
+//! let speed = Effect { id: "minecraft:speed".into(), amplifier: 0, duration: 200 };
+//! let tag = effect.encode(&speed).into_result().unwrap();
+//! assert!(tag.get("amplifier").is_none());
+//! assert_eq!(effect.decode(&tag).into_result(), Ok(speed));
+//! ```
+//!
+//! Like vanilla's `DataResult`, failures carry a message and, where possible, a partial
+//! result: a list with one bad element still decodes the others.
+
+use std::{collections::HashMap, fmt, marker::PhantomData, sync::Arc};
+
+use crate::Tag;
+
+/// Outcome of encoding or decoding: a value, or an error message with an optional
+/// partial value salvaged from the input
+#[derive(PartialEq, Clone, Debug)]
+pub enum DataResult<T> {
+    Success(T),
+    Error { message: String, partial: Option<T> },
+}
+
+impl<T> DataResult<T> {
+    pub fn success(value: T) -> Self {
+        DataResult::Success(value)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        DataResult::Error {
+            message: message.into(),
+            partial: None,
+        }
+    }
+
+    pub fn error_partial(message: impl Into<String>, partial: T) -> Self {
+        DataResult::Error {
+            message: message.into(),
+            partial: Some(partial),
+        }
+    }
+
+    /// Build from an optional value and the error messages collected while producing it
+    fn from_parts(value: Option<T>, errors: Vec<String>) -> Self {
+        match (value, errors.is_empty()) {
+            (Some(v), true) => DataResult::Success(v),
+            (partial, _) => DataResult::Error {
+                message: errors.join("; "),
+                partial,
+            },
+        }
+    }
+
+    /// Split into the (possibly partial) value, pushing any error message onto `errors`
+    fn take(self, errors: &mut Vec<String>) -> Option<T> {
+        match self {
+            DataResult::Success(v) => Some(v),
+            DataResult::Error { message, partial } => {
+                errors.push(message);
+                partial
+            }
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, DataResult::Success(_))
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> DataResult<U> {
+        match self {
+            DataResult::Success(v) => DataResult::Success(f(v)),
+            DataResult::Error { message, partial } => DataResult::Error {
+                message,
+                partial: partial.map(f),
+            },
+        }
+    }
+
+    /// Chain another fallible step; a partial value is fed through and errors accumulate
+    pub fn flat_map<U>(self, f: impl FnOnce(T) -> DataResult<U>) -> DataResult<U> {
+        match self {
+            DataResult::Success(v) => f(v),
+            DataResult::Error { message, partial } => {
+                let mut errors = vec![message];
+                let partial = partial.and_then(|v| f(v).take(&mut errors));
+                DataResult::from_parts(partial, errors)
+            }
+        }
+    }
+
+    pub fn map_error(self, f: impl FnOnce(String) -> String) -> Self {
+        match self {
+            DataResult::Error { message, partial } => DataResult::Error {
+                message: f(message),
+                partial,
+            },
+            ok => ok,
+        }
+    }
+
+    /// The value if fully successful
+    pub fn into_result(self) -> Result<T, String> {
+        match self {
+            DataResult::Success(v) => Ok(v),
+            DataResult::Error { message, .. } => Err(message),
+        }
+    }
+
+    /// The value or the partial value, reporting the error message to `on_error`
+    pub fn result_or_partial(self, on_error: impl FnOnce(&str)) -> Option<T> {
+        match self {
+            DataResult::Success(v) => Some(v),
+            DataResult::Error { message, partial } => {
+                on_error(&message);
+                partial
+            }
+        }
+    }
+
+    /// Turn an error with a partial value into a success, reporting the message
+    pub fn promote_partial(self, on_error: impl FnOnce(&str)) -> Self {
+        match self {
+            DataResult::Error {
+                message,
+                partial: Some(v),
+            } => {
+                on_error(&message);
+                DataResult::Success(v)
+            }
+            other => other,
+        }
+    }
+}
+
+/// Two-way conversion between `A` and a [`Tag`]
+pub trait Codec<A> {
+    fn encode(&self, value: &A) -> DataResult<Tag>;
+    fn decode(&self, tag: &Tag) -> DataResult<A>;
+
+    /// A codec for `B` through infallible conversions to and from `A`
+    fn xmap<B, F, G>(self, to: F, from: G) -> XMap<Self, A, F, G>
+    where
+        Self: Sized,
+        F: Fn(A) -> B,
+        G: Fn(&B) -> A,
+    {
+        XMap {
+            codec: self,
+            to,
+            from,
+            _a: PhantomData,
+        }
+    }
+
+    /// A codec for `B` through fallible conversions to and from `A`
+    fn flat_xmap<B, F, G>(self, to: F, from: G) -> FlatXMap<Self, A, F, G>
+    where
+        Self: Sized,
+        F: Fn(A) -> DataResult<B>,
+        G: Fn(&B) -> DataResult<A>,
+    {
+        FlatXMap {
+            codec: self,
+            to,
+            from,
+            _a: PhantomData,
+        }
+    }
+
+    /// Reject values failing `check` in both directions
+    fn validate<F>(self, check: F) -> Validate<Self, F>
+    where
+        Self: Sized,
+        F: Fn(&A) -> Result<(), String>,
+    {
+        Validate { codec: self, check }
+    }
+
+    /// A List of values
+    fn list_of(self) -> ListOf<Self>
+    where
+        Self: Sized,
+    {
+        ListOf(self)
+    }
+
+    /// A required compound entry
+    fn field_of(self, name: &str) -> FieldOf<Self>
+    where
+        Self: Sized,
+    {
+        FieldOf {
+            name: name.to_string(),
+            codec: self,
+        }
+    }
+
+    /// An optional compound entry, `None` when absent
+    fn optional_field_of(self, name: &str) -> OptionalFieldOf<Self>
+    where
+        Self: Sized,
+    {
+        OptionalFieldOf {
+            name: name.to_string(),
+            codec: self,
+        }
+    }
+
+    /// An optional compound entry with a default, omitted on encode when equal to it
+    fn optional_field_or(self, name: &str, default: A) -> OptionalFieldOr<Self, A>
+    where
+        Self: Sized,
+        A: Clone + PartialEq,
+    {
+        OptionalFieldOr {
+            name: name.to_string(),
+            codec: self,
+            default,
+        }
+    }
+
+    /// A compound whose `key` entry, decoded by this codec, selects the codec for the
+    /// remaining entries, like vanilla's `Codec.dispatch`
+    fn dispatch<V, F, G>(
+        self,
+        key: &str,
+        type_of: F,
+        codec_of: G,
+    ) -> CompoundCodec<Dispatch<Self, F, G>>
+    where
+        Self: Sized,
+        F: Fn(&V) -> A,
+        G: Fn(&A) -> DataResult<Box<dyn MapCodec<V>>>,
+    {
+        CompoundCodec(Dispatch {
+            key: key.to_string(),
+            type_codec: self,
+            type_of,
+            codec_of,
+        })
+    }
+}
+
+/// Encodes into and decodes from the entries of a compound, so several can share one
+/// compound. [`MapCodec::codec`] turns one into a standalone [`Codec`].
+pub trait MapCodec<A> {
+    fn encode_into(&self, value: &A, entries: &mut HashMap<String, Tag>) -> DataResult<()>;
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<A>;
+
+    fn codec(self) -> CompoundCodec<Self>
+    where
+        Self: Sized,
+    {
+        CompoundCodec(self)
+    }
+
+    /// Bind this field to the part of `O` it stores, for use in [`record`]
+    fn for_getter<O, G>(self, getter: G) -> RecordField<Self, G, A>
+    where
+        Self: Sized,
+        G: Fn(&O) -> A,
+    {
+        RecordField {
+            codec: self,
+            getter,
+            _a: PhantomData,
+        }
+    }
+
+    fn xmap<B, F, G>(self, to: F, from: G) -> XMap<Self, A, F, G>
+    where
+        Self: Sized,
+        F: Fn(A) -> B,
+        G: Fn(&B) -> A,
+    {
+        XMap {
+            codec: self,
+            to,
+            from,
+            _a: PhantomData,
+        }
+    }
+}
+
+macro_rules! forward_codec {
+    ($($ptr:ty),*) => {$(
+        impl<A, C: Codec<A> + ?Sized> Codec<A> for $ptr {
+            fn encode(&self, value: &A) -> DataResult<Tag> {
+                (**self).encode(value)
+            }
+            fn decode(&self, tag: &Tag) -> DataResult<A> {
+                (**self).decode(tag)
+            }
+        }
+        impl<A, C: MapCodec<A> + ?Sized> MapCodec<A> for $ptr {
+            fn encode_into(&self, value: &A, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+                (**self).encode_into(value, entries)
+            }
+            fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<A> {
+                (**self).decode_from(entries)
+            }
+        }
+    )*};
+}
+forward_codec!(&C, Box<C>, Arc<C>);
+
+// --- primitives ------------------------------------------------------------------
+
+/// Codec for a primitive Rust type; see the constants such as [`INT`]
+pub struct Primitive<T>(PhantomData<fn() -> T>);
+
+impl<T> Clone for Primitive<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Primitive<T> {}
+
+pub const BOOL: Primitive<bool> = Primitive(PhantomData);
+pub const BYTE: Primitive<i8> = Primitive(PhantomData);
+pub const SHORT: Primitive<i16> = Primitive(PhantomData);
+pub const INT: Primitive<i32> = Primitive(PhantomData);
+pub const LONG: Primitive<i64> = Primitive(PhantomData);
+pub const FLOAT: Primitive<f32> = Primitive(PhantomData);
+pub const DOUBLE: Primitive<f64> = Primitive(PhantomData);
+pub const STRING: Primitive<String> = Primitive(PhantomData);
+pub const BYTE_BUFFER: Primitive<Vec<u8>> = Primitive(PhantomData);
+pub const INT_STREAM: Primitive<Vec<i32>> = Primitive(PhantomData);
+pub const LONG_STREAM: Primitive<Vec<i64>> = Primitive(PhantomData);
+/// Passes tags through untouched
+pub const PASSTHROUGH: Primitive<Tag> = Primitive(PhantomData);
+
+/// A numeric payload widened without loss: integers as i64, floats as f64
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+/// Like vanilla's NbtOps, numeric codecs accept any numeric tag
+fn number(tag: &Tag) -> Option<Number> {
+    match tag {
+        Tag::Byte { value, .. } => Some(Number::Int(*value as i64)),
+        Tag::Short { value, .. } => Some(Number::Int(*value as i64)),
+        Tag::Int { value, .. } => Some(Number::Int(*value as i64)),
+        Tag::Long { value, .. } => Some(Number::Int(*value)),
+        Tag::Float { value, .. } => Some(Number::Float(*value as f64)),
+        Tag::Double { value, .. } => Some(Number::Float(*value)),
+        _ => None,
+    }
+}
+
+macro_rules! numeric_codec {
+    ($($t:ty),*) => {$(
+        impl Codec<$t> for Primitive<$t> {
+            fn encode(&self, value: &$t) -> DataResult<Tag> {
+                DataResult::success(Tag::from(*value))
+            }
+            fn decode(&self, tag: &Tag) -> DataResult<$t> {
+                match number(tag) {
+                    Some(Number::Int(v)) => DataResult::success(v as $t),
+                    Some(Number::Float(v)) => DataResult::success(v as $t),
+                    None => DataResult::error(format!("Not a number: {}", tag)),
+                }
+            }
+        }
+    )*};
+}
+numeric_codec!(i8, i16, i32, i64, f32, f64);
+
+impl Codec<bool> for Primitive<bool> {
+    fn encode(&self, value: &bool) -> DataResult<Tag> {
+        DataResult::success(Tag::from(*value))
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<bool> {
+        BYTE.decode(tag).map(|b| b != 0)
+    }
+}
+
+macro_rules! exact_codec {
+    ($($t:ty => $variant:ident $what:literal),*) => {$(
+        impl Codec<$t> for Primitive<$t> {
+            fn encode(&self, value: &$t) -> DataResult<Tag> {
+                DataResult::success(Tag::from(value.clone()))
+            }
+            fn decode(&self, tag: &Tag) -> DataResult<$t> {
+                match tag {
+                    Tag::$variant { value, .. } => DataResult::success(value.clone()),
+                    other => DataResult::error(format!(concat!("Not ", $what, ": {}"), other)),
+                }
+            }
+        }
+    )*};
+}
+exact_codec!(
+    String => String "a string",
+    Vec<u8> => ByteArray "a byte array",
+    Vec<i32> => IntArray "an int array",
+    Vec<i64> => LongArray "a long array"
+);
+
+impl Codec<Tag> for Primitive<Tag> {
+    fn encode(&self, value: &Tag) -> DataResult<Tag> {
+        DataResult::success(value.clone())
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<Tag> {
+        DataResult::success(tag.clone())
+    }
+}
+
+// --- combinators -----------------------------------------------------------------
+
+pub struct XMap<C, A, F, G> {
+    codec: C,
+    to: F,
+    from: G,
+    _a: PhantomData<fn() -> A>,
+}
+
+impl<A, B, C: Codec<A>, F: Fn(A) -> B, G: Fn(&B) -> A> Codec<B> for XMap<C, A, F, G> {
+    fn encode(&self, value: &B) -> DataResult<Tag> {
+        self.codec.encode(&(self.from)(value))
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<B> {
+        self.codec.decode(tag).map(&self.to)
+    }
+}
+
+impl<A, B, C: MapCodec<A>, F: Fn(A) -> B, G: Fn(&B) -> A> MapCodec<B> for XMap<C, A, F, G> {
+    fn encode_into(&self, value: &B, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        self.codec.encode_into(&(self.from)(value), entries)
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<B> {
+        self.codec.decode_from(entries).map(&self.to)
+    }
+}
+
+pub struct FlatXMap<C, A, F, G> {
+    codec: C,
+    to: F,
+    from: G,
+    _a: PhantomData<fn() -> A>,
+}
+
+impl<A, B, C, F, G> Codec<B> for FlatXMap<C, A, F, G>
+where
+    C: Codec<A>,
+    F: Fn(A) -> DataResult<B>,
+    G: Fn(&B) -> DataResult<A>,
+{
+    fn encode(&self, value: &B) -> DataResult<Tag> {
+        (self.from)(value).flat_map(|a| self.codec.encode(&a))
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<B> {
+        self.codec.decode(tag).flat_map(&self.to)
+    }
+}
+
+pub struct Validate<C, F> {
+    codec: C,
+    check: F,
+}
+
+impl<A, C: Codec<A>, F: Fn(&A) -> Result<(), String>> Codec<A> for Validate<C, F> {
+    fn encode(&self, value: &A) -> DataResult<Tag> {
+        match (self.check)(value) {
+            Ok(()) => self.codec.encode(value),
+            Err(message) => DataResult::error(message),
+        }
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<A> {
+        self.codec.decode(tag).flat_map(|v| match (self.check)(&v) {
+            Ok(()) => DataResult::success(v),
+            Err(message) => DataResult::error(message),
+        })
+    }
+}
+
+pub struct ListOf<C>(C);
+
+impl<A, C: Codec<A>> Codec<Vec<A>> for ListOf<C> {
+    fn encode(&self, value: &Vec<A>) -> DataResult<Tag> {
+        let mut errors = Vec::new();
+        let elements = value
+            .iter()
+            .filter_map(|v| self.0.encode(v).take(&mut errors))
+            .collect::<Vec<_>>();
+        DataResult::from_parts(Some(Tag::from(elements)), errors)
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<Vec<A>> {
+        let Tag::List { elements, .. } = tag else {
+            return DataResult::error(format!("Not a list: {}", tag));
+        };
+        let mut errors = Vec::new();
+        let values = elements
+            .iter()
+            .filter_map(|e| self.0.decode(e).take(&mut errors))
+            .collect();
+        DataResult::from_parts(Some(values), errors)
+    }
+}
+
+/// A compound with arbitrary string keys; bad entries are dropped from the partial result
+pub fn unbounded_map<A, C: Codec<A>>(values: C) -> UnboundedMap<C> {
+    UnboundedMap(values)
+}
+
+pub struct UnboundedMap<C>(C);
+
+impl<A, C: Codec<A>> Codec<HashMap<String, A>> for UnboundedMap<C> {
+    fn encode(&self, value: &HashMap<String, A>) -> DataResult<Tag> {
+        let mut errors = Vec::new();
+        let entries = value
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), self.0.encode(v).take(&mut errors)?)))
+            .collect::<HashMap<_, _>>();
+        DataResult::from_parts(Some(Tag::from(entries)), errors)
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<HashMap<String, A>> {
+        let Tag::Compound { entries, .. } = tag else {
+            return DataResult::error(format!("Not a map: {}", tag));
+        };
+        let mut errors = Vec::new();
+        let values = entries
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), self.0.decode(v).take(&mut errors)?)))
+            .collect();
+        DataResult::from_parts(Some(values), errors)
+    }
+}
+
+pub struct FieldOf<C> {
+    name: String,
+    codec: C,
+}
+
+impl<A, C: Codec<A>> MapCodec<A> for FieldOf<C> {
+    fn encode_into(&self, value: &A, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        self.codec
+            .encode(value)
+            .map(|tag| insert(entries, &self.name, tag))
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<A> {
+        match entries.get(&self.name) {
+            Some(tag) => self
+                .codec
+                .decode(tag)
+                .map_error(|e| format!("{}: {}", self.name, e)),
+            None => DataResult::error(format!("No key {}", self.name)),
+        }
+    }
+}
+
+pub struct OptionalFieldOf<C> {
+    name: String,
+    codec: C,
+}
+
+impl<A, C: Codec<A>> MapCodec<Option<A>> for OptionalFieldOf<C> {
+    fn encode_into(&self, value: &Option<A>, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        match value {
+            Some(v) => self
+                .codec
+                .encode(v)
+                .map(|tag| insert(entries, &self.name, tag)),
+            None => DataResult::success(()),
+        }
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<Option<A>> {
+        match entries.get(&self.name) {
+            Some(tag) => self
+                .codec
+                .decode(tag)
+                .map(Some)
+                .map_error(|e| format!("{}: {}", self.name, e)),
+            None => DataResult::success(None),
+        }
+    }
+}
+
+pub struct OptionalFieldOr<C, A> {
+    name: String,
+    codec: C,
+    default: A,
+}
+
+impl<A: Clone + PartialEq, C: Codec<A>> MapCodec<A> for OptionalFieldOr<C, A> {
+    fn encode_into(&self, value: &A, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        if *value == self.default {
+            return DataResult::success(());
+        }
+        self.codec
+            .encode(value)
+            .map(|tag| insert(entries, &self.name, tag))
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<A> {
+        match entries.get(&self.name) {
+            Some(tag) => self
+                .codec
+                .decode(tag)
+                .map_error(|e| format!("{}: {}", self.name, e)),
+            None => DataResult::success(self.default.clone()),
+        }
+    }
+}
+
+fn insert(entries: &mut HashMap<String, Tag>, key: &str, tag: Tag) {
+    entries.insert(key.to_string(), tag.with_name(key));
+}
+
+/// A [`MapCodec`] used as a codec for whole compounds
+pub struct CompoundCodec<M>(M);
+
+impl<A, M: MapCodec<A>> Codec<A> for CompoundCodec<M> {
+    fn encode(&self, value: &A) -> DataResult<Tag> {
+        let mut entries = HashMap::new();
+        self.0
+            .encode_into(value, &mut entries)
+            .map(|()| Tag::from(entries))
+    }
+    fn decode(&self, tag: &Tag) -> DataResult<A> {
+        match tag {
+            Tag::Compound { entries, .. } => self.0.decode_from(entries),
+            other => DataResult::error(format!("Not a map: {}", other)),
+        }
+    }
+}
+
+impl<A, M: MapCodec<A>> MapCodec<A> for CompoundCodec<M> {
+    fn encode_into(&self, value: &A, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        self.0.encode_into(value, entries)
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<A> {
+        self.0.decode_from(entries)
+    }
+}
+
+pub struct Dispatch<C, F, G> {
+    key: String,
+    type_codec: C,
+    type_of: F,
+    codec_of: G,
+}
+
+impl<K, V, C, F, G> MapCodec<V> for Dispatch<C, F, G>
+where
+    C: Codec<K>,
+    F: Fn(&V) -> K,
+    G: Fn(&K) -> DataResult<Box<dyn MapCodec<V>>>,
+{
+    fn encode_into(&self, value: &V, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        let kind = (self.type_of)(value);
+        self.type_codec.encode(&kind).flat_map(|tag| {
+            insert(entries, &self.key, tag);
+            (self.codec_of)(&kind).flat_map(|codec| codec.encode_into(value, entries))
+        })
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<V> {
+        let Some(tag) = entries.get(&self.key) else {
+            return DataResult::error(format!("No key {}", self.key));
+        };
+        self.type_codec
+            .decode(tag)
+            .flat_map(|kind| (self.codec_of)(&kind))
+            .flat_map(|codec| codec.decode_from(entries))
+    }
+}
+
+// --- records ---------------------------------------------------------------------
+
+/// One field of a [`record`]: a [`MapCodec`] plus the getter reading it from `O`
+pub struct RecordField<M, G, A> {
+    codec: M,
+    getter: G,
+    _a: PhantomData<fn() -> A>,
+}
+
+/// A tuple of [`RecordField`]s sharing the output type `O`
+pub trait RecordFields<O> {
+    type Values;
+    fn encode_fields(&self, value: &O, entries: &mut HashMap<String, Tag>) -> DataResult<()>;
+    fn decode_fields(&self, entries: &HashMap<String, Tag>) -> DataResult<Self::Values>;
+}
+
+macro_rules! record_fields {
+    ($(($($f:ident $a:ident $m:ident $g:ident),+))*) => {$(
+        impl<O, $($a, $m: MapCodec<$a>, $g: Fn(&O) -> $a),+> RecordFields<O>
+            for ($(RecordField<$m, $g, $a>,)+)
+        {
+            type Values = ($($a,)+);
+
+            fn encode_fields(&self, value: &O, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+                let ($($f,)+) = self;
+                let mut errors = Vec::new();
+                $($f.codec.encode_into(&($f.getter)(value), entries).take(&mut errors);)+
+                DataResult::from_parts(Some(()), errors)
+            }
+
+            fn decode_fields(&self, entries: &HashMap<String, Tag>) -> DataResult<Self::Values> {
+                let ($($f,)+) = self;
+                let mut errors = Vec::new();
+                $(let $f = $f.codec.decode_from(entries).take(&mut errors);)+
+                let values = match ($($f,)+) {
+                    ($(Some($f),)+) => Some(($($f,)+)),
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                };
+                DataResult::from_parts(values, errors)
+            }
+        }
+    )*};
+}
+record_fields! {
+    (f1 A1 M1 G1)
+    (f1 A1 M1 G1, f2 A2 M2 G2)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6, f7 A7 M7 G7)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6, f7 A7 M7 G7, f8 A8 M8 G8)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6, f7 A7 M7 G7, f8 A8 M8 G8, f9 A9 M9 G9)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6, f7 A7 M7 G7, f8 A8 M8 G8, f9 A9 M9 G9, f10 A10 M10 G10)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6, f7 A7 M7 G7, f8 A8 M8 G8, f9 A9 M9 G9, f10 A10 M10 G10, f11 A11 M11 G11)
+    (f1 A1 M1 G1, f2 A2 M2 G2, f3 A3 M3 G3, f4 A4 M4 G4, f5 A5 M5 G5, f6 A6 M6 G6, f7 A7 M7 G7, f8 A8 M8 G8, f9 A9 M9 G9, f10 A10 M10 G10, f11 A11 M11 G11, f12 A12 M12 G12)
+}
+
+/// Start a record codec from a tuple of fields, like `RecordCodecBuilder.create`.
+/// Finish it with [`RecordBuilder::apply`].
+pub fn record<O, F: RecordFields<O>>(fields: F) -> RecordBuilder<O, F> {
+    RecordBuilder {
+        fields,
+        _o: PhantomData,
+    }
+}
+
+pub struct RecordBuilder<O, F> {
+    fields: F,
+    _o: PhantomData<fn() -> O>,
+}
+
+impl<O, F: RecordFields<O>> RecordBuilder<O, F> {
+    /// Supply the constructor, which receives the decoded field values as a tuple
+    pub fn apply<C: Fn(F::Values) -> O>(self, constructor: C) -> CompoundCodec<Record<O, F, C>> {
+        CompoundCodec(Record {
+            fields: self.fields,
+            constructor,
+            _o: PhantomData,
+        })
+    }
+}
+
+pub struct Record<O, F, C> {
+    fields: F,
+    constructor: C,
+    _o: PhantomData<fn() -> O>,
+}
+
+impl<O, F: RecordFields<O>, C: Fn(F::Values) -> O> MapCodec<O> for Record<O, F, C> {
+    fn encode_into(&self, value: &O, entries: &mut HashMap<String, Tag>) -> DataResult<()> {
+        self.fields.encode_fields(value, entries)
+    }
+    fn decode_from(&self, entries: &HashMap<String, Tag>) -> DataResult<O> {
+        self.fields.decode_fields(entries).map(&self.constructor)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for DataResult<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataResult::Success(v) => write!(f, "DataResult.success[{}]", v),
+            DataResult::Error { message, .. } => write!(f, "DataResult.error[{}]", message),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{Tag, TagId, mixed, type_name};
+
+/// Error returned when a tag is converted to a Rust type it doesn't hold
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TagTypeError {
+    pub expected: TagId,
+    pub found: TagId,
+}
+
+impl fmt::Display for TagTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {}, found {}",
+            type_name(self.expected),
+            type_name(self.found)
+        )
+    }
+}
+
+impl std::error::Error for TagTypeError {}
+
+// Conversions produce unnamed tags, like list elements; see `Tag::with_name`.
+macro_rules! tag_from {
+    ($($t:ty => $variant:ident),* $(,)?) => {$(
+        impl From<$t> for Tag {
+            fn from(value: $t) -> Tag {
+                Tag::$variant {
+                    name: None,
+                    value: value.into(),
+                }
+            }
+        }
+    )*};
+}
+tag_from! {
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    String => String,
+    &str => String,
+    Vec<u8> => ByteArray,
+    &[u8] => ByteArray,
+    Vec<i32> => IntArray,
+    &[i32] => IntArray,
+    Vec<i64> => LongArray,
+    &[i64] => LongArray,
+}
+
+impl From<bool> for Tag {
+    fn from(value: bool) -> Tag {
+        Tag::from(value as i8)
+    }
+}
+
+/// A list whose element type is that of its elements: End when empty, and Compound when
+/// they are mixed (see [`Tag::is_mixed_list`])
+impl From<Vec<Tag>> for Tag {
+    fn from(mut elements: Vec<Tag>) -> Tag {
+        for elem in &mut elements {
+            elem.set_name(None);
+        }
+        Tag::List {
+            name: None,
+            element_id: mixed::list_element_id(&elements),
+            elements,
+        }
+    }
+}
+
+/// A compound whose entry names are set to their keys
+impl From<HashMap<String, Tag>> for Tag {
+    fn from(mut entries: HashMap<String, Tag>) -> Tag {
+        for (key, entry) in &mut entries {
+            entry.set_name(Some(key.clone()));
+        }
+        Tag::Compound {
+            name: None,
+            entries,
+        }
+    }
+}
+
+// Strict conversions back out of a tag: the variant must match exactly.
+macro_rules! try_from_tag {
+    ($($t:ty => $variant:ident $id:literal),* $(,)?) => {$(
+        impl TryFrom<&Tag> for $t {
+            type Error = TagTypeError;
+
+            fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+                match tag {
+                    Tag::$variant { value, .. } => Ok(value.clone()),
+                    other => Err(TagTypeError {
+                        expected: $id,
+                        found: other.id(),
+                    }),
+                }
+            }
+        }
+    )*};
+}
+try_from_tag! {
+    i8 => Byte 1,
+    i16 => Short 2,
+    i32 => Int 3,
+    i64 => Long 4,
+    f32 => Float 5,
+    f64 => Double 6,
+    String => String 8,
+    Vec<u8> => ByteArray 7,
+    Vec<i32> => IntArray 11,
+    Vec<i64> => LongArray 12,
+}
+
+macro_rules! try_from_tag_ref {
+    ($($t:ty => $variant:ident $id:literal),* $(,)?) => {$(
+        impl<'a> TryFrom<&'a Tag> for &'a $t {
+            type Error = TagTypeError;
+
+            fn try_from(tag: &'a Tag) -> Result<Self, Self::Error> {
+                match tag {
+                    Tag::$variant { value, .. } => Ok(value),
+                    other => Err(TagTypeError {
+                        expected: $id,
+                        found: other.id(),
+                    }),
+                }
+            }
+        }
+    )*};
+}
+try_from_tag_ref! {
+    str => String 8,
+    [u8] => ByteArray 7,
+    [i32] => IntArray 11,
+    [i64] => LongArray 12,
+}
+
+/// Booleans are stored as bytes; any non-zero byte is true
+impl TryFrom<&Tag> for bool {
+    type Error = TagTypeError;
+
+    fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+        i8::try_from(tag).map(|b| b != 0)
+    }
+}
+
+// Lenient reads, after vanilla's NumericTag: any numeric tag can be read as any numeric
+// type. Integers are narrowed by wrapping, and floats are floored before narrowing to
+// Int or smaller. The strict `TryFrom` conversions above are still there for validation.
+impl Tag {
+    /// Whether this is a Byte, Short, Int, Long, Float or Double
+    pub fn is_numeric(&self) -> bool {
+        matches!(self.id(), 1..=6)
+    }
+
+    /// Like vanilla's `getAsLong`: a Float is truncated, a Double is floored
+    pub fn as_long(&self) -> Option<i64> {
+        match self {
+            Tag::Byte { value, .. } => Some(*value as i64),
+            Tag::Short { value, .. } => Some(*value as i64),
+            Tag::Int { value, .. } => Some(*value as i64),
+            Tag::Long { value, .. } => Some(*value),
+            Tag::Float { value, .. } => Some(*value as i64),
+            Tag::Double { value, .. } => Some(value.floor() as i64),
+            _ => None,
+        }
+    }
+
+    /// Like vanilla's `getAsInt`: a Long keeps its low 32 bits, floats are floored
+    /// (saturating, with NaN as 0)
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            Tag::Float { value, .. } => Some(value.floor() as i32),
+            Tag::Double { value, .. } => Some(value.floor() as i32),
+            other => other.as_long().map(|v| v as i32),
+        }
+    }
+
+    /// [`Tag::as_int`] wrapped to 16 bits, like vanilla's `getAsShort`
+    pub fn as_short(&self) -> Option<i16> {
+        self.as_int().map(|v| v as i16)
+    }
+
+    /// [`Tag::as_int`] wrapped to 8 bits, like vanilla's `getAsByte`
+    pub fn as_byte(&self) -> Option<i8> {
+        self.as_int().map(|v| v as i8)
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Tag::Float { value, .. } => Some(*value),
+            Tag::Double { value, .. } => Some(*value as f32),
+            other => other.as_long().map(|v| v as f32),
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self {
+            Tag::Float { value, .. } => Some(*value as f64),
+            Tag::Double { value, .. } => Some(*value),
+            other => other.as_long().map(|v| v as f64),
+        }
+    }
+
+    /// Like vanilla's `getBoolean`: true when [`Tag::as_byte`] is non-zero, so an Int of
+    /// 256 reads as false
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_byte().map(|b| b != 0)
+    }
+}
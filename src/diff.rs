@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::{
+    Tag, mixed,
+    path::{NbtPath, PathSegment},
+};
+
+/// One difference between two tag trees
+#[derive(PartialEq, Clone, Debug)]
+pub enum Change {
+    /// `value` exists only in the new tree
+    Added { path: NbtPath, value: Tag },
+    /// `value` exists only in the old tree
+    Removed { path: NbtPath, value: Tag },
+    /// Same tag type, different value
+    Changed { path: NbtPath, from: Tag, to: Tag },
+    /// The tag at `path` has a different type in the new tree
+    TypeChanged { path: NbtPath, from: Tag, to: Tag },
+}
+
+impl Change {
+    pub fn path(&self) -> &NbtPath {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. }
+            | Change::TypeChanged { path, .. } => path,
+        }
+    }
+}
+
+/// The ordered list of changes turning one tree into another, as returned by [`diff`]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Patch {
+    pub changes: Vec<Change>,
+}
+
+/// Error returned when a [`Patch`] does not fit the tree it is applied to
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PatchError {
+    pub path: NbtPath,
+    pub message: &'static str,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, DisplayPath(&self.path))
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Compare two trees. Compounds are compared key by key and lists index by index;
+/// tag names are ignored and floats are compared by bit pattern.
+pub fn diff(old: &Tag, new: &Tag) -> Patch {
+    let mut changes = Vec::new();
+    diff_into(&mut changes, NbtPath::root(), old, new);
+    Patch { changes }
+}
+
+fn diff_into(changes: &mut Vec<Change>, path: NbtPath, old: &Tag, new: &Tag) {
+    match (old, new) {
+        (Tag::Compound { entries: a, .. }, Tag::Compound { entries: b, .. }) => {
+            let mut keys: Vec<&String> = a
+                .keys()
+                .chain(b.keys().filter(|k| !a.contains_key(*k)))
+                .collect();
+            keys.sort();
+            for key in keys {
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) => diff_into(changes, path.key(key.as_str()), x, y),
+                    (Some(x), None) => changes.push(Change::Removed {
+                        path: path.key(key.as_str()),
+                        value: x.clone(),
+                    }),
+                    (None, Some(y)) => changes.push(Change::Added {
+                        path: path.key(key.as_str()),
+                        value: y.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (Tag::List { elements: a, .. }, Tag::List { elements: b, .. })
+            if mixed::list_element_id(a) == mixed::list_element_id(b) =>
+        {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                diff_into(changes, path.index(i), x, y);
+            }
+            // Removals go last-first so applying them in order keeps indices valid
+            for i in (b.len()..a.len()).rev() {
+                changes.push(Change::Removed {
+                    path: path.index(i),
+                    value: a[i].clone(),
+                });
+            }
+            for (i, y) in b.iter().enumerate().skip(a.len()) {
+                changes.push(Change::Added {
+                    path: path.index(i),
+                    value: y.clone(),
+                });
+            }
+        }
+        _ if old.id() != new.id() => changes.push(Change::TypeChanged {
+            path,
+            from: old.clone(),
+            to: new.clone(),
+        }),
+        _ if !old.canonical_eq(new) => changes.push(Change::Changed {
+            path,
+            from: old.clone(),
+            to: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+impl Patch {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Apply the changes in order. Removed and changed values must still match what
+    /// the patch recorded, so a patch can't silently clobber data it didn't see.
+    /// On error, changes before the failing one have already been applied.
+    pub fn apply(&self, root: &mut Tag) -> Result<(), PatchError> {
+        for change in &self.changes {
+            apply_change(root, change)?;
+        }
+        Ok(())
+    }
+}
+
+fn apply_change(root: &mut Tag, change: &Change) -> Result<(), PatchError> {
+    let path = change.path();
+    let err = |message| {
+        Err(PatchError {
+            path: path.clone(),
+            message,
+        })
+    };
+    match change {
+        Change::Added { value, .. } => {
+            let Some((parent, last)) = path.split_last() else {
+                return err("cannot add the root");
+            };
+            match (root.get_path_mut(&parent), last) {
+                (Some(Tag::Compound { entries, .. }), PathSegment::Key(key)) => {
+                    if entries.contains_key(key) {
+                        return err("key already exists");
+                    }
+                    let mut value = value.clone();
+                    value.set_name(Some(key.clone()));
+                    entries.insert(key.clone(), value);
+                }
+                (Some(Tag::List { elements, .. }), PathSegment::Index(i))
+                    if *i <= elements.len() =>
+                {
+                    let mut value = value.clone();
+                    value.set_name(None);
+                    elements.insert(*i, value);
+                }
+                _ => return err("no compound or list to add to"),
+            }
+        }
+        Change::Removed { value, .. } => {
+            if !root
+                .get_path(path)
+                .is_some_and(|cur| cur.canonical_eq(value))
+            {
+                return err("removed value does not match");
+            }
+            let Some((parent, last)) = path.split_last() else {
+                return err("cannot remove the root");
+            };
+            match (root.get_path_mut(&parent), last) {
+                (Some(Tag::Compound { entries, .. }), PathSegment::Key(key)) => {
+                    entries.remove(key);
+                }
+                (Some(Tag::List { elements, .. }), PathSegment::Index(i)) => {
+                    elements.remove(*i);
+                }
+                _ => unreachable!("path was resolved above"),
+            }
+        }
+        Change::Changed { from, to, .. } | Change::TypeChanged { from, to, .. } => {
+            let Some(cur) = root.get_path_mut(path) else {
+                return err("path not found");
+            };
+            if !cur.canonical_eq(from) {
+                return err("changed value does not match");
+            }
+            let name = cur.name().map(String::from);
+            *cur = to.clone();
+            cur.set_name(name);
+        }
+    }
+    Ok(())
+}
+
+/// Renders the root path as `(root)` rather than an empty string
+struct DisplayPath<'a>(&'a NbtPath);
+
+impl fmt::Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_root() {
+            f.write_str("(root)")
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// `+ path: value`, `- path: value`, `~ path: old -> new`, `! path: old (type) -> new (type)`
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {}", DisplayPath(path), value),
+            Change::Removed { path, value } => write!(f, "- {}: {}", DisplayPath(path), value),
+            Change::Changed { path, from, to } => {
+                write!(f, "~ {}: {} -> {}", DisplayPath(path), from, to)
+            }
+            Change::TypeChanged { path, from, to } => write!(
+                f,
+                "! {}: {} ({}) -> {} ({})",
+                DisplayPath(path),
+                from,
+                from.type_name(),
+                to,
+                to.type_name()
+            ),
+        }
+    }
+}
+
+/// One change per line
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+//! Upgrading saved data between Minecraft data versions, a much smaller cousin of
+//! vanilla's DataFixerUpper. A [`DataFixer`] holds fixes keyed by [`DataType`] and the
+//! data version that introduced them; [`DataFixer::upgrade`] runs, in order, every fix
+//! newer than the tag's `DataVersion` up to the target. Data older than the fixes reach
+//! back to is refused rather than stamped with a version it was never migrated to.
+//!
+//! Fixes only see the tag they are applied to: entities and items nested inside a chunk
+//! or player are not visited by the entity and item fixes, so apply those separately.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{Tag, uuid::UuidForm};
+
+/// The kind of saved data a fix applies to
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum DataType {
+    /// A region file chunk
+    Chunk,
+    /// One entity compound, as in entity chunks or a chunk's `Entities` list
+    Entity,
+    /// One item stack compound
+    Item,
+    /// level.dat
+    Level,
+    /// A player .dat file
+    Player,
+}
+
+type FixFn = dyn Fn(&mut Tag) + Send + Sync;
+
+struct Fix {
+    data_type: DataType,
+    version: i32,
+    name: String,
+    apply: Box<FixFn>,
+}
+
+/// Registry of fixes, ordered by data version
+#[derive(Default)]
+pub struct DataFixer {
+    fixes: Vec<Fix>,
+    oldest: HashMap<DataType, i32>,
+}
+
+impl DataFixer {
+    /// An empty registry
+    pub fn new() -> Self {
+        DataFixer::default()
+    }
+
+    /// A registry with the fixes shipped by this crate. They start after the 1.13
+    /// flattening, and for chunks after 1.16 stopped packed block states from spanning
+    /// longs, so older data is refused.
+    pub fn vanilla() -> Self {
+        let mut fixer = DataFixer::new();
+        fixer
+            .oldest_supported(DataType::Chunk, 2527)
+            .oldest_supported(DataType::Entity, 1451)
+            .oldest_supported(DataType::Item, 1451)
+            .oldest_supported(DataType::Level, 1451)
+            .oldest_supported(DataType::Player, 1451)
+            .register(
+                DataType::Entity,
+                2510,
+                "EntityZombifiedPiglinRenameFix",
+                |tag| rename_id(tag, "minecraft:zombie_pigman", "minecraft:zombified_piglin"),
+            )
+            .register(
+                DataType::Item,
+                2510,
+                "EntityZombifiedPiglinRenameFix",
+                |tag| {
+                    rename_id(
+                        tag,
+                        "minecraft:zombie_pigman_spawn_egg",
+                        "minecraft:zombified_piglin_spawn_egg",
+                    )
+                },
+            )
+            .register(DataType::Entity, 2514, "EntityUUIDFix", entity_uuids)
+            .register(DataType::Player, 2514, "PlayerUUIDFix", player_uuids)
+            .register(DataType::Item, 2514, "ItemStackUUIDFix", item_uuids)
+            .register(
+                DataType::Chunk,
+                2832,
+                "ChunkBlockStatesFix",
+                chunk_block_states,
+            )
+            .register(DataType::Chunk, 2842, "ChunkRenamesFix", chunk_renames)
+            .register(DataType::Item, 3692, "ItemGrassRenameFix", |tag| {
+                rename_id(tag, "minecraft:grass", "minecraft:short_grass")
+            });
+        fixer
+    }
+
+    /// Add a fix for `data_type` introduced in data version `version`. Fixes for the
+    /// same version run in registration order.
+    pub fn register(
+        &mut self,
+        data_type: DataType,
+        version: i32,
+        name: impl Into<String>,
+        fix: impl Fn(&mut Tag) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let at = self.fixes.partition_point(|f| f.version <= version);
+        self.fixes.insert(
+            at,
+            Fix {
+                data_type,
+                version,
+                name: name.into(),
+                apply: Box::new(fix),
+            },
+        );
+        self
+    }
+
+    /// Refuse to upgrade `data_type` from versions before `version`, which the registered
+    /// fixes don't know how to migrate. Unversioned data counts as older than anything.
+    pub fn oldest_supported(&mut self, data_type: DataType, version: i32) -> &mut Self {
+        self.oldest.insert(data_type, version);
+        self
+    }
+
+    /// Apply every fix for `data_type` with `from < version <= to`, returning how many
+    /// ran. The tag's `DataVersion` is left alone.
+    pub fn update(&self, data_type: DataType, tag: &mut Tag, from: i32, to: i32) -> usize {
+        let mut applied = 0;
+        for fix in &self.fixes {
+            if fix.data_type == data_type && fix.version > from && fix.version <= to {
+                (fix.apply)(tag);
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    /// Upgrade `tag` from its own `DataVersion` to `to` and record the new version.
+    /// Data without a version predates 1.9 and gets every fix, if the registry reaches
+    /// back that far.
+    ///
+    /// Fails, leaving the tag alone, if it is newer than `to`, since there is no
+    /// downgrading, or older than [`oldest_supported`](Self::oldest_supported).
+    pub fn upgrade(&self, data_type: DataType, tag: &mut Tag, to: i32) -> Result<usize, FixError> {
+        let version = data_version(data_type, tag);
+        if let Some(&oldest) = self.oldest.get(&data_type)
+            && version.is_none_or(|from| from < oldest)
+        {
+            return Err(FixError::TooOld {
+                from: version,
+                oldest,
+            });
+        }
+        let from = version.unwrap_or(-1);
+        if from > to {
+            return Err(FixError::Downgrade { from, to });
+        }
+        let applied = self.update(data_type, tag, from, to);
+        let holder = match data_type {
+            DataType::Level => entries_mut(tag).and_then(|e| e.get_mut("Data")),
+            _ => Some(tag),
+        };
+        if let Some(entries) = holder.and_then(entries_mut) {
+            entries.insert("DataVersion".into(), Tag::new_int("DataVersion", to));
+        }
+        Ok(applied)
+    }
+
+    /// Names and versions of the fixes registered for `data_type`, in run order
+    pub fn fixes(&self, data_type: DataType) -> impl Iterator<Item = (i32, &str)> {
+        self.fixes
+            .iter()
+            .filter(move |f| f.data_type == data_type)
+            .map(|f| (f.version, f.name.as_str()))
+    }
+}
+
+/// The `DataVersion` of a tag, which level.dat keeps under `Data`
+pub fn data_version(data_type: DataType, tag: &Tag) -> Option<i32> {
+    let holder = match data_type {
+        DataType::Level => tag.get("Data")?,
+        _ => tag,
+    };
+    i32::try_from(holder.get("DataVersion")?).ok()
+}
+
+/// Error from [`DataFixer::upgrade`] when the data can't be brought to the target
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FixError {
+    /// The data is newer than the target
+    Downgrade { from: i32, to: i32 },
+    /// The data predates what the registered fixes can migrate; `from` is `None` when
+    /// it has no `DataVersion`
+    TooOld { from: Option<i32>, oldest: i32 },
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixError::Downgrade { from, to } => {
+                write!(f, "cannot downgrade data version {} to {}", from, to)
+            }
+            FixError::TooOld {
+                from: Some(from),
+                oldest,
+            } => write!(
+                f,
+                "data version {} is older than the oldest supported, {}",
+                from, oldest
+            ),
+            FixError::TooOld { from: None, oldest } => write!(
+                f,
+                "data without a version is older than the oldest supported, {}",
+                oldest
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FixError {}
+
+fn entries_mut(tag: &mut Tag) -> Option<&mut HashMap<String, Tag>> {
+    match tag {
+        Tag::Compound { entries, .. } => Some(entries),
+        _ => None,
+    }
+}
+
+fn rename(entries: &mut HashMap<String, Tag>, from: &str, to: &str) {
+    if let Some(tag) = entries.remove(from) {
+        entries.insert(to.to_string(), tag.with_name(to));
+    }
+}
+
+fn rename_id(tag: &mut Tag, from: &str, to: &str) {
+    if let Some(Tag::String { value, .. }) = entries_mut(tag).and_then(|e| e.get_mut("id"))
+        && value == from
+    {
+        *value = to.to_string();
+    }
+}
+
+/// Turn a `<key>Most`/`<key>Least` pair into the IntArray form
+fn pair_to_ints(tag: &mut Tag, key: &str) {
+    let most = format!("{}Most", key);
+    if tag.get(&most).is_some()
+        && let Some(uuid) = tag.remove_uuid(key)
+    {
+        tag.set_uuid(key, uuid, UuidForm::IntArray);
+    }
+}
+
+fn each_in_list(tag: &mut Tag, key: &str, mut f: impl FnMut(&mut Tag)) {
+    if let Some(Tag::List { elements, .. }) = entries_mut(tag).and_then(|e| e.get_mut(key)) {
+        elements.iter_mut().for_each(&mut f);
+    }
+}
+
+/// Attribute modifiers, nested in `Attributes` for entities and players
+fn attribute_uuids(tag: &mut Tag) {
+    each_in_list(tag, "Attributes", |attribute| {
+        each_in_list(attribute, "Modifiers", |modifier| {
+            pair_to_ints(modifier, "UUID")
+        })
+    });
+}
+
+/// 20w12a: entity UUIDs move from `UUIDMost`/`UUIDLeast` pairs and strings to 4-int
+/// arrays. Passengers are fixed along with their vehicle.
+fn entity_uuids(entity: &mut Tag) {
+    pair_to_ints(entity, "UUID");
+    if let Some(owner) = entity.get("OwnerUUID").and_then(crate::uuid::uuid_of) {
+        entity.remove_uuid("OwnerUUID");
+        entity.set_uuid("Owner", owner, UuidForm::IntArray);
+    }
+    if let Some(leash) = entries_mut(entity).and_then(|e| e.get_mut("Leash")) {
+        pair_to_ints(leash, "UUID");
+    }
+    attribute_uuids(entity);
+    each_in_list(entity, "Passengers", entity_uuids);
+}
+
+fn player_uuids(player: &mut Tag) {
+    pair_to_ints(player, "UUID");
+    attribute_uuids(player);
+    if let Some(vehicle) = entries_mut(player).and_then(|e| e.get_mut("RootVehicle")) {
+        pair_to_ints(vehicle, "Attach");
+    }
+}
+
+fn item_uuids(item: &mut Tag) {
+    let Some(tag) = entries_mut(item).and_then(|e| e.get_mut("tag")) else {
+        return;
+    };
+    each_in_list(tag, "AttributeModifiers", |modifier| {
+        pair_to_ints(modifier, "UUID")
+    });
+    if let Some(owner) = entries_mut(tag).and_then(|e| e.get_mut("SkullOwner"))
+        && let Some(id) = owner.get_uuid("Id")
+    {
+        owner.set_uuid("Id", id, UuidForm::IntArray);
+    }
+}
+
+/// 21w37a: each section's `Palette` and `BlockStates` move into a `block_states`
+/// compound as `palette` and `data`. Sections without a palette hold only air, which is
+/// only true from 1.16 on; older sections are in formats this doesn't convert.
+/// The biome and world height changes of the same version are not handled.
+fn chunk_block_states(chunk: &mut Tag) {
+    let level = match chunk.get("Level") {
+        Some(_) => entries_mut(chunk).and_then(|e| e.get_mut("Level")),
+        None => Some(chunk),
+    };
+    let Some(Tag::List { elements, .. }) = level
+        .and_then(entries_mut)
+        .and_then(|e| e.get_mut("Sections"))
+    else {
+        return;
+    };
+    for section in elements.iter_mut().filter_map(entries_mut) {
+        let palette = section.remove("Palette").unwrap_or_else(|| {
+            let air = HashMap::from([("Name".to_string(), Tag::from("minecraft:air"))]);
+            Tag::from(vec![Tag::from(air)])
+        });
+        let mut block_states = HashMap::from([("palette".to_string(), palette)]);
+        if let Some(data) = section.remove("BlockStates") {
+            block_states.insert("data".into(), data);
+        }
+        section.insert(
+            "block_states".into(),
+            Tag::from(block_states).with_name("block_states"),
+        );
+    }
+}
+
+/// 21w43a: the `Level` wrapper is removed and the remaining keys get snake_case names
+fn chunk_renames(chunk: &mut Tag) {
+    let Some(entries) = entries_mut(chunk) else {
+        return;
+    };
+    if let Some(Tag::Compound { entries: level, .. }) = entries.remove("Level") {
+        for (key, value) in level {
+            entries.entry(key).or_insert(value);
+        }
+    }
+    for (from, to) in [
+        ("Sections", "sections"),
+        ("TileEntities", "block_entities"),
+        ("TileTicks", "block_ticks"),
+        ("LiquidTicks", "fluid_ticks"),
+        ("Entities", "entities"),
+        ("Structures", "structures"),
+    ] {
+        rename(entries, from, to);
+    }
+    if let Some(structures) = entries.get_mut("structures").and_then(entries_mut) {
+        rename(structures, "Starts", "starts");
+    }
+}
@@ -0,0 +1,55 @@
+use std::ops::{Index, IndexMut};
+
+use crate::Tag;
+
+static END: Tag = Tag::End;
+
+/// `tag["key"]` reads a compound entry. Missing keys and non-compounds yield `Tag::End`
+/// instead of panicking, so lookups can be chained: `tag["Data"]["Player"]["Pos"][0]`.
+impl Index<&str> for Tag {
+    type Output = Tag;
+
+    fn index(&self, key: &str) -> &Tag {
+        self.get(key).unwrap_or(&END)
+    }
+}
+
+/// `tag["key"] = value` replaces a compound entry; add new ones with [`Tag::insert`] or
+/// [`Tag::get_or_insert_compound`], which also name them after their key.
+///
+/// Panics if this tag is not a compound or has no entry `key`.
+impl IndexMut<&str> for Tag {
+    fn index_mut(&mut self, key: &str) -> &mut Tag {
+        match self {
+            Tag::Compound { entries, .. } => match entries.get_mut(key) {
+                Some(entry) => entry,
+                None => panic!("no entry {:?} in Compound", key),
+            },
+            _ => panic!("cannot index non-Compound with a key"),
+        }
+    }
+}
+
+/// `tag[i]` reads a list element, yielding `Tag::End` when out of range or not a list
+impl Index<usize> for Tag {
+    type Output = Tag;
+
+    fn index(&self, index: usize) -> &Tag {
+        match self {
+            Tag::List { elements, .. } => elements.get(index).unwrap_or(&END),
+            _ => &END,
+        }
+    }
+}
+
+/// `tag[i] = value` replaces a list element.
+///
+/// Panics if this tag is not a list or `i` is out of range.
+impl IndexMut<usize> for Tag {
+    fn index_mut(&mut self, index: usize) -> &mut Tag {
+        match self {
+            Tag::List { elements, .. } => &mut elements[index],
+            _ => panic!("cannot index non-List with an integer"),
+        }
+    }
+}
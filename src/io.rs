@@ -3,12 +3,47 @@ use std::{
     io::{Error, ErrorKind, Read, Result, Write},
 };
 
-use crate::{Tag, TagId};
-use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use crate::{
+    Tag, TagId, mixed,
+    raw::{RawString, RawTag},
+};
+use flate2::{
+    Compression as Level,
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+
+/// How an NBT file is compressed. `level.dat` and player files are gzipped, region
+/// chunks are usually zlib, and some tools write raw NBT.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Compression {
+    Gzip,
+    Zlib,
+    None,
+}
+
+impl Compression {
+    /// Guess from the first bytes of a file: the gzip magic, a zlib header, or else raw
+    pub fn detect(bytes: &[u8]) -> Compression {
+        match bytes {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            // Deflate with a window of at most 32K, and a header checksum
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8
+                    && cmf >> 4 <= 7
+                    && u16::from_be_bytes([*cmf, *flg]).is_multiple_of(31) =>
+            {
+                Compression::Zlib
+            }
+            _ => Compression::None,
+        }
+    }
+}
 
 /// Binary reader for NBT format
 pub struct Reader<R: Read> {
     inner: R,
+    unwrap_lists: bool,
 }
 
 // --- Reader gzip ---
@@ -16,19 +51,44 @@ impl<R: Read> Reader<GzDecoder<R>> {
     pub fn from_gzip(inner: R) -> Self {
         Reader {
             inner: GzDecoder::new(inner),
+            unwrap_lists: false,
         }
     }
 }
 impl<W: Write> Writer<GzEncoder<W>> {
     pub fn to_gzip(inner: W) -> Self {
         Writer {
-            inner: GzEncoder::new(inner, Compression::default()),
+            inner: GzEncoder::new(inner, Level::default()),
+        }
+    }
+}
+
+// --- Reader zlib ---
+impl<R: Read> Reader<ZlibDecoder<R>> {
+    pub fn from_zlib(inner: R) -> Self {
+        Reader::new(ZlibDecoder::new(inner))
+    }
+}
+impl<W: Write> Writer<ZlibEncoder<W>> {
+    pub fn to_zlib(inner: W) -> Self {
+        Writer {
+            inner: ZlibEncoder::new(inner, Level::default()),
         }
     }
 }
 impl<R: Read> Reader<R> {
     pub fn new(inner: R) -> Self {
-        Reader { inner }
+        Reader {
+            inner,
+            unwrap_lists: false,
+        }
+    }
+
+    /// Unwrap `{"": value}` elements of compound lists, so mixed lists (1.21.5+) read
+    /// back as they were built. Off by default, which keeps the wrappers as compounds.
+    pub fn unwrap_mixed_lists(mut self, unwrap: bool) -> Self {
+        self.unwrap_lists = unwrap;
+        self
     }
 
     /// Read a full tag (ID + name + payload)
@@ -41,6 +101,83 @@ impl<R: Read> Reader<R> {
         self.read_payload(id, name)
     }
 
+    /// Read a nameless root tag (ID + payload), as sent over the network since 1.20.2
+    pub fn read_network_tag(&mut self) -> Result<Tag> {
+        let id = self.read_u8()?;
+        if id == 0 {
+            return Ok(Tag::End);
+        }
+        self.read_payload(id, None)
+    }
+
+    /// Read a full tag exactly as encoded; see [`RawTag`]
+    pub fn read_raw_tag(&mut self) -> Result<(RawString, RawTag)> {
+        let id = self.read_u8()?;
+        if id == 0 {
+            return Ok((RawString::default(), RawTag::End));
+        }
+        let name = self.read_raw_string()?;
+        Ok((name, self.read_raw_payload(id)?))
+    }
+
+    fn read_raw_payload(&mut self, id: TagId) -> Result<RawTag> {
+        Ok(match id {
+            1 => RawTag::Byte(self.read_i8()?),
+            2 => RawTag::Short(self.read_i16()?),
+            3 => RawTag::Int(self.read_i32()?),
+            4 => RawTag::Long(self.read_i64()?),
+            5 => RawTag::Float(self.read_f32()?.to_bits()),
+            6 => RawTag::Double(self.read_f64()?.to_bits()),
+            7 => {
+                let len = self.read_len()?;
+                RawTag::ByteArray(self.read_bytes(len)?)
+            }
+            8 => RawTag::String(self.read_raw_string()?),
+            9 => {
+                let element_id = self.read_u8()?;
+                let len = self.read_i32()?;
+                // Vanilla reads a negative length as an empty list
+                let negative_len = (len < 0).then_some(len);
+                let len = len.max(0) as usize;
+                let mut elements = Vec::with_capacity(len.min(PREALLOC));
+                for _ in 0..len {
+                    elements.push(self.read_raw_payload(element_id)?);
+                }
+                RawTag::List {
+                    element_id,
+                    elements,
+                    negative_len,
+                }
+            }
+            10 => {
+                let mut entries = Vec::new();
+                loop {
+                    let id = self.read_u8()?;
+                    if id == 0 {
+                        break;
+                    }
+                    let key = self.read_raw_string()?;
+                    entries.push((key, self.read_raw_payload(id)?));
+                }
+                RawTag::Compound(entries)
+            }
+            11 => {
+                let len = self.read_len()?;
+                RawTag::IntArray(self.read_array(len)?)
+            }
+            12 => {
+                let len = self.read_len()?;
+                RawTag::LongArray(self.read_array(len)?)
+            }
+            other => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unknown tag id {}", other),
+                ));
+            }
+        })
+    }
+
     fn read_payload(&mut self, id: TagId, name: Option<String>) -> Result<Tag> {
         match id {
             1 => Ok(Tag::Byte {
@@ -68,10 +205,11 @@ impl<R: Read> Reader<R> {
                 value: self.read_f64()?,
             }),
             7 => {
-                let len = self.read_i32()? as usize;
-                let mut buf = vec![0u8; len];
-                self.inner.read_exact(&mut buf)?;
-                Ok(Tag::ByteArray { name, value: buf })
+                let len = self.read_len()?;
+                Ok(Tag::ByteArray {
+                    name,
+                    value: self.read_bytes(len)?,
+                })
             }
             8 => {
                 let s = self.read_string()?;
@@ -79,14 +217,48 @@ impl<R: Read> Reader<R> {
             }
             9 => {
                 let elem_id = self.read_u8()?;
-                let len = self.read_i32()? as usize;
-                let mut elements = Vec::with_capacity(len);
-                for _ in 0..len {
-                    elements.push(self.read_payload(elem_id, None)?);
-                }
+                let len = self.read_len()?;
+                let elements = match elem_id {
+                    1 => list_of(self.read_bytes(len)?, |v| Tag::Byte {
+                        name: None,
+                        value: v as i8,
+                    }),
+                    2 => list_of(self.read_array(len)?, |value| Tag::Short {
+                        name: None,
+                        value,
+                    }),
+                    3 => list_of(self.read_array(len)?, |value| Tag::Int {
+                        name: None,
+                        value,
+                    }),
+                    4 => list_of(self.read_array(len)?, |value| Tag::Long {
+                        name: None,
+                        value,
+                    }),
+                    5 => list_of(self.read_array(len)?, |v| Tag::Float {
+                        name: None,
+                        value: f32::from_bits(v),
+                    }),
+                    6 => list_of(self.read_array(len)?, |v| Tag::Double {
+                        name: None,
+                        value: f64::from_bits(v),
+                    }),
+                    _ => {
+                        let mut elements = Vec::with_capacity(len.min(PREALLOC));
+                        for _ in 0..len {
+                            elements.push(self.read_payload(elem_id, None)?);
+                        }
+                        elements
+                    }
+                };
+                let (element_id, elements) = match elem_id {
+                    _ if elements.is_empty() => (0, elements),
+                    10 if self.unwrap_lists => mixed::unwrap_elements(elements),
+                    _ => (elem_id, elements),
+                };
                 Ok(Tag::List {
                     name,
-                    element_id: elem_id,
+                    element_id,
                     elements,
                 })
             }
@@ -104,20 +276,18 @@ impl<R: Read> Reader<R> {
                 Ok(Tag::Compound { name, entries })
             }
             11 => {
-                let len = self.read_i32()? as usize;
-                let mut v = Vec::with_capacity(len);
-                for _ in 0..len {
-                    v.push(self.read_i32()?);
-                }
-                Ok(Tag::IntArray { name, value: v })
+                let len = self.read_len()?;
+                Ok(Tag::IntArray {
+                    name,
+                    value: self.read_array(len)?,
+                })
             }
             12 => {
-                let len = self.read_i32()? as usize;
-                let mut v = Vec::with_capacity(len);
-                for _ in 0..len {
-                    v.push(self.read_i64()?);
-                }
-                Ok(Tag::LongArray { name, value: v })
+                let len = self.read_len()?;
+                Ok(Tag::LongArray {
+                    name,
+                    value: self.read_array(len)?,
+                })
             }
             other => Err(Error::new(
                 ErrorKind::InvalidData,
@@ -126,6 +296,32 @@ impl<R: Read> Reader<R> {
         }
     }
 
+    /// Read an array/list length prefix, rejecting negative values
+    fn read_len(&mut self) -> Result<usize> {
+        check_len(self.read_i32()?)
+    }
+
+    /// Read `len` bytes, growing the buffer as they arrive
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(len.min(PREALLOC));
+        (&mut self.inner).take(len as u64).read_to_end(&mut buf)?;
+        check_filled(buf.len(), len)?;
+        Ok(buf)
+    }
+
+    /// Read `len` big-endian numbers with a `read_exact` per [`PREALLOC`] of them,
+    /// swapping in place
+    fn read_array<T: BeNum>(&mut self, len: usize) -> Result<Vec<T>> {
+        let mut v = Vec::new();
+        while v.len() < len {
+            let start = v.len();
+            v.resize(start + (len - start).min(PREALLOC), T::default());
+            self.inner.read_exact(as_bytes_mut(&mut v[start..]))?;
+        }
+        from_be_in_place(&mut v);
+        Ok(v)
+    }
+
     fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0u8; 1];
         self.inner.read_exact(&mut buf)?;
@@ -170,6 +366,12 @@ impl<R: Read> Reader<R> {
         self.inner.read_exact(&mut buf)?;
         String::from_utf8(buf).map_err(|e| Error::new(ErrorKind::InvalidData, e))
     }
+    fn read_raw_string(&mut self) -> Result<RawString> {
+        let len = self.read_u16()? as usize;
+        let mut buf = vec![0u8; len];
+        self.inner.read_exact(&mut buf)?;
+        Ok(RawString(buf))
+    }
 }
 
 /// Binary writer for NBT format
@@ -182,6 +384,11 @@ impl<W: Write> Writer<W> {
         Writer { inner }
     }
 
+    /// The underlying writer, e.g. to `finish()` a gzip stream and see its errors
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
     /// Write a full tag (ID + name + payload)
     pub fn write_tag(&mut self, tag: &Tag) -> Result<()> {
         let id = tag.id();
@@ -197,6 +404,77 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
+    /// Write a nameless root tag (ID + payload), as sent over the network since 1.20.2
+    pub fn write_network_tag(&mut self, tag: &Tag) -> Result<()> {
+        self.write_u8(tag.id())?;
+        self.write_payload(tag)
+    }
+
+    /// Write a tag read by [`Reader::read_raw_tag`], byte for byte
+    pub fn write_raw_tag(&mut self, name: &RawString, tag: &RawTag) -> Result<()> {
+        self.write_u8(tag.id())?;
+        if tag.id() != 0 {
+            self.write_raw_string(name)?;
+            self.write_raw_payload(tag)?;
+        }
+        Ok(())
+    }
+
+    fn write_raw_payload(&mut self, tag: &RawTag) -> Result<()> {
+        match tag {
+            RawTag::End => {}
+            RawTag::Byte(v) => self.write_i8(*v)?,
+            RawTag::Short(v) => self.write_i16(*v)?,
+            RawTag::Int(v) => self.write_i32(*v)?,
+            RawTag::Long(v) => self.write_i64(*v)?,
+            RawTag::Float(bits) => self.write_f32(f32::from_bits(*bits))?,
+            RawTag::Double(bits) => self.write_f64(f64::from_bits(*bits))?,
+            RawTag::ByteArray(v) => {
+                self.write_i32(v.len() as i32)?;
+                self.inner.write_all(v)?;
+            }
+            RawTag::String(s) => self.write_raw_string(s)?,
+            RawTag::List {
+                element_id,
+                elements,
+                negative_len,
+            } => {
+                let len = match negative_len {
+                    Some(_) if !elements.is_empty() => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "list with a negative length has elements",
+                        ));
+                    }
+                    Some(len) => *len,
+                    None => elements.len() as i32,
+                };
+                self.write_u8(*element_id)?;
+                self.write_i32(len)?;
+                for elem in elements {
+                    self.write_raw_payload(elem)?;
+                }
+            }
+            RawTag::Compound(entries) => {
+                for (key, entry) in entries.iter().filter(|(_, e)| e.id() != 0) {
+                    self.write_u8(entry.id())?;
+                    self.write_raw_string(key)?;
+                    self.write_raw_payload(entry)?;
+                }
+                self.write_u8(0)?;
+            }
+            RawTag::IntArray(v) => {
+                self.write_i32(v.len() as i32)?;
+                self.write_array(v)?;
+            }
+            RawTag::LongArray(v) => {
+                self.write_i32(v.len() as i32)?;
+                self.write_array(v)?;
+            }
+        }
+        Ok(())
+    }
+
     fn write_payload(&mut self, tag: &Tag) -> Result<()> {
         match tag {
             Tag::End => {}
@@ -218,10 +496,28 @@ impl<W: Write> Writer<W> {
                 elements,
                 ..
             } => {
-                self.write_u8(*element_id)?;
+                let element_id = mixed::write_element_id(*element_id, elements)?;
+                if mixed::needs_wrapping(elements) {
+                    self.write_u8(10)?;
+                    self.write_i32(elements.len() as i32)?;
+                    for elem in elements {
+                        if mixed::is_wrapped(elem) {
+                            self.write_u8(elem.id())?;
+                            self.write_string("")?;
+                            self.write_payload(elem)?;
+                            self.write_u8(0)?;
+                        } else {
+                            self.write_payload(elem)?;
+                        }
+                    }
+                    return Ok(());
+                }
+                self.write_u8(element_id)?;
                 self.write_i32(elements.len() as i32)?;
-                for elem in elements {
-                    self.write_payload(elem)?; // lists omit names
+                if !self.write_numeric_list(element_id, elements)? {
+                    for elem in elements {
+                        self.write_payload(elem)?; // lists omit names
+                    }
                 }
             }
             Tag::Compound { entries, .. } => {
@@ -235,20 +531,40 @@ impl<W: Write> Writer<W> {
             }
             Tag::IntArray { value, .. } => {
                 self.write_i32(value.len() as i32)?;
-                for &i in value {
-                    self.write_i32(i)?;
-                }
+                self.write_array(value)?;
             }
             Tag::LongArray { value, .. } => {
                 self.write_i32(value.len() as i32)?;
-                for &l in value {
-                    self.write_i64(l)?;
-                }
+                self.write_array(value)?;
             }
         }
         Ok(())
     }
 
+    /// Bulk-write the payloads of a list of numbers. Returns `false` (having written
+    /// nothing) when the list is not numeric or an element doesn't match `element_id`.
+    fn write_numeric_list(&mut self, element_id: TagId, elements: &[Tag]) -> Result<bool> {
+        match numeric_list(element_id, elements) {
+            Some(NumericList::Bytes(v)) => self.inner.write_all(&v)?,
+            Some(NumericList::Shorts(v)) => self.write_array(&v)?,
+            Some(NumericList::Ints(v)) => self.write_array(&v)?,
+            Some(NumericList::Longs(v)) => self.write_array(&v)?,
+            Some(NumericList::Floats(v)) => self.write_array(&v)?,
+            Some(NumericList::Doubles(v)) => self.write_array(&v)?,
+            None => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Write numbers big-endian with a single `write_all`
+    fn write_array<T: BeNum>(&mut self, v: &[T]) -> Result<()> {
+        if cfg!(target_endian = "big") {
+            return self.inner.write_all(as_bytes(v));
+        }
+        let swapped: Vec<T> = v.iter().map(|x| x.native_to_be()).collect();
+        self.inner.write_all(as_bytes(&swapped))
+    }
+
     fn write_u8(&mut self, v: u8) -> Result<()> {
         self.inner.write_all(&[v])
     }
@@ -278,4 +594,118 @@ impl<W: Write> Writer<W> {
         self.inner.write_all(&(bytes.len() as u16).to_be_bytes())?;
         self.inner.write_all(bytes)
     }
+    fn write_raw_string(&mut self, s: &RawString) -> Result<()> {
+        if s.0.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, "string too long"));
+        }
+        self.inner.write_all(&(s.0.len() as u16).to_be_bytes())?;
+        self.inner.write_all(&s.0)
+    }
+}
+
+/// Fixed-width integers that are moved to and from the wire as one byte block.
+///
+/// # Safety
+/// Implementors must have no padding and accept every bit pattern, so that a `[Self]`
+/// can be viewed as `[u8]` in both directions.
+pub(crate) unsafe trait BeNum: Copy + Default {
+    fn be_to_native(self) -> Self;
+    fn native_to_be(self) -> Self;
+}
+
+macro_rules! impl_be_num {
+    ($($t:ty),*) => {$(
+        unsafe impl BeNum for $t {
+            #[inline]
+            fn be_to_native(self) -> Self {
+                <$t>::from_be(self)
+            }
+            #[inline]
+            fn native_to_be(self) -> Self {
+                <$t>::to_be(self)
+            }
+        }
+    )*};
+}
+impl_be_num!(i16, i32, i64, u32, u64);
+
+pub(crate) fn as_bytes<T: BeNum>(v: &[T]) -> &[u8] {
+    // SAFETY: BeNum types have no padding, and u8 has no alignment requirement
+    unsafe { std::slice::from_raw_parts(v.as_ptr().cast(), std::mem::size_of_val(v)) }
+}
+
+pub(crate) fn as_bytes_mut<T: BeNum>(v: &mut [T]) -> &mut [u8] {
+    // SAFETY: as above, and any bytes written through the view form a valid BeNum
+    unsafe { std::slice::from_raw_parts_mut(v.as_mut_ptr().cast(), std::mem::size_of_val(v)) }
+}
+
+/// Swap a buffer filled straight from the wire into native order (a no-op on BE targets)
+pub(crate) fn from_be_in_place<T: BeNum>(v: &mut [T]) {
+    for x in v.iter_mut() {
+        *x = x.be_to_native();
+    }
+}
+
+/// Most elements allocated ahead of reading them, so a hostile length prefix can't make
+/// a reader reserve far more memory than the input holds
+pub(crate) const PREALLOC: usize = 1 << 16;
+
+/// Fail with `UnexpectedEof` when a bounded read came up short
+pub(crate) fn check_filled(read: usize, len: usize) -> Result<()> {
+    if read < len {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("expected {} bytes, found {}", len, read),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_len(len: i32) -> Result<usize> {
+    usize::try_from(len).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Negative array length {}", len),
+        )
+    })
+}
+
+/// Turn a bulk-read number buffer into unnamed list elements
+pub(crate) fn list_of<T>(raw: Vec<T>, element: impl Fn(T) -> Tag) -> Vec<Tag> {
+    raw.into_iter().map(element).collect()
+}
+
+/// The payloads of a numeric list, gathered for a single bulk write (floats as bits)
+pub(crate) enum NumericList {
+    Bytes(Vec<u8>),
+    Shorts(Vec<i16>),
+    Ints(Vec<i32>),
+    Longs(Vec<i64>),
+    Floats(Vec<u32>),
+    Doubles(Vec<u64>),
+}
+
+/// Gather the payloads of `elements`, or `None` if the list is not numeric or an
+/// element doesn't match `element_id`
+pub(crate) fn numeric_list(element_id: TagId, elements: &[Tag]) -> Option<NumericList> {
+    macro_rules! bulk {
+        ($variant:ident, $map:expr) => {
+            elements
+                .iter()
+                .map(|e| match e {
+                    Tag::$variant { value, .. } => Some($map(*value)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?
+        };
+    }
+    Some(match element_id {
+        1 => NumericList::Bytes(bulk!(Byte, |v: i8| v as u8)),
+        2 => NumericList::Shorts(bulk!(Short, |v: i16| v)),
+        3 => NumericList::Ints(bulk!(Int, |v: i32| v)),
+        4 => NumericList::Longs(bulk!(Long, |v: i64| v)),
+        5 => NumericList::Floats(bulk!(Float, f32::to_bits)),
+        6 => NumericList::Doubles(bulk!(Double, f64::to_bits)),
+        _ => return None,
+    })
 }
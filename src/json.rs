@@ -0,0 +1,684 @@
+//! JSON in two flavours:
+//!
+//! - [`JsonMode::Natural`]: plain JSON for display and hand edits. Numbers lose their exact
+//!   NBT type: integers read back as Int (or Long when too big), decimals as Double, and
+//!   typed arrays as lists.
+//! - [`JsonMode::Typed`]: every value is an object carrying its NBT type, so a tag survives
+//!   the trip through JSON exactly, names and list element types included:
+//!
+//! ```json
+//! {"type": "compound", "name": "", "value": {
+//!   "Health": {"type": "float", "value": 20.0},
+//!   "Seed": {"type": "long", "value": "-4172144997902289642"},
+//!   "Pos": {"type": "list", "element": "double", "value": [{"type": "double", "value": 0.5}]}
+//! }}
+//! ```
+//!
+//! Longs are written as strings since JavaScript can't hold them in a number, and floats
+//! that JSON has no number for (NaN, infinities) as their bits in hex: `{"type": "float",
+//! "bits": "7fc00000"}`. `name` is only written where it differs from what its position
+//! implies: the key inside a compound, none for list elements and the root.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{Tag, TagId, mixed::list_element_id, type_name};
+
+/// Which JSON encoding [`Tag::to_json`] and [`Tag::from_json`] use
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum JsonMode {
+    /// Plain JSON values; lossy
+    #[default]
+    Natural,
+    /// `{"type": ..., "value": ...}` objects; exact
+    Typed,
+}
+
+impl Tag {
+    /// Compact JSON. In natural mode compound keys are sorted and NaN and infinite floats
+    /// become `null`.
+    pub fn to_json(&self, mode: JsonMode) -> String {
+        let mut out = String::new();
+        match mode {
+            JsonMode::Natural => write_json(&mut out, self, None),
+            JsonMode::Typed => write_typed(&mut out, self, None, None),
+        }
+        out
+    }
+
+    /// JSON indented by two spaces per level
+    pub fn to_json_pretty(&self, mode: JsonMode) -> String {
+        let mut out = String::new();
+        match mode {
+            JsonMode::Natural => write_json(&mut out, self, Some(0)),
+            JsonMode::Typed => write_typed(&mut out, self, None, Some(0)),
+        }
+        out
+    }
+
+    /// Parse JSON. Natural mode gives an unnamed tag, turns booleans into bytes and
+    /// rejects `null`; typed mode gives back exactly what [`Tag::to_json`] wrote.
+    pub fn from_json(s: &str, mode: JsonMode) -> Result<Tag, ParseJsonError> {
+        let mut parser = Parser { s, pos: 0 };
+        let value = parser.value()?;
+        parser.skip_ws();
+        if parser.pos < s.len() {
+            return parser.err("trailing characters after value");
+        }
+        match mode {
+            JsonMode::Natural => natural(value),
+            JsonMode::Typed => typed(&value, None),
+        }
+    }
+}
+
+fn write_json(out: &mut String, tag: &Tag, indent: Option<usize>) {
+    match tag {
+        Tag::End => out.push_str("null"),
+        Tag::Byte { value, .. } => out.push_str(&value.to_string()),
+        Tag::Short { value, .. } => out.push_str(&value.to_string()),
+        Tag::Int { value, .. } => out.push_str(&value.to_string()),
+        Tag::Long { value, .. } => out.push_str(&value.to_string()),
+        Tag::Float { value, .. } => write_float(out, *value as f64, format!("{:?}", value)),
+        Tag::Double { value, .. } => write_float(out, *value, format!("{:?}", value)),
+        Tag::String { value, .. } => write_string(out, value),
+        Tag::ByteArray { value, .. } => {
+            write_seq(out, value.iter().map(|&b| (b as i8).to_string()), None)
+        }
+        Tag::IntArray { value, .. } => write_seq(out, value.iter().map(i32::to_string), None),
+        Tag::LongArray { value, .. } => write_seq(out, value.iter().map(i64::to_string), None),
+        Tag::List { elements, .. } => {
+            let nested = elements
+                .iter()
+                .any(|e| matches!(e, Tag::List { .. } | Tag::Compound { .. }));
+            let indent = indent.filter(|_| nested);
+            let items = elements.iter().map(|e| {
+                let mut item = String::new();
+                write_json(&mut item, e, indent.map(|n| n + 1));
+                item
+            });
+            write_seq(out, items, indent)
+        }
+        Tag::Compound { entries, .. } => {
+            let keys: Vec<&String> = sorted_keys(entries)
+                .into_iter()
+                .filter(|k| entries[*k].id() != 0)
+                .collect();
+            write_object(out, keys, indent, |out, key| {
+                write_json(out, &entries[key], indent.map(|n| n + 1))
+            });
+        }
+    }
+}
+
+/// The typed object for `tag`. `expected` is the name its position implies, so `name` is
+/// only spelled out when it differs.
+fn write_typed(out: &mut String, tag: &Tag, expected: Option<&str>, indent: Option<usize>) {
+    let sep = if indent.is_some() { ", " } else { "," };
+    let colon = if indent.is_some() { ": " } else { ":" };
+    out.push_str("{\"type\"");
+    out.push_str(colon);
+    write_string(out, tag.type_name());
+    if tag.id() != 0 && tag.name() != expected {
+        out.push_str(sep);
+        out.push_str("\"name\"");
+        out.push_str(colon);
+        match tag.name() {
+            Some(name) => write_string(out, name),
+            None => out.push_str("null"),
+        }
+    }
+    if let Tag::List { element_id, .. } = tag {
+        out.push_str(sep);
+        out.push_str("\"element\"");
+        out.push_str(colon);
+        write_string(out, type_name(*element_id));
+    }
+    let bits = match tag {
+        Tag::Float { value, .. } if !value.is_finite() => Some(format!("{:08x}", value.to_bits())),
+        Tag::Double { value, .. } if !value.is_finite() => {
+            Some(format!("{:016x}", value.to_bits()))
+        }
+        _ => None,
+    };
+    if let Some(bits) = bits {
+        out.push_str(sep);
+        out.push_str("\"bits\"");
+        out.push_str(colon);
+        write_string(out, &bits);
+        out.push('}');
+        return;
+    }
+    if tag.id() != 0 {
+        out.push_str(sep);
+        out.push_str("\"value\"");
+        out.push_str(colon);
+    }
+    match tag {
+        Tag::End => {}
+        Tag::Byte { value, .. } => out.push_str(&value.to_string()),
+        Tag::Short { value, .. } => out.push_str(&value.to_string()),
+        Tag::Int { value, .. } => out.push_str(&value.to_string()),
+        Tag::Long { value, .. } => write_string(out, &value.to_string()),
+        Tag::Float { value, .. } => out.push_str(&format!("{:?}", value)),
+        Tag::Double { value, .. } => out.push_str(&format!("{:?}", value)),
+        Tag::String { value, .. } => write_string(out, value),
+        Tag::ByteArray { value, .. } => {
+            write_seq(out, value.iter().map(|&b| (b as i8).to_string()), None)
+        }
+        Tag::IntArray { value, .. } => write_seq(out, value.iter().map(i32::to_string), None),
+        Tag::LongArray { value, .. } => {
+            write_seq(out, value.iter().map(|v| format!("\"{}\"", v)), None)
+        }
+        Tag::List { elements, .. } => {
+            let items = elements.iter().map(|e| {
+                let mut item = String::new();
+                write_typed(&mut item, e, None, indent.map(|n| n + 1));
+                item
+            });
+            write_seq(out, items, indent)
+        }
+        Tag::Compound { entries, .. } => {
+            write_object(out, sorted_keys(entries), indent, |out, key| {
+                write_typed(out, &entries[key], Some(key), indent.map(|n| n + 1))
+            });
+        }
+    }
+    out.push('}');
+}
+
+fn sorted_keys(entries: &HashMap<String, Tag>) -> Vec<&String> {
+    let mut keys: Vec<&String> = entries.keys().collect();
+    keys.sort();
+    keys
+}
+
+/// `{"key":value,...}`, with each entry on its own line when `indent` is set
+fn write_object(
+    out: &mut String,
+    keys: Vec<&String>,
+    indent: Option<usize>,
+    mut write_value: impl FnMut(&mut String, &str),
+) {
+    out.push('{');
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, indent.map(|n| n + 1));
+        write_string(out, key);
+        out.push_str(if indent.is_some() { ": " } else { ":" });
+        write_value(out, key);
+    }
+    if !keys.is_empty() {
+        newline(out, indent);
+    }
+    out.push('}');
+}
+
+/// `shortest` keeps a `.0` on whole numbers so they read back as floats
+fn write_float(out: &mut String, value: f64, shortest: String) {
+    if value.is_finite() {
+        out.push_str(&shortest);
+    } else {
+        out.push_str("null");
+    }
+}
+
+/// `[a,b,c]`, with each item on its own line when `indent` is set
+fn write_seq(out: &mut String, items: impl Iterator<Item = String>, indent: Option<usize>) {
+    out.push('[');
+    let mut empty = true;
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, indent.map(|n| n + 1));
+        out.push_str(&item);
+        empty = false;
+    }
+    if !empty {
+        newline(out, indent);
+    }
+    out.push(']');
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn newline(out: &mut String, indent: Option<usize>) {
+    if let Some(n) = indent {
+        out.push('\n');
+        for _ in 0..n {
+            out.push_str("  ");
+        }
+    }
+}
+
+/// Error returned when a string is not valid JSON, or doesn't describe a tag
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseJsonError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseJsonError {}
+
+/// A parsed JSON value and where it starts, so conversion errors can point at it
+struct Value {
+    position: usize,
+    kind: Kind,
+}
+
+enum Kind {
+    Null,
+    Bool(bool),
+    /// Kept as written, so integers too big for a double stay exact
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn err<T>(&self, message: &'static str) -> Result<T, ParseJsonError> {
+        Err(ParseJsonError {
+            position: self.position,
+            message,
+        })
+    }
+
+    fn field(&self, key: &str) -> Option<&Value> {
+        match &self.kind {
+            Kind::Object(fields) => fields.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Result<&str, ParseJsonError> {
+        match &self.kind {
+            Kind::String(s) => Ok(s),
+            _ => self.err("expected a string"),
+        }
+    }
+
+    fn as_array(&self) -> Result<&[Value], ParseJsonError> {
+        match &self.kind {
+            Kind::Array(items) => Ok(items),
+            _ => self.err("expected an array"),
+        }
+    }
+
+    /// A number of type `T`; longs may also be given as strings
+    fn parse<T: std::str::FromStr>(&self) -> Result<T, ParseJsonError> {
+        let text = match &self.kind {
+            Kind::Number(text) | Kind::String(text) => text,
+            _ => return self.err("expected a number"),
+        };
+        match text.parse() {
+            Ok(v) => Ok(v),
+            Err(_) => self.err("number out of range"),
+        }
+    }
+}
+
+/// An integer as Int, or Long when it doesn't fit; anything else as Double
+fn natural(value: Value) -> Result<Tag, ParseJsonError> {
+    Ok(match value.kind {
+        Kind::Null => return value.err("null has no NBT equivalent"),
+        Kind::Bool(b) => Tag::from(b),
+        Kind::Number(ref text) => {
+            let integer = !text.contains(['.', 'e', 'E']);
+            if let Some(v) = text.parse::<i32>().ok().filter(|_| integer) {
+                Tag::from(v)
+            } else if let Some(v) = text.parse::<i64>().ok().filter(|_| integer) {
+                Tag::from(v)
+            } else {
+                Tag::from(value.parse::<f64>()?)
+            }
+        }
+        Kind::String(s) => Tag::from(s),
+        Kind::Array(items) => Tag::from(
+            items
+                .into_iter()
+                .map(natural)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Kind::Object(fields) => {
+            let mut entries = HashMap::new();
+            for (key, value) in fields {
+                entries.insert(key, natural(value)?);
+            }
+            Tag::from(entries)
+        }
+    })
+}
+
+/// A typed object back into its tag. `expected` is the name to use when none is given.
+fn typed(value: &Value, expected: Option<&str>) -> Result<Tag, ParseJsonError> {
+    if !matches!(value.kind, Kind::Object(_)) {
+        return value.err("expected a {\"type\": ...} object");
+    }
+    let Some(ty) = value.field("type") else {
+        return value.err("missing type");
+    };
+    let id = type_id(ty)?;
+    let name = match value.field("name") {
+        Some(Value {
+            kind: Kind::Null, ..
+        }) => None,
+        Some(name) => Some(name.as_str()?.to_string()),
+        None => expected.map(str::to_string),
+    };
+    if id == 0 {
+        return Ok(Tag::End);
+    }
+    if let Some(bits) = value.field("bits") {
+        let text = bits.as_str()?;
+        return match id {
+            5 => match u32::from_str_radix(text, 16) {
+                Ok(b) => Ok(Tag::Float {
+                    name,
+                    value: f32::from_bits(b),
+                }),
+                Err(_) => bits.err("invalid float bits"),
+            },
+            6 => match u64::from_str_radix(text, 16) {
+                Ok(b) => Ok(Tag::Double {
+                    name,
+                    value: f64::from_bits(b),
+                }),
+                Err(_) => bits.err("invalid float bits"),
+            },
+            _ => bits.err("only floats and doubles have bits"),
+        };
+    }
+    let Some(v) = value.field("value") else {
+        return value.err("missing value");
+    };
+    Ok(match id {
+        1 => Tag::Byte {
+            name,
+            value: v.parse()?,
+        },
+        2 => Tag::Short {
+            name,
+            value: v.parse()?,
+        },
+        3 => Tag::Int {
+            name,
+            value: v.parse()?,
+        },
+        4 => Tag::Long {
+            name,
+            value: v.parse()?,
+        },
+        5 => Tag::Float {
+            name,
+            value: v.parse()?,
+        },
+        6 => Tag::Double {
+            name,
+            value: v.parse()?,
+        },
+        7 => Tag::ByteArray {
+            name,
+            value: v
+                .as_array()?
+                .iter()
+                .map(|b| b.parse::<i8>().map(|b| b as u8))
+                .collect::<Result<_, _>>()?,
+        },
+        8 => Tag::String {
+            name,
+            value: v.as_str()?.to_string(),
+        },
+        9 => {
+            let elements = v
+                .as_array()?
+                .iter()
+                .map(|e| typed(e, None))
+                .collect::<Result<Vec<_>, _>>()?;
+            let element_id = match value.field("element") {
+                Some(element) => type_id(element)?,
+                None => list_element_id(&elements),
+            };
+            Tag::List {
+                name,
+                element_id,
+                elements,
+            }
+        }
+        10 => {
+            let Kind::Object(fields) = &v.kind else {
+                return v.err("expected an object");
+            };
+            let mut entries = HashMap::new();
+            for (key, entry) in fields {
+                entries.insert(key.clone(), typed(entry, Some(key))?);
+            }
+            Tag::Compound { name, entries }
+        }
+        11 => Tag::IntArray {
+            name,
+            value: v
+                .as_array()?
+                .iter()
+                .map(Value::parse)
+                .collect::<Result<_, _>>()?,
+        },
+        _ => Tag::LongArray {
+            name,
+            value: v
+                .as_array()?
+                .iter()
+                .map(Value::parse)
+                .collect::<Result<_, _>>()?,
+        },
+    })
+}
+
+/// A type name back into its ID
+fn type_id(value: &Value) -> Result<TagId, ParseJsonError> {
+    let name = value.as_str()?;
+    match (0..=12).find(|&id| type_name(id) == name) {
+        Some(id) => Ok(id),
+        None => value.err("unknown type"),
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn err<T>(&self, message: &'static str) -> Result<T, ParseJsonError> {
+        Err(ParseJsonError {
+            position: self.pos,
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace, then consume `b` if it comes next
+    fn eat(&mut self, b: u8) -> bool {
+        self.skip_ws();
+        let found = self.peek() == Some(b);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, b: u8, message: &'static str) -> Result<(), ParseJsonError> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            self.err(message)
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseJsonError> {
+        self.skip_ws();
+        let position = self.pos;
+        let kind = match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_ws();
+                        if self.peek() != Some(b'"') {
+                            return self.err("expected a key");
+                        }
+                        let key = self.string()?;
+                        self.expect(b':', "expected ':' after key")?;
+                        fields.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.expect(b',', "expected ',' or '}'")?;
+                    }
+                }
+                Kind::Object(fields)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',', "expected ',' or ']'")?;
+                    }
+                }
+                Kind::Array(items)
+            }
+            Some(b'"') => Kind::String(self.string()?),
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            _ => {
+                let rest = &self.s[self.pos..];
+                let (kind, len) = if rest.starts_with("true") {
+                    (Kind::Bool(true), 4)
+                } else if rest.starts_with("false") {
+                    (Kind::Bool(false), 5)
+                } else if rest.starts_with("null") {
+                    (Kind::Null, 4)
+                } else {
+                    return self.err("expected a value");
+                };
+                self.pos += len;
+                kind
+            }
+        };
+        Ok(Value { position, kind })
+    }
+
+    fn number(&mut self) -> Result<Kind, ParseJsonError> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))
+        {
+            self.pos += 1;
+        }
+        let text = &self.s[start..self.pos];
+        if text.parse::<f64>().is_err() {
+            self.pos = start;
+            return self.err("invalid number");
+        }
+        Ok(Kind::Number(text.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String, ParseJsonError> {
+        let start = self.pos;
+        let mut out = String::new();
+        let mut chars = self.s[start + 1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos = start + 1 + i + 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, e)| e) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(e @ ('"' | '\\' | '/')) => e,
+                        Some('u') => {
+                            let high = hex4(&mut chars);
+                            let decoded = match high {
+                                // A surrogate pair arrives as two escapes
+                                Some(high @ 0xd800..0xdc00) => {
+                                    match (chars.next(), chars.next(), hex4(&mut chars)) {
+                                        (Some((_, '\\')), Some((_, 'u')), Some(low)) => {
+                                            char::decode_utf16([high, low]).next()
+                                        }
+                                        _ => None,
+                                    }
+                                }
+                                Some(unit) => char::decode_utf16([unit]).next(),
+                                None => None,
+                            };
+                            match decoded {
+                                Some(Ok(c)) => c,
+                                _ => return self.err("invalid \\u escape"),
+                            }
+                        }
+                        _ => {
+                            self.pos = start + 1 + i;
+                            return self.err("invalid escape");
+                        }
+                    };
+                    out.push(escaped);
+                }
+                c => out.push(c),
+            }
+        }
+        self.err("unclosed string")
+    }
+}
+
+/// The four hex digits of a `\u` escape
+fn hex4(chars: &mut std::str::CharIndices<'_>) -> Option<u16> {
+    let hex: String = chars.take(4).map(|(_, h)| h).collect();
+    u16::from_str_radix(&hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 4)
+}
@@ -0,0 +1,222 @@
+//! Typed access to level.dat. The file is a gzip-compressed compound whose `Data` entry
+//! holds the world settings; [`LevelData`] models the commonly edited parts of `Data` and
+//! keeps every other key in `extra`, so saving never drops fields it doesn't know about.
+//! A modeled key holding an unexpected type is kept in `extra` as well rather than
+//! failing the read.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    FromNbt, FromNbtError, Reader, Tag, ToNbt, Writer,
+    typed::{entries_of, put, put_some, save_with_backup, take_valid},
+};
+
+/// The `Data` compound of level.dat. Each modeled field is `None` when the file doesn't
+/// have its key, and is only written back when set.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct LevelData {
+    /// `LevelName`
+    pub level_name: Option<String>,
+    /// `DataVersion`
+    pub data_version: Option<i32>,
+    /// `SpawnX`, `SpawnY`, `SpawnZ`; only set when all three are present as ints, and
+    /// otherwise left in `extra`
+    pub spawn: Option<[i32; 3]>,
+    /// `SpawnAngle`
+    pub spawn_angle: Option<f32>,
+    /// `Time`, the total game ticks
+    pub time: Option<i64>,
+    /// `DayTime`, which `/time set` changes
+    pub day_time: Option<i64>,
+    /// `GameType`: 0 survival, 1 creative, 2 adventure, 3 spectator
+    pub game_type: Option<i32>,
+    /// `Difficulty`: 0 peaceful to 3 hard
+    pub difficulty: Option<i8>,
+    /// `hardcore`
+    pub hardcore: Option<bool>,
+    /// `allowCommands`
+    pub allow_commands: Option<bool>,
+    /// `raining`
+    pub raining: Option<bool>,
+    /// `rainTime`
+    pub rain_time: Option<i32>,
+    /// `thundering`
+    pub thundering: Option<bool>,
+    /// `thunderTime`
+    pub thunder_time: Option<i32>,
+    /// `clearWeatherTime`
+    pub clear_weather_time: Option<i32>,
+    /// `GameRules`; vanilla stores every rule value as a string
+    pub game_rules: Option<BTreeMap<String, String>>,
+    /// `WorldGenSettings`, absent before 1.16
+    pub world_gen_settings: Option<WorldGenSettings>,
+    /// `DataPacks`
+    pub data_packs: Option<DataPacks>,
+    /// Every other key of `Data`
+    pub extra: HashMap<String, Tag>,
+}
+
+/// The `WorldGenSettings` compound
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct WorldGenSettings {
+    pub seed: Option<i64>,
+    /// `generate_features`; true by default
+    pub generate_features: Option<bool>,
+    pub bonus_chest: Option<bool>,
+    /// Generator settings per dimension, kept as raw NBT
+    pub dimensions: Option<Tag>,
+    pub extra: HashMap<String, Tag>,
+}
+
+/// The `DataPacks` compound
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct DataPacks {
+    pub enabled: Option<Vec<String>>,
+    pub disabled: Option<Vec<String>>,
+    pub extra: HashMap<String, Tag>,
+}
+
+impl LevelData {
+    /// Read a gzip-compressed level.dat
+    pub fn read<R: Read>(reader: R) -> io::Result<LevelData> {
+        let root = Reader::from_gzip(reader).read_tag()?;
+        let data = root
+            .get("Data")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "level.dat has no Data"))?;
+        LevelData::from_nbt(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write a gzip-compressed level.dat
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut root = Tag::new_compound("");
+        root.insert("Data".into(), self.to_nbt().with_name("Data"));
+        let mut writer = Writer::to_gzip(writer);
+        writer.write_tag(&root)?;
+        writer.into_inner().finish()?.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<LevelData> {
+        LevelData::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Save through a temporary file, keeping the previous file as `level.dat_old` the
+    /// way vanilla does, so a failed write never leaves the world without a level.dat
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save_with_backup(path.as_ref(), |file| self.write(file))
+    }
+}
+
+impl FromNbt for LevelData {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        const SPAWN: [&str; 3] = ["SpawnX", "SpawnY", "SpawnZ"];
+        let spawn = match SPAWN.map(|k| e.get(k).and_then(|t| i32::try_from(t).ok())) {
+            [Some(x), Some(y), Some(z)] => {
+                for k in SPAWN {
+                    e.remove(k);
+                }
+                Some([x, y, z])
+            }
+            _ => None,
+        };
+        Ok(LevelData {
+            level_name: take_valid(&mut e, "LevelName"),
+            data_version: take_valid(&mut e, "DataVersion"),
+            spawn,
+            spawn_angle: take_valid(&mut e, "SpawnAngle"),
+            time: take_valid(&mut e, "Time"),
+            day_time: take_valid(&mut e, "DayTime"),
+            game_type: take_valid(&mut e, "GameType"),
+            difficulty: take_valid(&mut e, "Difficulty"),
+            hardcore: take_valid(&mut e, "hardcore"),
+            allow_commands: take_valid(&mut e, "allowCommands"),
+            raining: take_valid(&mut e, "raining"),
+            rain_time: take_valid(&mut e, "rainTime"),
+            thundering: take_valid(&mut e, "thundering"),
+            thunder_time: take_valid(&mut e, "thunderTime"),
+            clear_weather_time: take_valid(&mut e, "clearWeatherTime"),
+            game_rules: take_valid(&mut e, "GameRules"),
+            world_gen_settings: take_valid(&mut e, "WorldGenSettings"),
+            data_packs: take_valid(&mut e, "DataPacks"),
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for LevelData {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put_some(&mut e, "LevelName", self.level_name.as_ref());
+        put_some(&mut e, "DataVersion", self.data_version);
+        if let Some([x, y, z]) = self.spawn {
+            put(&mut e, "SpawnX", x);
+            put(&mut e, "SpawnY", y);
+            put(&mut e, "SpawnZ", z);
+        }
+        put_some(&mut e, "SpawnAngle", self.spawn_angle);
+        put_some(&mut e, "Time", self.time);
+        put_some(&mut e, "DayTime", self.day_time);
+        put_some(&mut e, "GameType", self.game_type);
+        put_some(&mut e, "Difficulty", self.difficulty);
+        put_some(&mut e, "hardcore", self.hardcore);
+        put_some(&mut e, "allowCommands", self.allow_commands);
+        put_some(&mut e, "raining", self.raining);
+        put_some(&mut e, "rainTime", self.rain_time);
+        put_some(&mut e, "thundering", self.thundering);
+        put_some(&mut e, "thunderTime", self.thunder_time);
+        put_some(&mut e, "clearWeatherTime", self.clear_weather_time);
+        put_some(&mut e, "GameRules", self.game_rules.as_ref());
+        put_some(&mut e, "WorldGenSettings", self.world_gen_settings.as_ref());
+        put_some(&mut e, "DataPacks", self.data_packs.as_ref());
+        Tag::from(e)
+    }
+}
+
+impl FromNbt for WorldGenSettings {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(WorldGenSettings {
+            seed: take_valid(&mut e, "seed"),
+            generate_features: take_valid(&mut e, "generate_features"),
+            bonus_chest: take_valid(&mut e, "bonus_chest"),
+            dimensions: take_valid(&mut e, "dimensions"),
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for WorldGenSettings {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put_some(&mut e, "seed", self.seed);
+        put_some(&mut e, "generate_features", self.generate_features);
+        put_some(&mut e, "bonus_chest", self.bonus_chest);
+        put_some(&mut e, "dimensions", self.dimensions.as_ref());
+        Tag::from(e)
+    }
+}
+
+impl FromNbt for DataPacks {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(DataPacks {
+            enabled: take_valid(&mut e, "Enabled"),
+            disabled: take_valid(&mut e, "Disabled"),
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for DataPacks {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put_some(&mut e, "Enabled", self.enabled.as_ref());
+        put_some(&mut e, "Disabled", self.disabled.as_ref());
+        Tag::from(e)
+    }
+}
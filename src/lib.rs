@@ -1,8 +1,57 @@
 use std::collections::HashMap;
+// Lets code generated by the derives (`::nbt::...`) resolve inside this crate too
+extern crate self as nbt;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+mod canonical;
+pub mod chunk;
+pub mod codec;
+mod convert;
+pub mod diff;
+pub mod fix;
+mod index;
 pub mod io;
+mod json;
+pub mod level;
+mod macros;
+mod merge;
+mod mixed;
+pub mod nibble;
+pub mod packed;
+pub mod path;
+pub mod player;
+pub mod raw;
+pub mod region;
+mod snbt;
 #[cfg(test)]
+#[allow(clippy::err_expect)]
 mod test;
-pub use io::{Reader, Writer};
+pub mod typed;
+pub mod uuid;
+pub mod visit;
+#[cfg(feature = "tokio")]
+pub use async_io::{AsyncReader, AsyncWriter, read_nbt_async, write_nbt_async};
+pub use canonical::Canonical;
+pub use chunk::ChunkSection;
+pub use codec::{Codec, DataResult, MapCodec};
+pub use convert::TagTypeError;
+pub use diff::{Change, Patch, PatchError, diff};
+pub use fix::{DataFixer, DataType};
+pub use io::{Compression, Reader, Writer};
+pub use json::{JsonMode, ParseJsonError};
+pub use level::LevelData;
+#[cfg(feature = "derive")]
+pub use nbt_derive::{FromNbt, ToNbt};
+pub use nibble::NibbleArray;
+pub use packed::{Layout, PackedArray};
+pub use path::{NbtPath, PathSegment};
+pub use player::PlayerData;
+pub use raw::{RawString, RawTag};
+pub use region::Region;
+pub use snbt::ParseSnbtError;
+pub use typed::{FromNbt, FromNbtError, FromNbtErrorKind, ToNbt};
+pub use uuid::UuidForm;
+pub use visit::{Flow, Visitor, VisitorMut};
 
 /// Identifier for an NBT tag type
 pub type TagId = u8;
@@ -111,10 +160,12 @@ impl Tag {
             value: v.into(),
         }
     }
+    /// An empty list always gets `element_id` 0 (End), as vanilla reads and writes it;
+    /// [`Tag::push`] sets the type from the first element
     pub fn new_list(name: impl Into<String>, element_id: TagId, elements: Vec<Tag>) -> Tag {
         Tag::List {
             name: Some(name.into()),
-            element_id,
+            element_id: if elements.is_empty() { 0 } else { element_id },
             elements,
         }
     }
@@ -139,15 +190,29 @@ impl Tag {
 }
 
 impl Tag {
-    /// Insert a sub-tag into a Compound
+    /// Insert a sub-tag into a Compound, named after its key
     pub fn insert(&mut self, key: String, tag: Tag) {
         if let Tag::Compound { entries, .. } = self {
+            let tag = tag.with_name(key.clone());
             entries.insert(key, tag);
         } else {
             panic!("insert() called on non-Compound");
         }
     }
 
+    /// The entry `key` of a Compound, inserting an empty compound named `key` first if
+    /// there is none, for building nested compounds:
+    /// `tag.get_or_insert_compound("a").insert("b".into(), value)`
+    pub fn get_or_insert_compound(&mut self, key: &str) -> &mut Tag {
+        if let Tag::Compound { entries, .. } = self {
+            entries
+                .entry(key.to_string())
+                .or_insert_with(|| Tag::new_compound(key))
+        } else {
+            panic!("get_or_insert_compound() called on non-Compound");
+        }
+    }
+
     /// Retrieve a sub-tag from a Compound
     pub fn get(&self, key: &str) -> Option<&Tag> {
         if let Tag::Compound { entries, .. } = self {
@@ -157,9 +222,21 @@ impl Tag {
         }
     }
 
-    /// Add an element to a List
+    /// Add an element to a List. The first element sets the list's `element_id`, and an
+    /// element of a different type makes the list mixed, with `element_id` 10 (see
+    /// [`Tag::is_mixed_list`]).
     pub fn push(&mut self, tag: Tag) {
-        if let Tag::List { elements, .. } = self {
+        if let Tag::List {
+            element_id,
+            elements,
+            ..
+        } = self
+        {
+            match elements.first() {
+                None => *element_id = tag.id(),
+                Some(first) if first.id() != tag.id() => *element_id = 10,
+                Some(_) => {}
+            }
             elements.push(tag);
         } else {
             panic!("push() called on non-List");
@@ -203,6 +280,122 @@ impl Tag {
             | Tag::LongArray { name, .. } => name.as_deref(),
         }
     }
+
+    /// This tag renamed, for building trees from [`From`] conversions
+    pub fn with_name(mut self, name: impl Into<String>) -> Tag {
+        self.set_name(Some(name.into()));
+        self
+    }
+
+    /// Rename this tag (ignored for End)
+    pub fn set_name(&mut self, new_name: Option<String>) {
+        match self {
+            Tag::End => {}
+            Tag::Byte { name, .. }
+            | Tag::Short { name, .. }
+            | Tag::Int { name, .. }
+            | Tag::Long { name, .. }
+            | Tag::Float { name, .. }
+            | Tag::Double { name, .. }
+            | Tag::ByteArray { name, .. }
+            | Tag::String { name, .. }
+            | Tag::List { name, .. }
+            | Tag::Compound { name, .. }
+            | Tag::IntArray { name, .. }
+            | Tag::LongArray { name, .. } => *name = new_name,
+        }
+    }
+
+    /// Lower-case type name of this tag ("byte", "int_array", …)
+    pub fn type_name(&self) -> &'static str {
+        type_name(self.id())
+    }
+}
+
+/// Lower-case type name for a tag ID, or "unknown"
+pub fn type_name(id: TagId) -> &'static str {
+    match id {
+        0 => "end",
+        1 => "byte",
+        2 => "short",
+        3 => "int",
+        4 => "long",
+        5 => "float",
+        6 => "double",
+        7 => "byte_array",
+        8 => "string",
+        9 => "list",
+        10 => "compound",
+        11 => "int_array",
+        12 => "long_array",
+        _ => "unknown",
+    }
+}
+
+impl Tag {
+    /// Exact number of bytes [`Writer::write_tag`] produces for this tag. Fails, like
+    /// the writer, for tags that can't be encoded.
+    pub fn encoded_len(&self) -> std::io::Result<usize> {
+        match self {
+            Tag::End => Ok(1),
+            _ => Ok(1 + 2 + self.name().map_or(0, str::len) + self.payload_len()?),
+        }
+    }
+
+    /// Exact number of bytes [`Writer::write_network_tag`] produces for this tag
+    pub fn network_encoded_len(&self) -> std::io::Result<usize> {
+        Ok(1 + self.payload_len()?)
+    }
+
+    /// Serialize with a named root into a buffer allocated once at the exact size
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.encoded_len()?);
+        Writer::new(&mut buf).write_tag(self)?;
+        Ok(buf)
+    }
+
+    /// Serialize with a nameless network root into a buffer allocated once at the exact size
+    pub fn to_network_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.network_encoded_len()?);
+        Writer::new(&mut buf).write_network_tag(self)?;
+        Ok(buf)
+    }
+
+    fn payload_len(&self) -> std::io::Result<usize> {
+        Ok(match self {
+            Tag::End => 0,
+            Tag::Byte { .. } => 1,
+            Tag::Short { .. } => 2,
+            Tag::Int { .. } | Tag::Float { .. } => 4,
+            Tag::Long { .. } | Tag::Double { .. } => 8,
+            Tag::ByteArray { value, .. } => 4 + value.len(),
+            Tag::String { value, .. } => 2 + value.len(),
+            Tag::List {
+                element_id,
+                elements,
+                ..
+            } => {
+                mixed::write_element_id(*element_id, elements)?;
+                let wrap = mixed::needs_wrapping(elements);
+                let mut len = 1 + 4;
+                for e in elements {
+                    // `{"": e}` adds an ID, an empty name and an End
+                    let wrapper = if wrap && mixed::is_wrapped(e) { 4 } else { 0 };
+                    len += e.payload_len()? + wrapper;
+                }
+                len
+            }
+            Tag::Compound { entries, .. } => {
+                let mut len = 1;
+                for (key, entry) in entries {
+                    len += 1 + 2 + key.len() + entry.payload_len()?;
+                }
+                len
+            }
+            Tag::IntArray { value, .. } => 4 + value.len() * 4,
+            Tag::LongArray { value, .. } => 4 + value.len() * 8,
+        })
+    }
 }
 
 /// Read an NBT Tag from any reader
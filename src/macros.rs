@@ -0,0 +1,89 @@
+/// Build a [`Tag`](crate::Tag) tree from SNBT-like literal syntax.
+///
+/// The top level is the body of an unnamed compound. Values are anything with a
+/// `From` conversion into `Tag`, `{ ... }` for nested compounds, `[ ... ]` for lists
+/// and `[B; ...]`, `[I; ...]`, `[L; ...]` for byte, int and long arrays. List
+/// elements must all have the same Rust type, so mixed lists fail to compile; build
+/// those from a `Vec<Tag>` instead.
+///
+/// ```
+/// let level = nbt::nbt! {
+///     "Data": {
+///         "SpawnX": 10i32,
+///         LevelName: "world",
+///         "Pos": [1.0f64, 64.0, 2.0],
+///         "Blocks": [B; 1, 2],
+///         "Items": [{ "id": "minecraft:stone", "Count": 1i8 }],
+///     }
+/// };
+/// assert_eq!(level["Data"]["Pos"][1], nbt::Tag::from(64.0));
+/// ```
+///
+/// ```compile_fail
+/// let mixed = nbt::nbt! { "List": [1i8, "two"] };
+/// ```
+#[macro_export]
+macro_rules! nbt {
+    // --- compound entries: `key: value` separated by commas ---
+    (@entries $map:ident ()) => {};
+    (@entries $map:ident ($key:tt : { $($inner:tt)* } $(, $($rest:tt)*)?)) => {
+        $map.insert($crate::nbt!(@key $key), $crate::nbt!({ $($inner)* }));
+        $crate::nbt!(@entries $map ($($($rest)*)?));
+    };
+    (@entries $map:ident ($key:tt : [ $($inner:tt)* ] $(, $($rest:tt)*)?)) => {
+        $map.insert($crate::nbt!(@key $key), $crate::nbt!(@list [ $($inner)* ]));
+        $crate::nbt!(@entries $map ($($($rest)*)?));
+    };
+    (@entries $map:ident ($key:tt : $value:expr $(, $($rest:tt)*)?)) => {
+        $map.insert($crate::nbt!(@key $key), $crate::Tag::from($value));
+        $crate::nbt!(@entries $map ($($($rest)*)?));
+    };
+    (@entries $map:ident ($($bad:tt)*)) => {
+        compile_error!(concat!("expected `key: value` in nbt!, found `", stringify!($($bad)*), "`"))
+    };
+
+    (@key $key:ident) => { ::std::string::String::from(stringify!($key)) };
+    (@key $key:literal) => { ::std::string::String::from($key) };
+
+    // --- lists and typed arrays ---
+    (@list []) => { $crate::Tag::from(::std::vec::Vec::<$crate::Tag>::new()) };
+    (@list [B; $($e:expr),* $(,)?]) => {{
+        let v: ::std::vec::Vec<i8> = ::std::vec![$($e),*];
+        $crate::Tag::from(v.into_iter().map(|b| b as u8).collect::<::std::vec::Vec<u8>>())
+    }};
+    (@list [I; $($e:expr),* $(,)?]) => {{
+        let v: ::std::vec::Vec<i32> = ::std::vec![$($e),*];
+        $crate::Tag::from(v)
+    }};
+    (@list [L; $($e:expr),* $(,)?]) => {{
+        let v: ::std::vec::Vec<i64> = ::std::vec![$($e),*];
+        $crate::Tag::from(v)
+    }};
+    (@list [$({ $($c:tt)* }),+ $(,)?]) => {
+        $crate::Tag::from(::std::vec![$($crate::nbt!({ $($c)* })),+])
+    };
+    (@list [$([ $($l:tt)* ]),+ $(,)?]) => {
+        $crate::Tag::from(::std::vec![$($crate::nbt!(@list [ $($l)* ])),+])
+    };
+    (@list [$($e:expr),+ $(,)?]) => {{
+        let v = ::std::vec![$($e),+];
+        $crate::Tag::from(
+            v.into_iter()
+                .map($crate::Tag::from)
+                .collect::<::std::vec::Vec<$crate::Tag>>(),
+        )
+    }};
+    (@list $($bad:tt)*) => {
+        compile_error!(concat!("invalid list in nbt!: `", stringify!($($bad)*), "`"))
+    };
+
+    // --- entry points ---
+    ({ $($inner:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut entries = ::std::collections::HashMap::new();
+        $crate::nbt!(@entries entries ($($inner)*));
+        $crate::Tag::from(entries)
+    }};
+    ([ $($inner:tt)* ]) => { $crate::nbt!(@list [ $($inner)* ]) };
+    ($($inner:tt)*) => { $crate::nbt!({ $($inner)* }) };
+}
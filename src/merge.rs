@@ -0,0 +1,63 @@
+use crate::Tag;
+
+impl Tag {
+    /// Merge `other` into this tag the way vanilla `/data merge` does: nested compounds
+    /// merge recursively, every other value (lists included) replaces the existing one.
+    /// If either side isn't a compound, this tag is replaced by `other` and keeps its name.
+    pub fn merge(&mut self, other: &Tag) {
+        match (&mut *self, other) {
+            (
+                Tag::Compound { entries, .. },
+                Tag::Compound {
+                    entries: incoming, ..
+                },
+            ) => {
+                for (key, value) in incoming {
+                    match entries.get_mut(key) {
+                        Some(existing @ Tag::Compound { .. })
+                            if matches!(value, Tag::Compound { .. }) =>
+                        {
+                            existing.merge(value)
+                        }
+                        _ => {
+                            let mut value = value.clone();
+                            value.set_name(Some(key.clone()));
+                            entries.insert(key.clone(), value);
+                        }
+                    }
+                }
+            }
+            _ => {
+                let name = self.name().map(String::from);
+                *self = other.clone();
+                self.set_name(name);
+            }
+        }
+    }
+
+    /// Fill in keys from `defaults` that this compound lacks, recursing into compounds
+    /// present on both sides. Existing values are never overwritten, which makes this
+    /// suitable for upgrading older level.dat files with newly added fields.
+    /// Does nothing unless both tags are compounds.
+    pub fn merge_defaults(&mut self, defaults: &Tag) {
+        let (
+            Tag::Compound { entries, .. },
+            Tag::Compound {
+                entries: defaults, ..
+            },
+        ) = (self, defaults)
+        else {
+            return;
+        };
+        for (key, value) in defaults {
+            match entries.get_mut(key) {
+                Some(existing) => existing.merge_defaults(value),
+                None => {
+                    let mut value = value.clone();
+                    value.set_name(Some(key.clone()));
+                    entries.insert(key.clone(), value);
+                }
+            }
+        }
+    }
+}
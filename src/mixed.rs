@@ -0,0 +1,102 @@
+//! Lists with elements of different types, allowed by vanilla since 1.21.5.
+//!
+//! The binary format still has one element type per list, so a mixed list is written as
+//! a list of compounds. Compound elements are written as they are and every other
+//! element is wrapped as `{"": element}`. A compound that already looks like such a
+//! wrapper is wrapped too, so it reads back as itself. In memory a mixed list has
+//! `element_id` 10.
+//!
+//! Lists whose elements are all compounds are written as they are, wrappers included,
+//! so lists read without unwrapping are written back unchanged. Like in vanilla, a list
+//! holding only `{"": value}` compounds is therefore unwrapped when read back.
+//!
+//! Empty lists are written and read as End lists like vanilla, whatever their
+//! `element_id` says. A non-empty list whose `element_id` doesn't match its elements
+//! can't be written.
+//!
+//! [`crate::Reader::unwrap_mixed_lists`] turns the wrappers back into their values.
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::{Tag, TagId};
+
+impl Tag {
+    /// Whether this is a List whose elements don't all have the same type
+    pub fn is_mixed_list(&self) -> bool {
+        match self {
+            Tag::List { elements, .. } => needs_wrapping(elements),
+            _ => false,
+        }
+    }
+}
+
+/// The `element_id` for a list of `elements`: their shared type, 10 when they are
+/// mixed, or 0 when there are none
+pub(crate) fn list_element_id(elements: &[Tag]) -> TagId {
+    match elements.first() {
+        None => 0,
+        Some(first) if elements.iter().all(|e| e.id() == first.id()) => first.id(),
+        Some(_) => 10,
+    }
+}
+
+/// The element type a list is written with: End when it is empty, otherwise
+/// `element_id`, which has to match the elements. End elements have no payload, so a
+/// list holding any can't be written.
+pub(crate) fn write_element_id(element_id: TagId, elements: &[Tag]) -> Result<TagId> {
+    if elements.iter().any(|e| e.id() == 0) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "End tag in a non-empty list",
+        ));
+    }
+    let actual = list_element_id(elements);
+    if !elements.is_empty() && element_id != actual {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "list element_id {} doesn't match its elements of type {}",
+                element_id, actual
+            ),
+        ));
+    }
+    Ok(actual)
+}
+
+/// A compound holding only the key `""`
+pub(crate) fn is_wrapper(tag: &Tag) -> bool {
+    match tag {
+        Tag::Compound { entries, .. } => entries.len() == 1 && entries.contains_key(""),
+        _ => false,
+    }
+}
+
+/// Whether a list has to be written with the wrapper convention: its elements don't
+/// all have the same type
+pub(crate) fn needs_wrapping(elements: &[Tag]) -> bool {
+    elements.windows(2).any(|pair| pair[0].id() != pair[1].id())
+}
+
+/// Whether `elem` of a wrapped list is written inside a `{"": elem}` wrapper
+pub(crate) fn is_wrapped(elem: &Tag) -> bool {
+    elem.id() != 10 || is_wrapper(elem)
+}
+
+/// Replace each wrapper in a compound list with its value, returning the new
+/// `element_id` along with the elements
+pub(crate) fn unwrap_elements(elements: Vec<Tag>) -> (TagId, Vec<Tag>) {
+    let elements: Vec<Tag> = elements
+        .into_iter()
+        .map(|elem| match elem {
+            Tag::Compound { mut entries, .. } if entries.len() == 1 => match entries.remove("") {
+                Some(mut value) => {
+                    value.set_name(None);
+                    value
+                }
+                None => Tag::from(entries),
+            },
+            other => other,
+        })
+        .collect();
+    (list_element_id(&elements), elements)
+}
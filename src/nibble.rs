@@ -0,0 +1,142 @@
+//! 4-bit values packed two per byte, as in a section's `SkyLight` and `BlockLight` and
+//! the pre-1.13 `Data` and `Add` arrays
+
+use crate::{FromNbtError, Tag, chunk::block_index};
+
+/// Bytes in a full section's nibble array
+pub const NIBBLE_ARRAY_LEN: usize = 2048;
+
+/// 4096 nibbles over a 2048-byte ByteArray payload, in vanilla's order: block `x, y, z`
+/// is entry `y << 8 | z << 4 | x`, and even entries take the low nibble of their byte.
+///
+/// `B` is the storage: an owned `Vec<u8>` taken out of a tag, or a borrowed slice from
+/// [`NibbleArray::from_tag`] / [`NibbleArray::from_tag_mut`] to read or edit a tag in
+/// place. No variant copies the bytes.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct NibbleArray<B = Vec<u8>> {
+    bytes: B,
+}
+
+impl NibbleArray {
+    /// All zeroes
+    pub fn new() -> Self {
+        NibbleArray {
+            bytes: vec![0; NIBBLE_ARRAY_LEN],
+        }
+    }
+
+    /// All entries set to `value`, e.g. 15 for full sky light
+    pub fn filled(value: u8) -> Self {
+        let value = value & 0xf;
+        NibbleArray {
+            bytes: vec![value << 4 | value; NIBBLE_ARRAY_LEN],
+        }
+    }
+
+    /// An unnamed ByteArray tag
+    pub fn into_tag(self) -> Tag {
+        Tag::from(self.bytes)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl Default for NibbleArray {
+    fn default() -> Self {
+        NibbleArray::new()
+    }
+}
+
+impl<'a> NibbleArray<&'a [u8]> {
+    /// Read a ByteArray tag in place
+    pub fn from_tag(tag: &'a Tag) -> Result<Self, FromNbtError> {
+        NibbleArray::from_bytes(<&[u8]>::try_from(tag)?)
+    }
+}
+
+impl<'a> NibbleArray<&'a mut [u8]> {
+    /// Edit a ByteArray tag in place
+    pub fn from_tag_mut(tag: &'a mut Tag) -> Result<Self, FromNbtError> {
+        match tag {
+            Tag::ByteArray { value, .. } => NibbleArray::from_bytes(value.as_mut_slice()),
+            other => Err(FromNbtError::wrong_type(7, other)),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> NibbleArray<B> {
+    /// Wrap 2048 bytes
+    pub fn from_bytes(bytes: B) -> Result<Self, FromNbtError> {
+        let len = bytes.as_ref().len();
+        if len != NIBBLE_ARRAY_LEN {
+            return Err(FromNbtError::custom(format!(
+                "expected {} bytes of nibbles, found {}",
+                NIBBLE_ARRAY_LEN, len
+            )));
+        }
+        Ok(NibbleArray { bytes })
+    }
+
+    /// Entry `i`, in `0..4096`
+    pub fn get_index(&self, i: usize) -> u8 {
+        let byte = self.bytes.as_ref()[i >> 1];
+        if i & 1 == 0 { byte & 0xf } else { byte >> 4 }
+    }
+
+    /// The value for block `x, y, z` of the section, each in `0..16`
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.get_index(block_index(x, y, z))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..NIBBLE_ARRAY_LEN * 2).map(|i| self.get_index(i))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+
+    /// An owned copy, e.g. to keep after the borrowed tag goes away
+    pub fn to_owned_array(&self) -> NibbleArray {
+        NibbleArray {
+            bytes: self.bytes.as_ref().to_vec(),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> NibbleArray<B> {
+    /// Set entry `i`; bits of `value` above the low four are dropped
+    pub fn set_index(&mut self, i: usize, value: u8) {
+        let byte = &mut self.bytes.as_mut()[i >> 1];
+        let value = value & 0xf;
+        *byte = if i & 1 == 0 {
+            *byte & 0xf0 | value
+        } else {
+            *byte & 0x0f | value << 4
+        };
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: u8) {
+        self.set_index(block_index(x, y, z), value)
+    }
+}
+
+/// Takes the ByteArray's bytes without copying
+impl TryFrom<Tag> for NibbleArray {
+    type Error = FromNbtError;
+
+    fn try_from(tag: Tag) -> Result<Self, Self::Error> {
+        match tag {
+            Tag::ByteArray { value, .. } => NibbleArray::from_bytes(value),
+            other => Err(FromNbtError::wrong_type(7, &other)),
+        }
+    }
+}
+
+impl From<NibbleArray> for Tag {
+    fn from(array: NibbleArray) -> Self {
+        array.into_tag()
+    }
+}
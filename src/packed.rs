@@ -0,0 +1,236 @@
+//! Fixed-width unsigned integers packed into longs, as used for block states, biomes and
+//! heightmaps. Values are stored from the least significant bit of each long upwards.
+
+use std::fmt;
+
+/// How entries are laid out across longs
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Layout {
+    /// Entries never straddle two longs; leftover high bits are padding (1.16+, data
+    /// version 2527 onwards)
+    Aligned,
+    /// Entries are packed back to back and may continue in the next long (before 1.16)
+    Spanning,
+}
+
+impl Layout {
+    /// The layout used by a given data version
+    pub fn for_data_version(data_version: i32) -> Layout {
+        if data_version >= 2527 {
+            Layout::Aligned
+        } else {
+            Layout::Spanning
+        }
+    }
+}
+
+/// Bits per entry plus layout: everything needed to address entries in a long slice
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) struct Packing {
+    pub bits: u32,
+    pub layout: Layout,
+}
+
+impl Packing {
+    fn mask(self) -> u64 {
+        if self.bits == 64 {
+            u64::MAX
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    pub fn longs_needed(self, len: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        match self.layout {
+            Layout::Aligned => len.div_ceil((64 / self.bits) as usize),
+            Layout::Spanning => (len * self.bits as usize).div_ceil(64),
+        }
+    }
+
+    /// Long index and bit offset of entry `i`
+    fn locate(self, i: usize) -> (usize, u32) {
+        match self.layout {
+            Layout::Aligned => {
+                let per_long = (64 / self.bits) as usize;
+                (i / per_long, (i % per_long) as u32 * self.bits)
+            }
+            Layout::Spanning => {
+                let bit = i * self.bits as usize;
+                (bit / 64, (bit % 64) as u32)
+            }
+        }
+    }
+
+    pub fn get(self, data: &[i64], i: usize) -> u64 {
+        if self.bits == 0 {
+            return 0;
+        }
+        let (long, offset) = self.locate(i);
+        let mut value = data[long] as u64 >> offset;
+        if offset + self.bits > 64 {
+            value |= (data[long + 1] as u64) << (64 - offset);
+        }
+        value & self.mask()
+    }
+
+    pub fn set(self, data: &mut [i64], i: usize, value: u64) {
+        if self.bits == 0 {
+            return;
+        }
+        let mask = self.mask();
+        let value = value & mask;
+        let (long, offset) = self.locate(i);
+        let word = data[long] as u64 & !(mask << offset) | value << offset;
+        data[long] = word as i64;
+        if offset + self.bits > 64 {
+            let spill = offset + self.bits - 64;
+            let high = mask >> (self.bits - spill);
+            let word = data[long + 1] as u64 & !high | value >> (64 - offset);
+            data[long + 1] = word as i64;
+        }
+    }
+}
+
+/// A fixed-length array of `bits`-wide unsigned entries packed into longs
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PackedArray {
+    packing: Packing,
+    len: usize,
+    data: Vec<i64>,
+}
+
+impl PackedArray {
+    /// `len` zero entries. `bits` must be at most 64.
+    pub fn new(bits: u32, len: usize, layout: Layout) -> Self {
+        assert!(bits <= 64, "cannot pack {} bits per entry", bits);
+        let packing = Packing { bits, layout };
+        PackedArray {
+            packing,
+            len,
+            data: vec![0; packing.longs_needed(len)],
+        }
+    }
+
+    /// Wrap longs read from a LongArray, checking there are exactly as many as needed
+    pub fn from_longs(
+        data: Vec<i64>,
+        bits: u32,
+        len: usize,
+        layout: Layout,
+    ) -> Result<Self, PackedArrayError> {
+        assert!(bits <= 64, "cannot pack {} bits per entry", bits);
+        let packing = Packing { bits, layout };
+        let expected = packing.longs_needed(len);
+        if data.len() != expected {
+            return Err(PackedArrayError {
+                expected,
+                found: data.len(),
+            });
+        }
+        Ok(PackedArray { packing, len, data })
+    }
+
+    /// Pack `values`, each of which must fit in `bits`
+    pub fn from_values(values: &[u64], bits: u32, layout: Layout) -> Self {
+        let mut array = PackedArray::new(bits, values.len(), layout);
+        for (i, &v) in values.iter().enumerate() {
+            array.set(i, v);
+        }
+        array
+    }
+
+    /// Number of longs needed for `len` entries of `bits` bits
+    pub fn longs_needed(bits: u32, len: usize, layout: Layout) -> usize {
+        Packing { bits, layout }.longs_needed(len)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.packing.bits
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.packing.layout
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Panics if `i` is out of bounds
+    pub fn get(&self, i: usize) -> u64 {
+        assert!(
+            i < self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        self.packing.get(&self.data, i)
+    }
+
+    /// Panics if `i` is out of bounds; bits of `value` above `bits` are dropped
+    pub fn set(&mut self, i: usize, value: u64) {
+        assert!(
+            i < self.len,
+            "index {} out of bounds for length {}",
+            i,
+            self.len
+        );
+        self.packing.set(&mut self.data, i, value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.len).map(|i| self.packing.get(&self.data, i))
+    }
+
+    /// The same entries with a different width or layout
+    pub fn repack(&self, bits: u32, layout: Layout) -> PackedArray {
+        let mut out = PackedArray::new(bits, self.len, layout);
+        for (i, v) in self.iter().enumerate() {
+            out.set(i, v);
+        }
+        out
+    }
+
+    pub fn as_longs(&self) -> &[i64] {
+        &self.data
+    }
+
+    pub fn into_longs(self) -> Vec<i64> {
+        self.data
+    }
+}
+
+/// Error from [`PackedArray::from_longs`] when the long count doesn't match
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PackedArrayError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for PackedArrayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} longs of packed data, found {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for PackedArrayError {}
+
+/// Bits needed to store indices into a palette of `len` entries
+pub(crate) fn ceil_log2(len: usize) -> u32 {
+    if len <= 1 {
+        0
+    } else {
+        usize::BITS - (len - 1).leading_zeros()
+    }
+}
@@ -0,0 +1,213 @@
+use std::{fmt, str::FromStr};
+
+use crate::Tag;
+
+/// One step of an [`NbtPath`]: a compound key or a list/array index
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Location of a tag inside a tree, written the vanilla way: `Data.Player.Pos[0]`.
+/// Keys that aren't plain identifiers are quoted: `"my key".x`.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Default)]
+pub struct NbtPath {
+    pub segments: Vec<PathSegment>,
+}
+
+impl NbtPath {
+    /// The empty path, addressing the root tag
+    pub fn root() -> Self {
+        NbtPath::default()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// This path extended by a compound key
+    pub fn key(&self, key: impl Into<String>) -> Self {
+        let mut p = self.clone();
+        p.segments.push(PathSegment::Key(key.into()));
+        p
+    }
+
+    /// This path extended by a list index
+    pub fn index(&self, index: usize) -> Self {
+        let mut p = self.clone();
+        p.segments.push(PathSegment::Index(index));
+        p
+    }
+
+    /// The final compound key, if the path ends in one
+    pub fn last_key(&self) -> Option<&str> {
+        match self.segments.last() {
+            Some(PathSegment::Key(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Split into the parent path and the last segment (None for the root)
+    pub fn split_last(&self) -> Option<(NbtPath, &PathSegment)> {
+        let (last, parent) = self.segments.split_last()?;
+        Some((
+            NbtPath {
+                segments: parent.to_vec(),
+            },
+            last,
+        ))
+    }
+}
+
+/// Whether a compound key can be written without quotes in SNBT and NBT paths
+pub(crate) fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'+'))
+}
+
+/// Write `s` as a double-quoted string with `"` and `\` escaped
+pub(crate) fn write_quoted(f: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        if matches!(c, '"' | '\\') {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+impl fmt::Display for NbtPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    if is_bare_key(key) {
+                        f.write_str(key)?;
+                    } else {
+                        write_quoted(f, key)?;
+                    }
+                }
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when a string is not a valid [`NbtPath`]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParsePathError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParsePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParsePathError {}
+
+impl FromStr for NbtPath {
+    type Err = ParsePathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |position, message| Err(ParsePathError { position, message });
+        let bytes = s.as_bytes();
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'[' => {
+                    let end = match s[i..].find(']') {
+                        Some(end) => i + end,
+                        None => return err(i, "unclosed '['"),
+                    };
+                    match s[i + 1..end].trim().parse() {
+                        Ok(index) => segments.push(PathSegment::Index(index)),
+                        Err(_) => return err(i + 1, "expected a list index"),
+                    }
+                    i = end + 1;
+                }
+                b'.' if !segments.is_empty()
+                    && i + 1 < bytes.len()
+                    && !matches!(bytes[i + 1], b'.' | b'[') =>
+                {
+                    i += 1;
+                    continue;
+                }
+                b'.' => return err(i, "unexpected '.'"),
+                b'"' => {
+                    let mut key = String::new();
+                    let mut chars = s[i + 1..].char_indices();
+                    let mut closed = None;
+                    while let Some((j, c)) = chars.next() {
+                        match c {
+                            '\\' => match chars.next() {
+                                Some((_, e)) => key.push(e),
+                                None => break,
+                            },
+                            '"' => {
+                                closed = Some(i + 1 + j + 1);
+                                break;
+                            }
+                            c => key.push(c),
+                        }
+                    }
+                    match closed {
+                        Some(end) => i = end,
+                        None => return err(i, "unclosed quoted key"),
+                    }
+                    segments.push(PathSegment::Key(key));
+                }
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && !matches!(bytes[i], b'.' | b'[' | b'"') {
+                        i += 1;
+                    }
+                    segments.push(PathSegment::Key(s[start..i].to_string()));
+                }
+            }
+            if i < bytes.len() && !matches!(bytes[i], b'.' | b'[') {
+                return err(i, "expected '.' or '['");
+            }
+        }
+        Ok(NbtPath { segments })
+    }
+}
+
+impl Tag {
+    /// Follow `path` from this tag. Indices only address List elements.
+    pub fn get_path(&self, path: &NbtPath) -> Option<&Tag> {
+        let mut cur = self;
+        for segment in &path.segments {
+            cur = match (segment, cur) {
+                (PathSegment::Key(key), Tag::Compound { entries, .. }) => entries.get(key)?,
+                (PathSegment::Index(i), Tag::List { elements, .. }) => elements.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+
+    /// Mutable version of [`Tag::get_path`]
+    pub fn get_path_mut(&mut self, path: &NbtPath) -> Option<&mut Tag> {
+        let mut cur = self;
+        for segment in &path.segments {
+            cur = match (segment, cur) {
+                (PathSegment::Key(key), Tag::Compound { entries, .. }) => entries.get_mut(key)?,
+                (PathSegment::Index(i), Tag::List { elements, .. }) => elements.get_mut(*i)?,
+                _ => return None,
+            };
+        }
+        Some(cur)
+    }
+}
@@ -0,0 +1,372 @@
+//! Typed access to `playerdata/<uuid>.dat`. [`PlayerData`] models the current (1.20.5+)
+//! layout of the fields tools usually care about and keeps all other keys in `extra`.
+//! Older files are best upgraded with [`DataFixer`](crate::DataFixer) first; fields they
+//! store under legacy names end up in `extra` untouched.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    FromNbt, FromNbtError, Reader, Tag, ToNbt, Writer,
+    typed::{backup_path, entries_of, put, put_some, save_with_backup, take},
+};
+
+/// A player .dat file. Each modeled field is `None` when the file doesn't have its key,
+/// and is only written back when set.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct PlayerData {
+    /// `DataVersion`
+    pub data_version: Option<i32>,
+    /// `Dimension`, e.g. `minecraft:overworld`
+    pub dimension: Option<String>,
+    /// `Pos`
+    pub pos: Option<[f64; 3]>,
+    /// `Motion`
+    pub motion: Option<[f64; 3]>,
+    /// `Rotation`: yaw, pitch
+    pub rotation: Option<[f32; 2]>,
+    /// `OnGround`
+    pub on_ground: Option<bool>,
+    /// `Health`
+    pub health: Option<f32>,
+    /// `foodLevel`
+    pub food_level: Option<i32>,
+    /// `XpLevel`
+    pub xp_level: Option<i32>,
+    /// `XpTotal`
+    pub xp_total: Option<i32>,
+    /// `playerGameType`
+    pub game_type: Option<i32>,
+    /// `SelectedItemSlot`
+    pub selected_item_slot: Option<i32>,
+    /// `Inventory`
+    pub inventory: Option<Vec<ItemStack>>,
+    /// `EnderItems`
+    pub ender_items: Option<Vec<ItemStack>>,
+    /// `abilities`
+    pub abilities: Option<Abilities>,
+    /// `attributes`
+    pub attributes: Option<Vec<Attribute>>,
+    /// `active_effects`
+    pub active_effects: Option<Vec<Effect>>,
+    /// Every other key
+    pub extra: HashMap<String, Tag>,
+}
+
+/// An item stack in an inventory
+#[derive(PartialEq, Clone, Debug)]
+pub struct ItemStack {
+    /// `Slot`; inventory slots 0-8 are the hotbar, 100-103 armor, -106 the offhand
+    pub slot: Option<i8>,
+    /// `id`
+    pub id: String,
+    /// `count`; vanilla reads a missing count as 1
+    pub count: Option<i32>,
+    /// `components` and any other keys, along with a `Slot` or `count` that wasn't stored
+    /// as a Byte or Int, so an unchanged one is saved with its original type
+    pub extra: HashMap<String, Tag>,
+}
+
+/// The `abilities` compound
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Abilities {
+    pub flying: Option<bool>,
+    /// `flySpeed`; 0.05 by default
+    pub fly_speed: Option<f32>,
+    pub instabuild: Option<bool>,
+    pub invulnerable: Option<bool>,
+    /// `mayBuild`; true by default
+    pub may_build: Option<bool>,
+    pub mayfly: Option<bool>,
+    /// `walkSpeed`; 0.1 by default
+    pub walk_speed: Option<f32>,
+    pub extra: HashMap<String, Tag>,
+}
+
+/// An entry of `attributes`
+#[derive(PartialEq, Clone, Debug)]
+pub struct Attribute {
+    /// `id`, e.g. `minecraft:max_health`
+    pub id: String,
+    /// `base`
+    pub base: Option<f64>,
+    /// `modifiers`, kept as raw NBT
+    pub modifiers: Option<Vec<Tag>>,
+    pub extra: HashMap<String, Tag>,
+}
+
+/// An entry of `active_effects`
+#[derive(PartialEq, Clone, Debug)]
+pub struct Effect {
+    /// `id`, e.g. `minecraft:speed`
+    pub id: String,
+    /// `amplifier`; level 1 is amplifier 0
+    pub amplifier: Option<i8>,
+    /// `duration` in ticks, -1 for infinite
+    pub duration: Option<i32>,
+    pub extra: HashMap<String, Tag>,
+}
+
+impl PlayerData {
+    /// Read a gzip-compressed player file
+    pub fn read<R: Read>(reader: R) -> io::Result<PlayerData> {
+        let root = Reader::from_gzip(reader).read_tag()?;
+        PlayerData::from_nbt(&root).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write a gzip-compressed player file
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = Writer::to_gzip(writer);
+        writer.write_tag(&self.to_nbt().with_name(""))?;
+        writer.into_inner().finish()?.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<PlayerData> {
+        PlayerData::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Load `path`, falling back to its `.dat_old` backup if it is missing or corrupt
+    pub fn load_with_backup(path: impl AsRef<Path>) -> io::Result<PlayerData> {
+        let path = path.as_ref();
+        PlayerData::load(path).or_else(|e| PlayerData::load(backup_path(path)).map_err(|_| e))
+    }
+
+    /// Save the way vanilla does: write a temporary file next to `path`, move the
+    /// current file to `.dat_old`, then move the new file into place. A crash mid-save
+    /// leaves either the old file or its backup intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save_with_backup(path.as_ref(), |file| self.write(file))
+    }
+
+    /// The player's `UUID`, in whichever form the file stores it
+    pub fn uuid(&self) -> Option<u128> {
+        crate::uuid::uuid_in(|key| self.extra.get(key), "UUID")
+    }
+
+    /// The inventory item in `slot`
+    pub fn inventory_slot(&self, slot: i8) -> Option<&ItemStack> {
+        self.inventory
+            .iter()
+            .flatten()
+            .find(|item| item.slot == Some(slot))
+    }
+
+    /// Total count of items with `id` across the inventory and ender chest
+    pub fn count_items(&self, id: &str) -> i64 {
+        self.inventory
+            .iter()
+            .chain(&self.ender_items)
+            .flatten()
+            .filter(|item| item.id == id)
+            .map(|item| item.count.unwrap_or(1) as i64)
+            .sum()
+    }
+
+    pub fn attribute(&self, id: &str) -> Option<&Attribute> {
+        self.attributes.iter().flatten().find(|a| a.id == id)
+    }
+
+    pub fn effect(&self, id: &str) -> Option<&Effect> {
+        self.active_effects.iter().flatten().find(|e| e.id == id)
+    }
+}
+
+fn take_array<T: FromNbt, const N: usize>(
+    entries: &mut HashMap<String, Tag>,
+    key: &str,
+) -> Result<Option<[T; N]>, FromNbtError> {
+    take::<Vec<T>>(entries, key)?
+        .map(|v| {
+            v.try_into().map_err(|v: Vec<T>| {
+                FromNbtError::custom(format!("expected {} elements, found {}", N, v.len()))
+                    .at_key(key)
+            })
+        })
+        .transpose()
+}
+
+impl FromNbt for PlayerData {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(PlayerData {
+            data_version: take(&mut e, "DataVersion")?,
+            dimension: take(&mut e, "Dimension")?,
+            pos: take_array(&mut e, "Pos")?,
+            motion: take_array(&mut e, "Motion")?,
+            rotation: take_array(&mut e, "Rotation")?,
+            on_ground: take(&mut e, "OnGround")?,
+            health: take(&mut e, "Health")?,
+            food_level: take(&mut e, "foodLevel")?,
+            xp_level: take(&mut e, "XpLevel")?,
+            xp_total: take(&mut e, "XpTotal")?,
+            game_type: take(&mut e, "playerGameType")?,
+            selected_item_slot: take(&mut e, "SelectedItemSlot")?,
+            inventory: take(&mut e, "Inventory")?,
+            ender_items: take(&mut e, "EnderItems")?,
+            abilities: take(&mut e, "abilities")?,
+            attributes: take(&mut e, "attributes")?,
+            active_effects: take(&mut e, "active_effects")?,
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for PlayerData {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put_some(&mut e, "DataVersion", self.data_version);
+        put_some(&mut e, "Dimension", self.dimension.as_ref());
+        put_some(&mut e, "Pos", self.pos.map(Vec::from));
+        put_some(&mut e, "Motion", self.motion.map(Vec::from));
+        put_some(&mut e, "Rotation", self.rotation.map(Vec::from));
+        put_some(&mut e, "OnGround", self.on_ground);
+        put_some(&mut e, "Health", self.health);
+        put_some(&mut e, "foodLevel", self.food_level);
+        put_some(&mut e, "XpLevel", self.xp_level);
+        put_some(&mut e, "XpTotal", self.xp_total);
+        put_some(&mut e, "playerGameType", self.game_type);
+        put_some(&mut e, "SelectedItemSlot", self.selected_item_slot);
+        put_some(&mut e, "Inventory", self.inventory.as_ref());
+        put_some(&mut e, "EnderItems", self.ender_items.as_ref());
+        put_some(&mut e, "abilities", self.abilities.as_ref());
+        put_some(&mut e, "attributes", self.attributes.as_ref());
+        put_some(&mut e, "active_effects", self.active_effects.as_ref());
+        Tag::from(e)
+    }
+}
+
+/// Read a key of any numeric type. One not stored as `id` also stays in `entries`, so
+/// [`put_numeric`] can write an unchanged value back with its original type.
+fn take_numeric<T>(
+    entries: &mut HashMap<String, Tag>,
+    key: &str,
+    id: u8,
+    read: fn(&Tag) -> Option<T>,
+) -> Result<Option<T>, FromNbtError> {
+    let Some(tag) = entries.get(key) else {
+        return Ok(None);
+    };
+    let value = read(tag).ok_or_else(|| FromNbtError::wrong_type(id, tag).at_key(key))?;
+    if tag.id() == id {
+        entries.remove(key);
+    }
+    Ok(Some(value))
+}
+
+/// Write a field read with [`take_numeric`], keeping the tag already in `entries` if it
+/// still holds the same value. `None` removes the key.
+fn put_numeric<T: ToNbt + PartialEq>(
+    entries: &mut HashMap<String, Tag>,
+    key: &str,
+    value: Option<T>,
+    read: fn(&Tag) -> Option<T>,
+) {
+    match value {
+        Some(v) if entries.get(key).and_then(read).as_ref() == Some(&v) => {}
+        Some(v) => put(entries, key, v),
+        None => {
+            entries.remove(key);
+        }
+    }
+}
+
+impl FromNbt for ItemStack {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(ItemStack {
+            // Plugins write these as any numeric type, which vanilla accepts
+            slot: take_numeric(&mut e, "Slot", 1, Tag::as_byte)?,
+            id: take(&mut e, "id")?.ok_or_else(|| FromNbtError::missing_field("id"))?,
+            count: take_numeric(&mut e, "count", 3, Tag::as_int)?,
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for ItemStack {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put_numeric(&mut e, "Slot", self.slot, Tag::as_byte);
+        put(&mut e, "id", &self.id);
+        put_numeric(&mut e, "count", self.count, Tag::as_int);
+        Tag::from(e)
+    }
+}
+
+impl FromNbt for Abilities {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(Abilities {
+            flying: take(&mut e, "flying")?,
+            fly_speed: take(&mut e, "flySpeed")?,
+            instabuild: take(&mut e, "instabuild")?,
+            invulnerable: take(&mut e, "invulnerable")?,
+            may_build: take(&mut e, "mayBuild")?,
+            mayfly: take(&mut e, "mayfly")?,
+            walk_speed: take(&mut e, "walkSpeed")?,
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for Abilities {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put_some(&mut e, "flying", self.flying);
+        put_some(&mut e, "flySpeed", self.fly_speed);
+        put_some(&mut e, "instabuild", self.instabuild);
+        put_some(&mut e, "invulnerable", self.invulnerable);
+        put_some(&mut e, "mayBuild", self.may_build);
+        put_some(&mut e, "mayfly", self.mayfly);
+        put_some(&mut e, "walkSpeed", self.walk_speed);
+        Tag::from(e)
+    }
+}
+
+impl FromNbt for Attribute {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(Attribute {
+            id: take(&mut e, "id")?.ok_or_else(|| FromNbtError::missing_field("id"))?,
+            base: take(&mut e, "base")?,
+            modifiers: take(&mut e, "modifiers")?,
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for Attribute {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put(&mut e, "id", &self.id);
+        put_some(&mut e, "base", self.base);
+        put_some(&mut e, "modifiers", self.modifiers.as_ref());
+        Tag::from(e)
+    }
+}
+
+impl FromNbt for Effect {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        let mut e = entries_of(tag)?;
+        Ok(Effect {
+            id: take(&mut e, "id")?.ok_or_else(|| FromNbtError::missing_field("id"))?,
+            amplifier: take(&mut e, "amplifier")?,
+            duration: take(&mut e, "duration")?,
+            extra: e,
+        })
+    }
+}
+
+impl ToNbt for Effect {
+    fn to_nbt(&self) -> Tag {
+        let mut e = self.extra.clone();
+        put(&mut e, "id", &self.id);
+        put_some(&mut e, "amplifier", self.amplifier);
+        put_some(&mut e, "duration", self.duration);
+        Tag::from(e)
+    }
+}
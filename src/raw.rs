@@ -0,0 +1,194 @@
+//! Tags exactly as they were encoded, for tools that must write back what they read
+//! byte for byte.
+//!
+//! [`Reader`](crate::Reader) normalizes what it reads: strings must be valid UTF-8,
+//! compound entries lose their order, empty lists forget their element type, negative
+//! list lengths are rejected. A [`RawTag`] keeps all of that, along with float bit
+//! patterns and duplicate keys, so
+//! [`Reader::read_raw_tag`](crate::Reader::read_raw_tag) followed by
+//! [`Writer::write_raw_tag`](crate::Writer::write_raw_tag) reproduces the input.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{Tag, TagId};
+
+/// A tag payload as stored. Names live with the entries of a [`RawTag::Compound`], or
+/// next to the root tag.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum RawTag {
+    /// A lone End tag, as a root
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    /// Bits, so NaN payloads survive and equality is exact
+    Float(u32),
+    Double(u64),
+    ByteArray(Vec<u8>),
+    String(RawString),
+    /// `element_id` as written, even when the list is empty or the ID is unusual.
+    /// `negative_len` keeps a negative length prefix, which vanilla reads as an empty
+    /// list; such a list has no elements.
+    List {
+        element_id: TagId,
+        elements: Vec<RawTag>,
+        negative_len: Option<i32>,
+    },
+    /// Entries in file order, duplicates included
+    Compound(Vec<(RawString, RawTag)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl RawTag {
+    pub fn id(&self) -> TagId {
+        match self {
+            RawTag::End => 0,
+            RawTag::Byte(_) => 1,
+            RawTag::Short(_) => 2,
+            RawTag::Int(_) => 3,
+            RawTag::Long(_) => 4,
+            RawTag::Float(_) => 5,
+            RawTag::Double(_) => 6,
+            RawTag::ByteArray(_) => 7,
+            RawTag::String(_) => 8,
+            RawTag::List { .. } => 9,
+            RawTag::Compound(_) => 10,
+            RawTag::IntArray(_) => 11,
+            RawTag::LongArray(_) => 12,
+        }
+    }
+
+    /// The normal, lossy view: strings decoded with replacement characters, the last of
+    /// any duplicate keys kept, list element types normalized
+    pub fn to_tag(&self, name: Option<String>) -> Tag {
+        match self {
+            RawTag::End => Tag::End,
+            RawTag::Byte(value) => Tag::Byte {
+                name,
+                value: *value,
+            },
+            RawTag::Short(value) => Tag::Short {
+                name,
+                value: *value,
+            },
+            RawTag::Int(value) => Tag::Int {
+                name,
+                value: *value,
+            },
+            RawTag::Long(value) => Tag::Long {
+                name,
+                value: *value,
+            },
+            RawTag::Float(bits) => Tag::Float {
+                name,
+                value: f32::from_bits(*bits),
+            },
+            RawTag::Double(bits) => Tag::Double {
+                name,
+                value: f64::from_bits(*bits),
+            },
+            RawTag::ByteArray(value) => Tag::ByteArray {
+                name,
+                value: value.clone(),
+            },
+            RawTag::String(value) => Tag::String {
+                name,
+                value: value.to_string_lossy(),
+            },
+            RawTag::List { elements, .. } => {
+                let elements: Vec<Tag> = elements.iter().map(|e| e.to_tag(None)).collect();
+                let mut list = Tag::from(elements);
+                list.set_name(name);
+                list
+            }
+            RawTag::Compound(entries) => {
+                let mut map = HashMap::new();
+                for (key, entry) in entries {
+                    let key = key.to_string_lossy();
+                    map.insert(key.clone(), entry.to_tag(Some(key)));
+                }
+                Tag::Compound { name, entries: map }
+            }
+            RawTag::IntArray(value) => Tag::IntArray {
+                name,
+                value: value.clone(),
+            },
+            RawTag::LongArray(value) => Tag::LongArray {
+                name,
+                value: value.clone(),
+            },
+        }
+    }
+}
+
+/// String bytes as stored: normally Java's modified UTF-8, but kept even when they aren't
+#[derive(PartialEq, Eq, Hash, Clone, Default)]
+pub struct RawString(pub Vec<u8>);
+
+impl RawString {
+    /// The bytes as a `&str`, if they happen to be valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    /// Decode modified UTF-8 (also accepting standard UTF-8), replacing anything
+    /// malformed with U+FFFD
+    pub fn to_string_lossy(&self) -> String {
+        let b = &self.0;
+        let mut units = Vec::with_capacity(b.len());
+        let mut i = 0;
+        while i < b.len() {
+            let cont = |j: usize| {
+                b.get(i + j)
+                    .filter(|&&c| c & 0xc0 == 0x80)
+                    .map(|&c| (c & 0x3f) as u32)
+            };
+            // Java encodes supplementary characters as two 3-byte surrogates; standard
+            // UTF-8 uses one 4-byte sequence
+            let (code, len) = match b[i] {
+                c @ 0x00..0x80 => (c as u32, 1),
+                c @ 0xc0..0xe0 => match cont(1) {
+                    Some(x) => (((c & 0x1f) as u32) << 6 | x, 2),
+                    None => (0xfffd, 1),
+                },
+                c @ 0xe0..0xf0 => match (cont(1), cont(2)) {
+                    (Some(x), Some(y)) => (((c & 0x0f) as u32) << 12 | x << 6 | y, 3),
+                    _ => (0xfffd, 1),
+                },
+                c @ 0xf0..0xf8 => match (cont(1), cont(2), cont(3)) {
+                    (Some(x), Some(y), Some(z)) => {
+                        (((c & 0x07) as u32) << 18 | x << 12 | y << 6 | z, 4)
+                    }
+                    _ => (0xfffd, 1),
+                },
+                _ => (0xfffd, 1),
+            };
+            match char::from_u32(code) {
+                Some(c) if code > 0xffff => {
+                    units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+                }
+                _ if code > 0x10ffff => units.push(0xfffd),
+                _ => units.push(code as u16),
+            }
+            i += len;
+        }
+        String::from_utf16_lossy(&units)
+    }
+}
+
+impl From<&str> for RawString {
+    fn from(s: &str) -> RawString {
+        RawString(s.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Debug for RawString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(s) => write!(f, "{:?}", s),
+            None => write!(f, "RawString({:?})", self.0),
+        }
+    }
+}
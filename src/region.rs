@@ -0,0 +1,368 @@
+//! Anvil region files (`r.<x>.<z>.mca`), each holding up to 32×32 chunks.
+//!
+//! The file is split into 4096-byte sectors. The first sector has one location per
+//! chunk: a 3-byte sector offset and a 1-byte sector count. The second has one
+//! timestamp per chunk, in seconds. Each chunk starts with a 4-byte length and a
+//! compression byte, followed by the compressed NBT.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Compression, Reader, Tag, Writer};
+
+pub const SECTOR_LEN: usize = 4096;
+/// Chunks along each side of a region
+pub const REGION_WIDTH: usize = 32;
+const CHUNKS: usize = REGION_WIDTH * REGION_WIDTH;
+/// Flag on the compression byte of chunks stored in a separate `c.<x>.<z>.mcc` file
+const EXTERNAL: u8 = 0x80;
+
+/// Where a chunk sits in the file and how it is stored
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ChunkInfo {
+    /// Chunk coordinates within the region, in `0..32`
+    pub x: usize,
+    pub z: usize,
+    /// First sector of the chunk
+    pub sector: u32,
+    /// Sectors reserved for the chunk
+    pub sectors: u8,
+    /// Last save, in seconds since the Unix epoch
+    pub timestamp: u32,
+    /// Bytes of chunk data after the length prefix, including the compression byte
+    pub length: u32,
+    /// The compression byte: 1 gzip, 2 zlib, 3 none, 4 LZ4, with 0x80 set for chunks
+    /// stored in an external `.mcc` file
+    pub compression: u8,
+}
+
+impl ChunkInfo {
+    pub fn is_external(&self) -> bool {
+        self.compression & EXTERNAL != 0
+    }
+}
+
+/// Something wrong with a chunk's entry, found by [`Region::check`]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ChunkProblem {
+    pub x: usize,
+    pub z: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ChunkProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunk {}, {}: {}", self.x, self.z, self.message)
+    }
+}
+
+/// A region file. Chunks are addressed by their coordinates in the world or within the
+/// region; only the low five bits are used.
+pub struct Region<F> {
+    file: F,
+    locations: [u32; CHUNKS],
+    timestamps: [u32; CHUNKS],
+}
+
+impl Region<File> {
+    /// Open a region file for reading and writing, creating it if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Region::new(file)
+    }
+}
+
+fn index(x: i32, z: i32) -> usize {
+    (x & 31) as usize + (z & 31) as usize * REGION_WIDTH
+}
+
+fn invalid(message: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+impl<F: Read + Seek> Region<F> {
+    /// Read the header. An empty file is an empty region.
+    pub fn new(mut file: F) -> io::Result<Self> {
+        let mut header = vec![0u8; 2 * SECTOR_LEN];
+        file.seek(SeekFrom::Start(0))?;
+        let mut filled = 0;
+        while filled < header.len() {
+            match file.read(&mut header[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled != 0 && filled != header.len() {
+            return Err(invalid("region file header is truncated"));
+        }
+        let word = |i: usize| u32::from_be_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+        Ok(Region {
+            file,
+            locations: std::array::from_fn(word),
+            timestamps: std::array::from_fn(|i| word(CHUNKS + i)),
+        })
+    }
+
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    pub fn contains(&self, x: i32, z: i32) -> bool {
+        self.locations[index(x, z)] != 0
+    }
+
+    /// Coordinates of every chunk present, in `0..32`
+    pub fn chunk_positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..CHUNKS)
+            .filter(|&i| self.locations[i] != 0)
+            .map(|i| (i % REGION_WIDTH, i / REGION_WIDTH))
+    }
+
+    /// Location, timestamp and chunk header of a chunk, if present
+    pub fn info(&mut self, x: i32, z: i32) -> io::Result<Option<ChunkInfo>> {
+        let i = index(x, z);
+        let location = self.locations[i];
+        if location == 0 {
+            return Ok(None);
+        }
+        let sector = location >> 8;
+        let mut header = [0u8; 5];
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_LEN as u64))?;
+        self.file.read_exact(&mut header)?;
+        Ok(Some(ChunkInfo {
+            x: i % REGION_WIDTH,
+            z: i / REGION_WIDTH,
+            sector,
+            sectors: location as u8,
+            timestamp: self.timestamps[i],
+            length: u32::from_be_bytes(header[..4].try_into().unwrap()),
+            compression: header[4],
+        }))
+    }
+
+    /// The compression byte and the still-compressed data of a chunk
+    pub fn read_chunk_raw(&mut self, x: i32, z: i32) -> io::Result<Option<(u8, Vec<u8>)>> {
+        let Some(info) = self.info(x, z)? else {
+            return Ok(None);
+        };
+        if info.is_external() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "chunk is stored in an external .mcc file",
+            ));
+        }
+        if info.sector < 2 || info.sectors == 0 {
+            return Err(invalid(format!(
+                "chunk entry points at {} sectors from sector {}",
+                info.sectors, info.sector
+            )));
+        }
+        let available = info.sectors as usize * SECTOR_LEN - 4;
+        if info.length == 0 || info.length as usize > available {
+            return Err(invalid(format!(
+                "chunk length {} does not fit its {} sectors",
+                info.length, info.sectors
+            )));
+        }
+        let mut data = vec![0u8; info.length as usize - 1];
+        self.file.read_exact(&mut data)?;
+        Ok(Some((info.compression, data)))
+    }
+
+    /// Read and decompress a chunk
+    pub fn read_chunk(&mut self, x: i32, z: i32) -> io::Result<Option<Tag>> {
+        let Some((compression, data)) = self.read_chunk_raw(x, z)? else {
+            return Ok(None);
+        };
+        let tag = match compression {
+            1 => Reader::from_gzip(&data[..]).read_tag()?,
+            2 => Reader::from_zlib(&data[..]).read_tag()?,
+            3 => Reader::new(&data[..]).read_tag()?,
+            other => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("unsupported chunk compression {}", other),
+                ));
+            }
+        };
+        Ok(Some(tag))
+    }
+
+    /// Look for entries pointing into the header or past the end of the file, chunks
+    /// sharing sectors, bad lengths and chunks that don't decode. Chunks in external
+    /// `.mcc` files or compressed with LZ4 or a custom scheme (1.20.5+) aren't decoded.
+    pub fn check(&mut self) -> io::Result<Vec<ChunkProblem>> {
+        let file_sectors = self
+            .file
+            .seek(SeekFrom::End(0))?
+            .div_ceil(SECTOR_LEN as u64);
+        let mut problems = Vec::new();
+        let mut owner: Vec<Option<usize>> = vec![None; file_sectors as usize];
+        for i in 0..CHUNKS {
+            let location = self.locations[i];
+            if location == 0 {
+                continue;
+            }
+            let (x, z) = (i % REGION_WIDTH, i / REGION_WIDTH);
+            let mut problem = |message: String| problems.push(ChunkProblem { x, z, message });
+            let (start, count) = ((location >> 8) as usize, (location & 0xff) as usize);
+            if start < 2 {
+                problem(format!("starts at sector {}, inside the header", start));
+                continue;
+            }
+            if count == 0 {
+                problem("has no sectors".to_string());
+                continue;
+            }
+            if (start + count) as u64 > file_sectors {
+                problem(format!(
+                    "sectors {}..{} run past the end of the file ({} sectors)",
+                    start,
+                    start + count,
+                    file_sectors
+                ));
+                continue;
+            }
+            let mut overlapped = false;
+            for slot in &mut owner[start..start + count] {
+                match *slot {
+                    Some(other) if !overlapped => {
+                        overlapped = true;
+                        problem(format!(
+                            "overlaps chunk {}, {}",
+                            other % REGION_WIDTH,
+                            other / REGION_WIDTH
+                        ));
+                    }
+                    Some(_) => {}
+                    None => *slot = Some(i),
+                }
+            }
+            if overlapped {
+                continue;
+            }
+            let undecodable = self
+                .info(x as i32, z as i32)?
+                .is_some_and(|info| info.is_external() || matches!(info.compression, 4 | 127));
+            if !undecodable && let Err(e) = self.read_chunk(x as i32, z as i32) {
+                problem(e.to_string());
+            }
+        }
+        Ok(problems)
+    }
+}
+
+impl<F: Read + Write + Seek> Region<F> {
+    /// Compress `tag` with zlib and store it, stamped with the current time. The chunk
+    /// is rewritten in place when it still fits, and otherwise moved to the first gap
+    /// big enough or the end of the file.
+    pub fn write_chunk(&mut self, x: i32, z: i32, tag: &Tag) -> io::Result<()> {
+        let mut writer = Writer::to_zlib(Vec::new());
+        writer.write_tag(tag)?;
+        let data = writer.into_inner().finish()?;
+        self.write_chunk_raw(x, z, Compression::Zlib, &data)
+    }
+
+    /// Store already-compressed chunk data, stamped with the current time
+    pub fn write_chunk_raw(
+        &mut self,
+        x: i32,
+        z: i32,
+        compression: Compression,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let i = index(x, z);
+        let length = data.len() + 1;
+        let sectors = (4 + length).div_ceil(SECTOR_LEN);
+        if sectors > u8::MAX as usize {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "chunk is too large for a region file",
+            ));
+        }
+        let start = self.allocate(i, sectors)?;
+
+        let mut buf = Vec::with_capacity(sectors * SECTOR_LEN);
+        buf.extend_from_slice(&(length as u32).to_be_bytes());
+        buf.push(match compression {
+            Compression::Gzip => 1,
+            Compression::Zlib => 2,
+            Compression::None => 3,
+        });
+        buf.extend_from_slice(data);
+        buf.resize(sectors * SECTOR_LEN, 0);
+        self.file
+            .seek(SeekFrom::Start((start * SECTOR_LEN) as u64))?;
+        self.file.write_all(&buf)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        self.set_entry(i, (start as u32) << 8 | sectors as u32, now)
+    }
+
+    /// Remove a chunk from the header, returning whether it was present. Its sectors
+    /// are left in place and reused by later writes.
+    pub fn delete_chunk(&mut self, x: i32, z: i32) -> io::Result<bool> {
+        let i = index(x, z);
+        let present = self.locations[i] != 0;
+        if present {
+            self.set_entry(i, 0, 0)?;
+        }
+        Ok(present)
+    }
+
+    /// First sector of a run of `sectors` free sectors for chunk `i`
+    fn allocate(&mut self, i: usize, sectors: usize) -> io::Result<usize> {
+        let (start, count) = ((self.locations[i] >> 8) as usize, self.locations[i] as u8);
+        if self.locations[i] != 0 && start >= 2 && sectors <= count as usize {
+            return Ok(start);
+        }
+        let end = (self
+            .file
+            .seek(SeekFrom::End(0))?
+            .div_ceil(SECTOR_LEN as u64) as usize)
+            .max(2);
+        let mut used = vec![false; end];
+        used[..2].fill(true);
+        for (j, &location) in self.locations.iter().enumerate() {
+            let (s, n) = ((location >> 8) as usize, (location & 0xff) as usize);
+            if j != i && location != 0 {
+                for slot in used.iter_mut().skip(s).take(n) {
+                    *slot = true;
+                }
+            }
+        }
+        let mut run = 0;
+        for (sector, &taken) in used.iter().enumerate() {
+            run = if taken { 0 } else { run + 1 };
+            if run == sectors {
+                return Ok(sector + 1 - sectors);
+            }
+        }
+        // Extend the last free run, if any, to the end of the file
+        Ok(end - run)
+    }
+
+    fn set_entry(&mut self, i: usize, location: u32, timestamp: u32) -> io::Result<()> {
+        self.locations[i] = location;
+        self.timestamps[i] = timestamp;
+        self.file.seek(SeekFrom::Start((i * 4) as u64))?;
+        self.file.write_all(&location.to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR_LEN + i * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+        self.file.flush()
+    }
+}
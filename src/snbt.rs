@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    Tag,
+    path::{is_bare_key, write_quoted},
+};
+
+/// Stringified NBT, as accepted by `/data` and `/give`. Names are not part of SNBT, so
+/// only the value is printed. Compound keys are sorted to keep the output stable.
+///
+/// `{:#}` pretty-prints compounds and nested lists over several indented lines.
+///
+/// SNBT has no literal for NaN or infinite floats, or for End. They are written as
+/// `float_bits(0x7fc00000)`, `double_bits(0x7ff0000000000000)` and `end()`, which only
+/// this crate's parser reads back.
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pretty = f.alternate();
+        write_snbt(f, self, pretty.then_some(0))
+    }
+}
+
+fn write_snbt(f: &mut fmt::Formatter<'_>, tag: &Tag, indent: Option<usize>) -> fmt::Result {
+    match tag {
+        Tag::End => f.write_str("end()"),
+        Tag::Byte { value, .. } => write!(f, "{}b", value),
+        Tag::Short { value, .. } => write!(f, "{}s", value),
+        Tag::Int { value, .. } => write!(f, "{}", value),
+        Tag::Long { value, .. } => write!(f, "{}L", value),
+        Tag::Float { value, .. } if !value.is_finite() => {
+            write!(f, "float_bits({:#010x})", value.to_bits())
+        }
+        Tag::Double { value, .. } if !value.is_finite() => {
+            write!(f, "double_bits({:#018x})", value.to_bits())
+        }
+        Tag::Float { value, .. } => write!(f, "{:?}f", value),
+        Tag::Double { value, .. } => write!(f, "{:?}d", value),
+        Tag::String { value, .. } => write_quoted(f, value),
+        Tag::ByteArray { value, .. } => write_array(f, 'B', value.iter().map(|&b| b as i8), "b"),
+        Tag::IntArray { value, .. } => write_array(f, 'I', value.iter(), ""),
+        Tag::LongArray { value, .. } => write_array(f, 'L', value.iter(), "L"),
+        Tag::List { elements, .. } => {
+            // Only break lines when the elements are containers themselves
+            let nested = elements
+                .iter()
+                .any(|e| matches!(e, Tag::List { .. } | Tag::Compound { .. }));
+            let indent = indent.filter(|_| nested);
+            f.write_char('[')?;
+            for (i, elem) in elements.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                newline(f, indent.map(|n| n + 1))?;
+                write_snbt(f, elem, indent.map(|n| n + 1))?;
+            }
+            if !elements.is_empty() {
+                newline(f, indent)?;
+            }
+            f.write_char(']')
+        }
+        Tag::Compound { entries, .. } => {
+            let mut keys: Vec<&String> = entries.keys().collect();
+            keys.sort();
+            f.write_char('{')?;
+            for (i, key) in keys.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                newline(f, indent.map(|n| n + 1))?;
+                if is_bare_key(key) {
+                    f.write_str(key)?;
+                } else {
+                    write_quoted(f, key)?;
+                }
+                f.write_str(if indent.is_some() { ": " } else { ":" })?;
+                write_snbt(f, &entries[*key], indent.map(|n| n + 1))?;
+            }
+            if !keys.is_empty() {
+                newline(f, indent)?;
+            }
+            f.write_char('}')
+        }
+    }
+}
+
+fn write_array<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    prefix: char,
+    values: impl Iterator<Item = T>,
+    suffix: &str,
+) -> fmt::Result {
+    write!(f, "[{};", prefix)?;
+    for (i, v) in values.enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        write!(f, "{}{}", v, suffix)?;
+    }
+    f.write_char(']')
+}
+
+fn newline(f: &mut fmt::Formatter<'_>, indent: Option<usize>) -> fmt::Result {
+    if let Some(n) = indent {
+        f.write_char('\n')?;
+        for _ in 0..n {
+            f.write_str("    ")?;
+        }
+    }
+    Ok(())
+}
+
+/// Error returned when a string is not valid SNBT
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseSnbtError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseSnbtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseSnbtError {}
+
+/// Parse SNBT as printed by `Display`, or as typed into commands: `{Count: 1b, id:
+/// "minecraft:stone"}`. Unquoted words that aren't numbers are strings, and `true` and
+/// `false` are bytes. The result is unnamed.
+impl FromStr for Tag {
+    type Err = ParseSnbtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, pos: 0 };
+        let tag = parser.value()?;
+        parser.skip_ws();
+        if parser.pos < s.len() {
+            return parser.err("trailing characters after value");
+        }
+        Ok(tag)
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn err<T>(&self, message: &'static str) -> Result<T, ParseSnbtError> {
+        Err(ParseSnbtError {
+            position: self.pos,
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace, then consume `b` if it comes next
+    fn eat(&mut self, b: u8) -> bool {
+        self.skip_ws();
+        let found = self.peek() == Some(b);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, b: u8, message: &'static str) -> Result<(), ParseSnbtError> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            self.err(message)
+        }
+    }
+
+    fn value(&mut self) -> Result<Tag, ParseSnbtError> {
+        self.skip_ws();
+        match self.peek() {
+            Some(b'{') => self.compound(),
+            Some(b'[') => self.list(),
+            Some(b'"' | b'\'') => Ok(Tag::from(self.quoted()?)),
+            Some(_) => {
+                let start = self.pos;
+                let word = self.word();
+                if word.is_empty() {
+                    return self.err("expected a value");
+                }
+                if self.peek() == Some(b'(') {
+                    return self.call(word, start);
+                }
+                scalar(word).map_err(|message| ParseSnbtError {
+                    position: start,
+                    message,
+                })
+            }
+            None => self.err("expected a value"),
+        }
+    }
+
+    /// `end()`, `float_bits(0x...)` or `double_bits(0x...)` as `Display` writes them
+    fn call(&mut self, name: &str, start: usize) -> Result<Tag, ParseSnbtError> {
+        self.pos += 1;
+        self.skip_ws();
+        let arg = self.word();
+        self.expect(b')', "expected ')'")?;
+        let hex = arg.strip_prefix("0x").unwrap_or("-");
+        let tag = match name {
+            "end" if arg.is_empty() => Some(Tag::End),
+            "float_bits" => u32::from_str_radix(hex, 16)
+                .ok()
+                .map(|b| Tag::from(f32::from_bits(b))),
+            "double_bits" => u64::from_str_radix(hex, 16)
+                .ok()
+                .map(|b| Tag::from(f64::from_bits(b))),
+            _ => None,
+        };
+        tag.ok_or(ParseSnbtError {
+            position: start,
+            message: "expected end(), float_bits(0x...) or double_bits(0x...)",
+        })
+    }
+
+    fn compound(&mut self) -> Result<Tag, ParseSnbtError> {
+        self.pos += 1;
+        let mut entries = HashMap::new();
+        if !self.eat(b'}') {
+            loop {
+                self.skip_ws();
+                let key = match self.peek() {
+                    // `""` is a valid key, as in mixed list wrappers
+                    Some(b'"' | b'\'') => self.quoted()?,
+                    _ => match self.word() {
+                        "" => return self.err("expected a key"),
+                        word => word.to_string(),
+                    },
+                };
+                self.expect(b':', "expected ':' after key")?;
+                let value = self.value()?;
+                entries.insert(key, value);
+                if self.eat(b'}') {
+                    break;
+                }
+                self.expect(b',', "expected ',' or '}'")?;
+            }
+        }
+        Ok(Tag::from(entries))
+    }
+
+    fn list(&mut self) -> Result<Tag, ParseSnbtError> {
+        self.pos += 1;
+        self.skip_ws();
+        let rest = &self.s.as_bytes()[self.pos..];
+        if let [kind @ (b'B' | b'I' | b'L'), b';', ..] = rest {
+            let kind = *kind;
+            self.pos += 2;
+            return self.array(kind);
+        }
+        let mut elements = Vec::new();
+        if !self.eat(b']') {
+            loop {
+                elements.push(self.value()?);
+                if self.eat(b']') {
+                    break;
+                }
+                self.expect(b',', "expected ',' or ']'")?;
+            }
+        }
+        Ok(Tag::from(elements))
+    }
+
+    /// The elements of `[B; ...]`, `[I; ...]` or `[L; ...]`, after the `;`
+    fn array(&mut self, kind: u8) -> Result<Tag, ParseSnbtError> {
+        let mut values = Vec::new();
+        if !self.eat(b']') {
+            loop {
+                self.skip_ws();
+                let start = self.pos;
+                let value = match scalar(self.word()) {
+                    Ok(tag @ (Tag::Byte { .. } | Tag::Short { .. } | Tag::Int { .. })) => {
+                        tag.as_long()
+                    }
+                    Ok(Tag::Long { value, .. }) => Some(value),
+                    _ => None,
+                };
+                let value = value.filter(|&v| match kind {
+                    b'B' => i8::try_from(v).is_ok(),
+                    b'I' => i32::try_from(v).is_ok(),
+                    _ => true,
+                });
+                let Some(value) = value else {
+                    self.pos = start;
+                    return self.err("expected an integer that fits the array type");
+                };
+                values.push(value);
+                if self.eat(b']') {
+                    break;
+                }
+                self.expect(b',', "expected ',' or ']'")?;
+            }
+        }
+        Ok(match kind {
+            b'B' => Tag::from(values.iter().map(|&v| v as u8).collect::<Vec<u8>>()),
+            b'I' => Tag::from(values.iter().map(|&v| v as i32).collect::<Vec<i32>>()),
+            _ => Tag::from(values),
+        })
+    }
+
+    /// An unquoted word: letters, digits and `_-.+`
+    fn word(&mut self) -> &'a str {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'+'))
+        {
+            self.pos += 1;
+        }
+        &self.s[start..self.pos]
+    }
+
+    fn quoted(&mut self) -> Result<String, ParseSnbtError> {
+        let start = self.pos;
+        let quote = self.s.as_bytes()[start] as char;
+        let mut out = String::new();
+        let mut chars = self.s[start + 1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, e)| e) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('s') => ' ',
+                        Some(e @ ('\\' | '"' | '\'')) => e,
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).map(|(_, h)| h).collect();
+                            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                                Some(c) if hex.len() == 4 => c,
+                                _ => return self.err("invalid \\u escape"),
+                            }
+                        }
+                        _ => {
+                            self.pos = start + 1 + i;
+                            return self.err("invalid escape");
+                        }
+                    };
+                    out.push(escaped);
+                }
+                c if c == quote => {
+                    self.pos = start + 1 + i + 1;
+                    return Ok(out);
+                }
+                c => out.push(c),
+            }
+        }
+        self.err("unclosed string")
+    }
+}
+
+/// An unquoted word as a number, boolean or string
+fn scalar(word: &str) -> Result<Tag, &'static str> {
+    match word {
+        "true" => return Ok(Tag::from(true)),
+        "false" => return Ok(Tag::from(false)),
+        _ => {}
+    }
+    let (body, suffix) = match word.as_bytes().last() {
+        Some(b) if b.is_ascii_alphabetic() => (&word[..word.len() - 1], b.to_ascii_lowercase()),
+        _ => (word, 0),
+    };
+    let is_int = {
+        let digits = body.strip_prefix(['-', '+']).unwrap_or(body);
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    };
+    let out_of_range = "number out of range";
+    match suffix {
+        b'b' if is_int => body.parse::<i8>().map(Tag::from).map_err(|_| out_of_range),
+        b's' if is_int => body.parse::<i16>().map(Tag::from).map_err(|_| out_of_range),
+        b'l' if is_int => body.parse::<i64>().map(Tag::from).map_err(|_| out_of_range),
+        0 if is_int => body.parse::<i32>().map(Tag::from).map_err(|_| out_of_range),
+        b'f' if is_float(body) => Ok(Tag::from(body.parse::<f32>().unwrap())),
+        b'd' if is_float(body) => Ok(Tag::from(body.parse::<f64>().unwrap())),
+        0 if is_float(body) && body.bytes().any(|b| matches!(b, b'.' | b'e' | b'E')) => {
+            Ok(Tag::from(body.parse::<f64>().unwrap()))
+        }
+        _ => Ok(Tag::from(word)),
+    }
+}
+
+/// Decimal digits with an optional sign, point and exponent, or `NaN`/`inf` as Rust
+/// prints them
+fn is_float(s: &str) -> bool {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if matches!(digits, "NaN" | "inf") {
+        return true;
+    }
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(i) => (&digits[..i], Some(&digits[i + 1..])),
+        None => (digits, None),
+    };
+    let mut parts = mantissa.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let frac = parts.next().unwrap_or("");
+    let all_digits = |p: &str| p.bytes().all(|b| b.is_ascii_digit());
+    (!whole.is_empty() || !frac.is_empty())
+        && all_digits(whole)
+        && all_digits(frac)
+        && exponent.is_none_or(|e| {
+            let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+            !e.is_empty() && all_digits(e)
+        })
+}
@@ -485,3 +485,3069 @@ mod tests {
         assert_eq!(raw[0], 10u8);
     }
 }
+
+// ---------------------------------------------------------------------------------
+#[cfg(feature = "tokio")]
+mod async_io {
+    use std::collections::HashMap;
+
+    use crate::{AsyncReader, AsyncWriter, Reader, Tag, Writer, read_nbt_async, write_nbt_async};
+
+    fn sample() -> Tag {
+        let mut entries = HashMap::new();
+        entries.insert("B".into(), Tag::new_byte("B", -3));
+        entries.insert("S".into(), Tag::new_string("S", "hello"));
+        entries.insert("IA".into(), Tag::new_int_array("IA", vec![1, -2, 3]));
+        entries.insert("LA".into(), Tag::new_long_array("LA", vec![i64::MIN, 0]));
+        entries.insert(
+            "L".into(),
+            Tag::List {
+                name: Some("L".into()),
+                element_id: 6,
+                elements: vec![Tag::Double {
+                    name: None,
+                    value: 0.5,
+                }],
+            },
+        );
+        Tag::Compound {
+            name: Some("Data".into()),
+            entries,
+        }
+    }
+
+    #[tokio::test]
+    async fn matches_sync_encoding() {
+        let root = sample();
+        let mut sync_bytes = Vec::new();
+        Writer::new(&mut sync_bytes).write_tag(&root).unwrap();
+
+        let back = read_nbt_async(&sync_bytes[..]).await.unwrap();
+        assert_eq!(back, root);
+
+        let mut async_bytes = Vec::new();
+        write_nbt_async(&root, &mut async_bytes).await.unwrap();
+        let back = Reader::new(&async_bytes[..]).read_tag().unwrap();
+        assert_eq!(back, root);
+    }
+
+    #[tokio::test]
+    async fn roundtrip_gzip() {
+        let root = sample();
+        let mut w = AsyncWriter::to_gzip(Vec::new());
+        w.write_tag(&root).await.unwrap();
+        w.shutdown().await.unwrap();
+        let out = w.into_inner().into_inner();
+        assert_eq!(&out[..2], &[0x1f, 0x8b]);
+
+        let back = Reader::from_gzip(&out[..]).read_tag().unwrap();
+        assert_eq!(back, root);
+        let back = AsyncReader::from_gzip(&out[..]).read_tag().await.unwrap();
+        assert_eq!(back, root);
+    }
+
+    #[tokio::test]
+    async fn error_on_unknown_tag_id() {
+        let raw = [99u8, 0, 0];
+        let err = read_nbt_async(&raw[..]).await.expect_err("should error");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn huge_length_prefixes_fail_without_allocating() {
+        for input in super::bulk_io::huge_prefixes() {
+            let err = read_nbt_async(&input[..]).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof, "{:?}", input);
+        }
+    }
+
+    #[tokio::test]
+    async fn mixed_lists() {
+        let list = Tag::from(vec![Tag::from(1), Tag::from("two")]).with_name("list");
+        let mut async_bytes = Vec::new();
+        write_nbt_async(&list, &mut async_bytes).await.unwrap();
+        assert_eq!(async_bytes, list.to_bytes().unwrap());
+
+        let back = AsyncReader::new(&async_bytes[..])
+            .unwrap_mixed_lists(true)
+            .read_tag()
+            .await
+            .unwrap();
+        assert_eq!(back, list);
+
+        let with_end = Tag::from(vec![Tag::from(1), Tag::End]).with_name("");
+        let err = write_nbt_async(&with_end, Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn futures_are_send() {
+        fn assert_send<T: Send>(_: T) {}
+        let buf: &[u8] = &[];
+        assert_send(read_nbt_async(buf));
+        assert_send(write_nbt_async(&Tag::End, Vec::new()));
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod bulk_io {
+    use crate::{Tag, read_nbt, write_nbt};
+
+    fn roundtrip(tag: &Tag) -> (Vec<u8>, Tag) {
+        let mut bytes = Vec::new();
+        write_nbt(tag, &mut bytes).unwrap();
+        let back = read_nbt(&bytes[..]).unwrap();
+        (bytes, back)
+    }
+
+    /// Unnamed roots claiming `i32::MAX` entries of `payload`, followed by a few bytes
+    pub(super) fn huge_prefixes() -> Vec<Vec<u8>> {
+        let mut inputs: Vec<Vec<u8>> = [7u8, 11, 12]
+            .iter()
+            .map(|&id| vec![id, 0, 0, 0x7f, 0xff, 0xff, 0xff, 1, 2, 3])
+            .collect();
+        for element in [1u8, 3, 4, 6, 10] {
+            inputs.push(vec![9, 0, 0, element, 0x7f, 0xff, 0xff, 0xff, 1, 2, 3]);
+        }
+        inputs
+    }
+
+    #[test]
+    fn huge_length_prefixes_fail_without_allocating() {
+        for input in huge_prefixes() {
+            let err = read_nbt(&input[..]).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn long_array_bytes_are_be() {
+        let tag = Tag::new_long_array("L", vec![0x0102_0304_0506_0708, -1]);
+        let (bytes, back) = roundtrip(&tag);
+        assert_eq!(
+            &bytes[4..],
+            &[
+                0, 0, 0, 2, 1, 2, 3, 4, 5, 6, 7, 8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+            ]
+        );
+        assert_eq!(back, tag);
+    }
+
+    #[test]
+    fn numeric_lists_roundtrip() {
+        let lists = [
+            Tag::new_list(
+                "b",
+                1,
+                vec![Tag::Byte {
+                    name: None,
+                    value: -1,
+                }],
+            ),
+            Tag::new_list(
+                "s",
+                2,
+                vec![Tag::Short {
+                    name: None,
+                    value: -300,
+                }],
+            ),
+            Tag::new_list(
+                "i",
+                3,
+                (0..100)
+                    .map(|value| Tag::Int { name: None, value })
+                    .collect(),
+            ),
+            Tag::new_list(
+                "l",
+                4,
+                vec![Tag::Long {
+                    name: None,
+                    value: i64::MAX,
+                }],
+            ),
+            Tag::new_list(
+                "f",
+                5,
+                vec![Tag::Float {
+                    name: None,
+                    value: -1.5,
+                }],
+            ),
+            Tag::new_list(
+                "d",
+                6,
+                vec![Tag::Double {
+                    name: None,
+                    value: 1e300,
+                }],
+            ),
+        ];
+        for list in &lists {
+            assert_eq!(&roundtrip(list).1, list);
+        }
+    }
+
+    #[test]
+    fn int_list_matches_per_element_layout() {
+        let tag = Tag::new_list(
+            "i",
+            3,
+            vec![
+                Tag::Int {
+                    name: None,
+                    value: 1,
+                },
+                Tag::Int {
+                    name: None,
+                    value: -2,
+                },
+            ],
+        );
+        let (bytes, _) = roundtrip(&tag);
+        assert_eq!(
+            &bytes[4..],
+            &[3, 0, 0, 0, 2, 0, 0, 0, 1, 0xff, 0xff, 0xff, 0xfe]
+        );
+    }
+
+    #[test]
+    fn negative_array_length_is_rejected() {
+        let raw = [11u8, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        let err = read_nbt(&raw[..]).expect_err("should error");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_array_is_an_error() {
+        let raw = [12u8, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1];
+        assert!(read_nbt(&raw[..]).is_err());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod encoded_len {
+    use std::collections::HashMap;
+
+    use crate::{Reader, Tag, Writer};
+
+    fn sample() -> Tag {
+        let mut inner = HashMap::new();
+        inner.insert("S".to_string(), Tag::new_string("S", "héllo"));
+        inner.insert("BA".to_string(), Tag::new_byte_array("BA", vec![1, 2, 3]));
+        let mut entries = HashMap::new();
+        entries.insert(
+            "Inner".to_string(),
+            Tag::Compound {
+                name: Some("Inner".into()),
+                entries: inner,
+            },
+        );
+        entries.insert("IA".to_string(), Tag::new_int_array("IA", vec![1, 2]));
+        entries.insert("LA".to_string(), Tag::new_long_array("LA", vec![3]));
+        entries.insert("Empty".to_string(), Tag::new_list("Empty", 0, Vec::new()));
+        entries.insert(
+            "Pos".to_string(),
+            Tag::new_list(
+                "Pos",
+                6,
+                vec![
+                    Tag::Double {
+                        name: None,
+                        value: 1.0
+                    };
+                    3
+                ],
+            ),
+        );
+        Tag::Compound {
+            name: Some("Data".into()),
+            entries,
+        }
+    }
+
+    #[test]
+    fn matches_written_size() {
+        let tags = [sample(), Tag::End, Tag::new_float("F", 1.0)];
+        for tag in &tags {
+            let mut named = Vec::new();
+            Writer::new(&mut named).write_tag(tag).unwrap();
+            assert_eq!(tag.encoded_len().unwrap(), named.len());
+
+            let mut network = Vec::new();
+            Writer::new(&mut network).write_network_tag(tag).unwrap();
+            assert_eq!(tag.network_encoded_len().unwrap(), network.len());
+        }
+    }
+
+    #[test]
+    fn to_bytes_allocates_exactly() {
+        let tag = sample();
+        let bytes = tag.to_bytes().unwrap();
+        assert_eq!(bytes.len(), bytes.capacity());
+        assert_eq!(Reader::new(&bytes[..]).read_tag().unwrap(), tag);
+    }
+
+    #[test]
+    fn network_root_has_no_name() {
+        let mut tag = Tag::new_compound("ignored");
+        tag.insert("x".to_string(), Tag::new_byte("x", 1));
+        let bytes = tag.to_network_bytes().unwrap();
+        assert_eq!(bytes, [10, 1, 0, 1, b'x', 1, 0]);
+
+        let back = Reader::new(&bytes[..]).read_network_tag().unwrap();
+        assert_eq!(back.name(), None);
+        assert_eq!(back.get("x"), tag.get("x"));
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod diff {
+    use crate::{Change, NbtPath, PathSegment, Tag, diff};
+
+    fn player() -> Tag {
+        let mut root = Tag::new_compound("");
+        root.insert("Health".to_string(), Tag::new_float("Health", 20.0));
+        root.insert("XpLevel".to_string(), Tag::new_int("XpLevel", 3));
+        root.insert(
+            "Pos".to_string(),
+            Tag::new_list(
+                "Pos",
+                6,
+                vec![
+                    Tag::Double {
+                        name: None,
+                        value: 1.0,
+                    },
+                    Tag::Double {
+                        name: None,
+                        value: 64.0,
+                    },
+                ],
+            ),
+        );
+        root
+    }
+
+    #[test]
+    fn path_parse_and_display() {
+        let path: NbtPath = "Data.Player.Pos[0]".parse().unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                PathSegment::Key("Data".into()),
+                PathSegment::Key("Player".into()),
+                PathSegment::Key("Pos".into()),
+                PathSegment::Index(0),
+            ]
+        );
+        assert_eq!(path.to_string(), "Data.Player.Pos[0]");
+
+        let quoted = NbtPath::root().key("a b").key("c.d").index(2);
+        assert_eq!(quoted.to_string(), r#""a b"."c.d"[2]"#);
+        assert_eq!(quoted.to_string().parse::<NbtPath>().unwrap(), quoted);
+
+        assert!("a..b".parse::<NbtPath>().is_err());
+        assert!("a[x]".parse::<NbtPath>().is_err());
+        assert!("a.".parse::<NbtPath>().is_err());
+    }
+
+    #[test]
+    fn identical_trees_have_no_changes() {
+        assert!(diff(&player(), &player()).is_empty());
+    }
+
+    #[test]
+    fn reports_each_kind_of_change() {
+        let old = player();
+        let mut new = player();
+        new.insert("Health".to_string(), Tag::new_float("Health", 12.5));
+        new.insert("XpLevel".to_string(), Tag::new_string("XpLevel", "3"));
+        new.insert("Score".to_string(), Tag::new_int("Score", 7));
+        if let Some(Tag::List { elements, .. }) = new.get_path_mut(&"Pos".parse().unwrap()) {
+            elements.pop();
+        }
+
+        let patch = diff(&old, &new);
+        assert_eq!(
+            patch.to_string(),
+            "~ Health: 20.0f -> 12.5f\n\
+             - Pos[1]: 64.0d\n\
+             + Score: 7\n\
+             ! XpLevel: 3 (int) -> \"3\" (string)\n"
+        );
+        assert!(matches!(patch.changes[1], Change::Removed { .. }));
+    }
+
+    #[test]
+    fn patch_applies_back() {
+        let old = player();
+        let mut new = player();
+        new.insert("XpLevel".to_string(), Tag::new_int("XpLevel", 4));
+        new.insert(
+            "Pos".to_string(),
+            Tag::new_list(
+                "Pos",
+                6,
+                vec![
+                    Tag::Double {
+                        name: None,
+                        value: 3.0
+                    };
+                    4
+                ],
+            ),
+        );
+        let patch = diff(&old, &new);
+
+        let mut patched = old.clone();
+        patch.apply(&mut patched).unwrap();
+        assert_eq!(patched, new);
+
+        let reverse = diff(&new, &old);
+        reverse.apply(&mut patched).unwrap();
+        assert_eq!(patched, old);
+    }
+
+    #[test]
+    fn patch_refuses_stale_values() {
+        let old = player();
+        let mut new = player();
+        new.insert("XpLevel".to_string(), Tag::new_int("XpLevel", 4));
+        let patch = diff(&old, &new);
+
+        let mut other = player();
+        other.insert("XpLevel".to_string(), Tag::new_int("XpLevel", 9));
+        let err = patch.apply(&mut other).expect_err("should conflict");
+        assert_eq!(err.path.to_string(), "XpLevel");
+    }
+
+    #[test]
+    fn nan_is_not_a_change() {
+        let a = Tag::new_double("d", f64::NAN);
+        assert!(diff(&a, &a.clone()).is_empty());
+    }
+
+    #[test]
+    fn snbt_display() {
+        let mut root = Tag::new_compound("");
+        root.insert("b".to_string(), Tag::new_byte("b", -1));
+        root.insert("id".to_string(), Tag::new_string("id", "say \"hi\""));
+        root.insert("BA".to_string(), Tag::new_byte_array("BA", vec![1, 255]));
+        root.insert("LA".to_string(), Tag::new_long_array("LA", vec![5]));
+        root.insert("my key".to_string(), Tag::new_short("my key", 2));
+        assert_eq!(
+            root.to_string(),
+            r#"{BA:[B;1b,-1b],LA:[L;5L],b:-1b,id:"say \"hi\"","my key":2s}"#
+        );
+
+        let mut nested = Tag::new_compound("");
+        nested.insert(
+            "Pos".to_string(),
+            Tag::new_list(
+                "Pos",
+                3,
+                vec![Tag::Int {
+                    name: None,
+                    value: 1,
+                }],
+            ),
+        );
+        nested.insert("Empty".to_string(), Tag::new_compound("Empty"));
+        assert_eq!(
+            format!("{:#}", nested),
+            "{\n    Empty: {},\n    Pos: [1]\n}"
+        );
+    }
+
+    #[test]
+    fn snbt_non_finite_and_end_round_trip() {
+        let mut root = Tag::new_compound("");
+        root.insert("f".to_string(), Tag::new_float("f", f32::NAN));
+        root.insert("d".to_string(), Tag::new_double("d", f64::NEG_INFINITY));
+        root.insert("k".to_string(), Tag::End);
+        let snbt = root.to_string();
+        assert_eq!(
+            snbt,
+            "{d:double_bits(0xfff0000000000000),f:float_bits(0x7fc00000),k:end()}"
+        );
+        let back: Tag = snbt.parse().unwrap();
+        assert!(back.canonical_eq(&root));
+        assert!("float_bits(7fc00000)".parse::<Tag>().is_err());
+        assert!("end(1)".parse::<Tag>().is_err());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod merge {
+    use crate::Tag;
+
+    fn compound(name: &str, entries: Vec<Tag>) -> Tag {
+        let mut tag = Tag::new_compound(name);
+        for entry in entries {
+            let key = entry.name().unwrap().to_string();
+            tag.insert(key, entry);
+        }
+        tag
+    }
+
+    fn level() -> Tag {
+        compound(
+            "Data",
+            vec![
+                Tag::new_int("SpawnX", 0),
+                Tag::new_list(
+                    "DataPacks",
+                    8,
+                    vec![Tag::String {
+                        name: None,
+                        value: "vanilla".into(),
+                    }],
+                ),
+                compound(
+                    "GameRules",
+                    vec![
+                        Tag::new_string("doDaylightCycle", "true"),
+                        Tag::new_string("keepInventory", "false"),
+                    ],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn merge_recurses_and_replaces() {
+        let mut tag = level();
+        let patch = compound(
+            "",
+            vec![
+                Tag::new_int("SpawnX", 10),
+                Tag::new_list("DataPacks", 8, Vec::new()),
+                compound("GameRules", vec![Tag::new_string("keepInventory", "true")]),
+            ],
+        );
+        tag.merge(&patch);
+
+        assert_eq!(tag.name(), Some("Data"));
+        assert_eq!(tag.get("SpawnX"), Some(&Tag::new_int("SpawnX", 10)));
+        assert_eq!(
+            tag.get("DataPacks"),
+            Some(&Tag::new_list("DataPacks", 8, Vec::new()))
+        );
+        let rules = tag.get("GameRules").unwrap();
+        assert_eq!(
+            rules.get("keepInventory"),
+            Some(&Tag::new_string("keepInventory", "true"))
+        );
+        assert_eq!(
+            rules.get("doDaylightCycle"),
+            Some(&Tag::new_string("doDaylightCycle", "true"))
+        );
+    }
+
+    #[test]
+    fn merge_sets_entry_names_to_keys() {
+        let mut tag = Tag::new_compound("");
+        let mut other = Tag::new_compound("");
+        other.insert("x".to_string(), Tag::new_int("unrelated", 1));
+        tag.merge(&other);
+        assert_eq!(tag.get("x"), Some(&Tag::new_int("x", 1)));
+    }
+
+    #[test]
+    fn merge_defaults_only_fills_gaps() {
+        let mut old = level();
+        let defaults = compound(
+            "",
+            vec![
+                Tag::new_int("SpawnX", 99),
+                Tag::new_int("WanderingTraderSpawnDelay", 24000),
+                compound(
+                    "GameRules",
+                    vec![
+                        Tag::new_string("keepInventory", "true"),
+                        Tag::new_string("doWardenSpawning", "true"),
+                    ],
+                ),
+            ],
+        );
+        old.merge_defaults(&defaults);
+
+        assert_eq!(old.get("SpawnX"), Some(&Tag::new_int("SpawnX", 0)));
+        assert_eq!(
+            old.get("WanderingTraderSpawnDelay"),
+            Some(&Tag::new_int("WanderingTraderSpawnDelay", 24000))
+        );
+        let rules = old.get("GameRules").unwrap();
+        assert_eq!(
+            rules.get("keepInventory"),
+            Some(&Tag::new_string("keepInventory", "false"))
+        );
+        assert!(rules.get("doWardenSpawning").is_some());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod canonical {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::{Canonical, Tag};
+
+    fn item(id: &str, count: i8, damage: f32) -> Tag {
+        let mut tag = Tag::new_compound("");
+        tag.insert("id".to_string(), Tag::new_string("id", id));
+        tag.insert("Count".to_string(), Tag::new_byte("Count", count));
+        tag.insert("Damage".to_string(), Tag::new_float("Damage", damage));
+        tag
+    }
+
+    #[test]
+    fn nan_equals_itself() {
+        let a = Tag::new_float("f", f32::NAN);
+        assert_ne!(a, a.clone());
+        assert!(a.canonical_eq(&a.clone()));
+        assert!(!Tag::new_float("f", 0.0).canonical_eq(&Tag::new_float("f", -0.0)));
+    }
+
+    #[test]
+    fn names_and_order_are_ignored() {
+        let a = item("minecraft:stone", 1, 0.0);
+        let mut b = Tag::new_compound("other");
+        b.insert("Damage".to_string(), Tag::new_float("Damage", 0.0));
+        b.insert("Count".to_string(), Tag::new_byte("Count", 1));
+        b.insert("id".to_string(), Tag::new_string("id", "minecraft:stone"));
+        assert_eq!(Canonical(&a), Canonical(&b));
+        assert!(!a.canonical_eq(&item("minecraft:stone", 2, 0.0)));
+    }
+
+    #[test]
+    fn deduplicates_in_hash_set() {
+        let stacks = vec![
+            item("minecraft:stone", 1, f32::NAN),
+            item("minecraft:dirt", 1, 0.0),
+            item("minecraft:stone", 1, f32::NAN),
+        ];
+        let unique: HashSet<Canonical<&Tag>> = stacks.iter().map(Canonical).collect();
+        assert_eq!(unique.len(), 2);
+
+        let mut counts: HashMap<Canonical, usize> = HashMap::new();
+        for stack in stacks {
+            *counts.entry(stack.into()).or_default() += 1;
+        }
+        assert_eq!(counts[&Canonical(item("minecraft:stone", 1, f32::NAN))], 2);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod visit {
+    use std::collections::HashMap;
+
+    use crate::{Flow, NbtPath, PathSegment, Tag, TagId, Visitor, VisitorMut};
+
+    fn section(states: &[&str]) -> Tag {
+        let palette = states
+            .iter()
+            .map(|name| {
+                let mut state = Tag::Compound {
+                    name: None,
+                    entries: HashMap::new(),
+                };
+                state.insert("Name".to_string(), Tag::new_string("Name", *name));
+                state
+            })
+            .collect();
+        let mut block_states = Tag::new_compound("block_states");
+        block_states.insert("palette".to_string(), Tag::new_list("palette", 10, palette));
+        let mut section = Tag::Compound {
+            name: None,
+            entries: HashMap::new(),
+        };
+        section.insert("block_states".to_string(), block_states);
+        section
+    }
+
+    fn chunk() -> Tag {
+        let mut root = Tag::new_compound("");
+        root.insert(
+            "sections".to_string(),
+            Tag::new_list(
+                "sections",
+                10,
+                vec![
+                    section(&["minecraft:air", "minecraft:grass"]),
+                    section(&["minecraft:grass", "minecraft:stone"]),
+                ],
+            ),
+        );
+        root.insert(
+            "Heightmap".to_string(),
+            Tag::new_long_array("Heightmap", vec![1, 2]),
+        );
+        root
+    }
+
+    #[test]
+    fn rename_block_ids_across_palettes() {
+        struct Rename;
+        impl VisitorMut for Rename {
+            fn visit_string(&mut self, path: &NbtPath, value: &mut String) -> Flow {
+                if path.last_key() == Some("Name") && value == "minecraft:grass" {
+                    *value = "minecraft:short_grass".to_string();
+                }
+                Flow::Continue
+            }
+        }
+        let mut tag = chunk();
+        assert_eq!(tag.walk_mut(&mut Rename), Flow::Continue);
+
+        let name = tag
+            .get_path(&"sections[1].block_states.palette[0].Name".parse().unwrap())
+            .unwrap();
+        assert_eq!(name, &Tag::new_string("Name", "minecraft:short_grass"));
+    }
+
+    #[test]
+    fn paths_and_skipping() {
+        #[derive(Default)]
+        struct Collect(Vec<String>);
+        impl Visitor for Collect {
+            fn visit_string(&mut self, path: &NbtPath, _: &str) -> Flow {
+                self.0.push(path.to_string());
+                Flow::Continue
+            }
+            fn enter_list(&mut self, path: &NbtPath, _: TagId, _: &[Tag]) -> Flow {
+                // Only descend into the first section
+                if path.segments.last() == Some(&PathSegment::Key("palette".into()))
+                    && path.segments[1] != PathSegment::Index(0)
+                {
+                    return Flow::Skip;
+                }
+                Flow::Continue
+            }
+        }
+        let mut v = Collect::default();
+        chunk().walk(&mut v);
+        v.0.sort();
+        assert_eq!(
+            v.0,
+            [
+                "sections[0].block_states.palette[0].Name",
+                "sections[0].block_states.palette[1].Name",
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_ends_traversal() {
+        struct FindStone(usize);
+        impl Visitor for FindStone {
+            fn visit_string(&mut self, _: &NbtPath, value: &str) -> Flow {
+                self.0 += 1;
+                if value == "minecraft:grass" {
+                    Flow::Stop
+                } else {
+                    Flow::Continue
+                }
+            }
+        }
+        let mut v = FindStone(0);
+        let tag = Tag::new_list(
+            "l",
+            8,
+            ["minecraft:air", "minecraft:grass", "minecraft:stone"]
+                .iter()
+                .map(|s| Tag::String {
+                    name: None,
+                    value: s.to_string(),
+                })
+                .collect(),
+        );
+        assert_eq!(tag.walk(&mut v), Flow::Stop);
+        assert_eq!(v.0, 2);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod index_convert {
+    use crate::{Tag, TagTypeError};
+
+    fn level() -> Tag {
+        let mut player = Tag::new_compound("Player");
+        player.insert(
+            "Pos".into(),
+            Tag::from(vec![Tag::from(1.5), Tag::from(64.0), Tag::from(-3.0)]),
+        );
+        player.insert("Health".into(), Tag::from(20.0f32));
+        let mut data = Tag::new_compound("Data");
+        data.insert("Player".into(), player);
+        let mut root = Tag::new_compound("");
+        root.insert("Data".into(), data);
+        root
+    }
+
+    #[test]
+    fn chained_index() {
+        let root = level();
+        assert_eq!(root["Data"]["Player"]["Pos"][1], Tag::from(64.0));
+        assert_eq!(f32::try_from(&root["Data"]["Player"]["Health"]), Ok(20.0));
+    }
+
+    #[test]
+    fn missing_is_end() {
+        let root = level();
+        assert_eq!(root["Data"]["Nope"]["Deeper"][3], Tag::End);
+        assert_eq!(root["Data"]["Player"]["Pos"][7], Tag::End);
+        assert_eq!(Tag::from(1)["key"], Tag::End);
+    }
+
+    #[test]
+    fn index_mut_writes() {
+        let mut root = level();
+        root["Data"]["Player"]["Pos"][0] = Tag::from(9.0);
+        root["Data"]["Player"]["Health"] = Tag::new_float("Health", 10.0);
+        assert_eq!(root["Data"]["Player"]["Pos"][0], Tag::from(9.0));
+        assert_eq!(f32::try_from(&root["Data"]["Player"]["Health"]), Ok(10.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry \"SpawnX\" in Compound")]
+    fn index_mut_panics_on_missing_keys() {
+        let mut root = level();
+        root["Data"]["SpawnX"] = Tag::from(10);
+    }
+
+    #[test]
+    fn inserted_entries_are_named_after_their_key() {
+        let mut root = Tag::new_compound("");
+        root.get_or_insert_compound("a")
+            .insert("b".into(), Tag::from(1i8));
+        root.get_or_insert_compound("c");
+        root.insert("d".into(), Tag::new_int("other", 2));
+        assert_eq!(root["a"].name(), Some("a"));
+        assert_eq!(root["c"], Tag::new_compound("c"));
+        assert_eq!(root["d"], Tag::new_int("d", 2));
+        let bytes = root.to_bytes().unwrap();
+        assert_eq!(bytes.len(), root.encoded_len().unwrap());
+        let back = crate::read_nbt(&bytes[..]).unwrap();
+        assert_eq!(back["a"]["b"], Tag::new_byte("b", 1));
+        assert_eq!(back["c"], Tag::new_compound("c"));
+    }
+
+    #[test]
+    fn from_conversions() {
+        assert_eq!(
+            Tag::from(true),
+            Tag::Byte {
+                name: None,
+                value: 1
+            }
+        );
+        assert_eq!(
+            Tag::from("x"),
+            Tag::String {
+                name: None,
+                value: "x".into()
+            }
+        );
+        assert_eq!(Tag::from(vec![1i64, 2]).id(), 12);
+        assert_eq!(Tag::from(vec![1i32]).id(), 11);
+        assert_eq!(Tag::from(vec![1u8]).id(), 7);
+        assert_eq!(Tag::from(7i16).with_name("n"), Tag::new_short("n", 7));
+        let Tag::List { element_id, .. } = Tag::from(Vec::<Tag>::new()) else {
+            panic!("not list");
+        };
+        assert_eq!(element_id, 0);
+    }
+
+    #[test]
+    fn strict_try_from() {
+        let tag = Tag::new_string("id", "minecraft:stone");
+        assert_eq!(<&str>::try_from(&tag), Ok("minecraft:stone"));
+        assert_eq!(
+            i32::try_from(&tag),
+            Err(TagTypeError {
+                expected: 3,
+                found: 8
+            })
+        );
+        assert_eq!(
+            i64::try_from(&Tag::from(1)).unwrap_err().to_string(),
+            "expected long, found int"
+        );
+        assert_eq!(bool::try_from(&Tag::from(2i8)), Ok(true));
+        assert_eq!(<&[i64]>::try_from(&Tag::from(vec![4i64])), Ok(&[4i64][..]));
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod nbt_macro {
+    use std::collections::HashMap;
+
+    use crate::{Tag, nbt};
+
+    #[test]
+    fn builds_typed_tree() {
+        let tag = nbt! {
+            "Data": {
+                "SpawnX": 10i32,
+                "Difficulty": 2i8,
+                LevelName: "test",
+                "Pos": [1.0f64, 64.0, 2.0],
+                "Blocks": [B; 1, -1],
+                "Biomes": [I; 1, 2, 3],
+                "Heightmap": [L; 5],
+                "Items": [{ "id": "minecraft:stone" }, { "id": "minecraft:dirt" }],
+                "Nested": [[1i16], []],
+                "Empty": {},
+            }
+        };
+        let data = &tag["Data"];
+        assert_eq!(data["SpawnX"], Tag::new_int("SpawnX", 10));
+        assert_eq!(data["Difficulty"], Tag::new_byte("Difficulty", 2));
+        assert_eq!(data["LevelName"], Tag::new_string("LevelName", "test"));
+        assert_eq!(data["Blocks"], Tag::new_byte_array("Blocks", vec![1, 255]));
+        assert_eq!(data["Biomes"], Tag::new_int_array("Biomes", vec![1, 2, 3]));
+        assert_eq!(data["Heightmap"], Tag::new_long_array("Heightmap", vec![5]));
+        assert_eq!(
+            data["Pos"],
+            Tag::new_list(
+                "Pos",
+                6,
+                vec![
+                    Tag::Double {
+                        name: None,
+                        value: 1.0
+                    },
+                    Tag::Double {
+                        name: None,
+                        value: 64.0
+                    },
+                    Tag::Double {
+                        name: None,
+                        value: 2.0
+                    },
+                ]
+            )
+        );
+        assert_eq!(
+            data["Items"][1]["id"],
+            Tag::new_string("id", "minecraft:dirt")
+        );
+        assert_eq!(data["Items"][1].name(), None);
+        assert_eq!(data["Nested"][0].id(), 9);
+        assert_eq!(data["Empty"], Tag::new_compound("Empty"));
+    }
+
+    #[test]
+    fn matches_hand_built_compound() {
+        let mut entries = HashMap::new();
+        entries.insert("DataVersion".to_string(), Tag::new_int("DataVersion", 3837));
+        entries.insert(
+            "TestList".to_string(),
+            Tag::new_list(
+                "TestList",
+                3,
+                vec![Tag::Int {
+                    name: None,
+                    value: 1,
+                }],
+            ),
+        );
+        let expected = Tag::Compound {
+            name: None,
+            entries,
+        };
+        assert_eq!(nbt! { "DataVersion": 3837, "TestList": [1] }, expected);
+    }
+
+    #[test]
+    fn accepts_expressions_and_tags() {
+        let count = 3;
+        let inner = nbt! { "x": 1 };
+        let tag = nbt!({ "Count": count as i8, "Inner": inner.clone(), "Sum": 1 + 2 });
+        assert_eq!(tag["Count"], Tag::new_byte("Count", 3));
+        assert_eq!(tag["Inner"]["x"], inner["x"]);
+        assert_eq!(tag["Sum"], Tag::new_int("Sum", 3));
+        assert_eq!(nbt!([1i64, 2]).id(), 9);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+#[cfg(feature = "derive")]
+mod derive {
+    use std::collections::HashMap;
+
+    use crate::{FromNbt, FromNbtErrorKind, Tag, ToNbt, nbt};
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    struct Item {
+        id: String,
+        #[nbt(rename = "Count")]
+        count: i8,
+        #[nbt(rename = "tag")]
+        extra: Option<HashMap<String, Tag>>,
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    struct Player {
+        #[nbt(rename = "Pos")]
+        pos: Vec<f64>,
+        #[nbt(rename = "UUID", int_array)]
+        uuid: Vec<i32>,
+        #[nbt(rename = "Inventory", default)]
+        inventory: Vec<Item>,
+        #[nbt(rename = "XpLevel", default = "one")]
+        xp_level: i32,
+        #[nbt(skip)]
+        dirty: bool,
+        #[nbt(rename = "Mode")]
+        mode: GameMode,
+        #[nbt(flatten)]
+        unknown: HashMap<String, Tag>,
+    }
+
+    fn one() -> i32 {
+        1
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    enum GameMode {
+        #[nbt(rename = "survival")]
+        Survival,
+        #[nbt(rename = "creative")]
+        Creative,
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    #[nbt(tag = "id")]
+    enum BlockEntity {
+        #[nbt(rename = "minecraft:sign")]
+        Sign {
+            #[nbt(rename = "Text")]
+            text: String,
+        },
+        #[nbt(rename = "minecraft:chest")]
+        Chest(Chest),
+        #[nbt(rename = "minecraft:bell")]
+        Bell,
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    struct Chest {
+        #[nbt(rename = "Items")]
+        items: Vec<Item>,
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    struct Wrapper<T>(T);
+
+    #[test]
+    fn struct_roundtrip_with_attributes() {
+        let player = Player {
+            pos: vec![1.0, 64.0, -2.5],
+            uuid: vec![1, 2, 3, 4],
+            inventory: vec![Item {
+                id: "minecraft:stone".into(),
+                count: 3,
+                extra: None,
+            }],
+            xp_level: 7,
+            dirty: true,
+            mode: GameMode::Creative,
+            unknown: HashMap::from([("Score".to_string(), Tag::new_int("Score", 9))]),
+        };
+        let tag = player.to_nbt();
+        assert_eq!(tag["UUID"], Tag::new_int_array("UUID", vec![1, 2, 3, 4]));
+        assert_eq!(tag["Mode"], Tag::new_string("Mode", "creative"));
+        assert_eq!(tag["Score"], Tag::new_int("Score", 9));
+        assert_eq!(tag["Inventory"][0]["Count"], Tag::new_byte("Count", 3));
+        assert!(tag["Inventory"][0].get("tag").is_none());
+        assert!(tag.get("dirty").is_none());
+
+        let back = Player::from_nbt(&tag).unwrap();
+        assert_eq!(
+            back,
+            Player {
+                dirty: false,
+                ..player
+            }
+        );
+    }
+
+    #[test]
+    fn defaults_and_missing_fields() {
+        let tag = nbt! { "Pos": [0.0f64], "UUID": [I; 0, 0, 0, 0], "Mode": "survival" };
+        let player = Player::from_nbt(&tag).unwrap();
+        assert_eq!(player.xp_level, 1);
+        assert!(player.inventory.is_empty());
+        assert!(player.unknown.is_empty());
+
+        let err = Item::from_nbt(&nbt! { "id": "minecraft:stone" }).unwrap_err();
+        assert_eq!(err.kind, FromNbtErrorKind::MissingField("Count".into()));
+    }
+
+    #[test]
+    fn error_paths() {
+        let tag = nbt! {
+            "Pos": [0.0f64],
+            "UUID": [I; 0, 0, 0, 0],
+            "Mode": "survival",
+            "Inventory": [{ "id": "minecraft:stone", "Count": 1 }],
+        };
+        let err = Player::from_nbt(&tag).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at Inventory[0].Count: expected byte, found int"
+        );
+
+        let err = GameMode::from_nbt(&Tag::from("spectator")).unwrap_err();
+        assert_eq!(
+            err.kind,
+            FromNbtErrorKind::UnknownVariant("spectator".into())
+        );
+    }
+
+    #[test]
+    fn tagged_enums() {
+        let sign = BlockEntity::Sign { text: "hi".into() };
+        let tag = sign.to_nbt();
+        assert_eq!(tag["id"], Tag::new_string("id", "minecraft:sign"));
+        assert_eq!(tag["Text"], Tag::new_string("Text", "hi"));
+        assert_eq!(BlockEntity::from_nbt(&tag).unwrap(), sign);
+
+        let chest = BlockEntity::Chest(Chest { items: Vec::new() });
+        let tag = chest.to_nbt();
+        assert_eq!(tag["Items"].id(), 9);
+        assert_eq!(BlockEntity::from_nbt(&tag).unwrap(), chest);
+
+        let bell = BlockEntity::Bell.to_nbt();
+        assert_eq!(BlockEntity::from_nbt(&bell).unwrap(), BlockEntity::Bell);
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    enum Machine {
+        Beacon {
+            level: i32,
+            #[nbt(flatten)]
+            rest: HashMap<String, Tag>,
+        },
+    }
+
+    #[test]
+    fn flattened_variant_fields_skip_the_tag() {
+        let tag = nbt! { "type": "Beacon", "level": 3, "Lock": "key" };
+        let beacon = Machine::from_nbt(&tag).unwrap();
+        let Machine::Beacon { level, rest } = &beacon;
+        assert_eq!(*level, 3);
+        assert_eq!(rest.keys().collect::<Vec<_>>(), ["Lock"]);
+        assert_eq!(beacon.to_nbt(), tag);
+    }
+
+    #[test]
+    fn newtype_is_transparent() {
+        assert_eq!(Wrapper(5i32).to_nbt(), Tag::from(5));
+        assert_eq!(
+            Wrapper::<String>::from_nbt(&Tag::from("a")).unwrap(),
+            Wrapper("a".into())
+        );
+    }
+
+    #[derive(ToNbt, FromNbt, PartialEq, Debug)]
+    struct Marker {
+        r#type: String,
+    }
+
+    #[test]
+    fn raw_identifiers_lose_their_prefix() {
+        let marker = Marker {
+            r#type: "beacon".into(),
+        };
+        let tag = marker.to_nbt();
+        assert_eq!(tag, nbt! { "type": "beacon" });
+        assert_eq!(Marker::from_nbt(&tag).unwrap(), marker);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod codec {
+    use crate::{
+        DataResult, Tag,
+        codec::{self, Codec, MapCodec, record},
+        nbt,
+    };
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Effect {
+        id: String,
+        amplifier: i8,
+        duration: i32,
+        icon: Option<bool>,
+    }
+
+    fn effect() -> impl Codec<Effect> {
+        record((
+            codec::STRING
+                .field_of("id")
+                .for_getter(|e: &Effect| e.id.clone()),
+            codec::BYTE
+                .optional_field_or("amplifier", 0)
+                .for_getter(|e: &Effect| e.amplifier),
+            codec::INT
+                .field_of("duration")
+                .for_getter(|e: &Effect| e.duration),
+            codec::BOOL
+                .optional_field_of("show_icon")
+                .for_getter(|e: &Effect| e.icon),
+        ))
+        .apply(|(id, amplifier, duration, icon)| Effect {
+            id,
+            amplifier,
+            duration,
+            icon,
+        })
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let haste = Effect {
+            id: "minecraft:haste".into(),
+            amplifier: 2,
+            duration: 600,
+            icon: Some(false),
+        };
+        let tag = effect().encode(&haste).into_result().unwrap();
+        assert_eq!(tag["amplifier"], Tag::new_byte("amplifier", 2));
+        assert_eq!(tag["show_icon"], Tag::new_byte("show_icon", 0));
+        assert_eq!(effect().decode(&tag), DataResult::success(haste));
+
+        let decoded = effect()
+            .decode(&nbt! { "id": "minecraft:speed", "duration": 20i32 })
+            .into_result()
+            .unwrap();
+        assert_eq!(decoded.amplifier, 0);
+        assert_eq!(decoded.icon, None);
+    }
+
+    #[test]
+    fn numbers_decode_leniently() {
+        assert_eq!(codec::INT.decode(&Tag::from(3i8)), DataResult::success(3));
+        assert_eq!(
+            codec::BYTE.decode(&Tag::from(2.9f64)),
+            DataResult::success(2)
+        );
+        assert_eq!(
+            codec::BOOL.decode(&Tag::from(1i8)),
+            DataResult::success(true)
+        );
+        assert!(!codec::INT.decode(&Tag::from("3")).is_success());
+    }
+
+    #[test]
+    fn errors_keep_partial_results() {
+        let ints = codec::INT.list_of();
+        let list = Tag::from(vec![Tag::from(1), Tag::from("x"), Tag::from(3)]);
+        match ints.decode(&list) {
+            DataResult::Error { message, partial } => {
+                assert_eq!(message, "Not a number: \"x\"");
+                assert_eq!(partial, Some(vec![1, 3]));
+            }
+            ok => panic!("expected an error, got {:?}", ok),
+        }
+
+        // A missing required field leaves no partial record, and all errors are kept
+        match effect().decode(&nbt! { "amplifier": "high" }) {
+            DataResult::Error { message, partial } => {
+                assert_eq!(
+                    message,
+                    "No key id; amplifier: Not a number: \"high\"; No key duration"
+                );
+                assert_eq!(partial, None);
+            }
+            ok => panic!("expected an error, got {:?}", ok),
+        }
+    }
+
+    #[test]
+    fn xmap_and_validate() {
+        #[derive(Debug, PartialEq)]
+        struct BlockPos(i32, i32, i32);
+        let pos = codec::INT_STREAM.flat_xmap(
+            |v| match v[..] {
+                [x, y, z] => DataResult::success(BlockPos(x, y, z)),
+                _ => DataResult::error(format!("Expected 3 coordinates, got {}", v.len())),
+            },
+            |p: &BlockPos| DataResult::success(vec![p.0, p.1, p.2]),
+        );
+        let tag = pos.encode(&BlockPos(1, 64, -3)).into_result().unwrap();
+        assert_eq!(tag, Tag::from(vec![1, 64, -3]));
+        assert_eq!(pos.decode(&tag), DataResult::success(BlockPos(1, 64, -3)));
+        assert!(!pos.decode(&Tag::from(vec![1, 2])).is_success());
+
+        let light = codec::INT.validate(|v| match *v {
+            0..=15 => Ok(()),
+            _ => Err(format!("Light level out of range: {}", v)),
+        });
+        assert!(light.decode(&Tag::from(16)).into_result().is_err());
+        let doubled = light.xmap(|v| v * 2, |v: &i32| v / 2);
+        assert_eq!(doubled.decode(&Tag::from(7)), DataResult::success(14));
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum IntProvider {
+        Constant(i32),
+        Uniform { min: i32, max: i32 },
+    }
+
+    fn int_provider() -> impl Codec<IntProvider> {
+        codec::STRING.dispatch(
+            "type",
+            |p: &IntProvider| match p {
+                IntProvider::Constant(_) => "minecraft:constant".to_string(),
+                IntProvider::Uniform { .. } => "minecraft:uniform".to_string(),
+            },
+            |kind: &String| -> DataResult<Box<dyn MapCodec<IntProvider>>> {
+                match kind.as_str() {
+                    "minecraft:constant" => DataResult::success(Box::new(
+                        codec::INT
+                            .field_of("value")
+                            .xmap(IntProvider::Constant, |p| match p {
+                                IntProvider::Constant(v) => *v,
+                                _ => unreachable!(),
+                            }),
+                    )),
+                    "minecraft:uniform" => DataResult::success(Box::new(
+                        record((
+                            codec::INT
+                                .field_of("min_inclusive")
+                                .for_getter(|p| match p {
+                                    IntProvider::Uniform { min, .. } => *min,
+                                    _ => unreachable!(),
+                                }),
+                            codec::INT
+                                .field_of("max_inclusive")
+                                .for_getter(|p| match p {
+                                    IntProvider::Uniform { max, .. } => *max,
+                                    _ => unreachable!(),
+                                }),
+                        ))
+                        .apply(|(min, max)| IntProvider::Uniform { min, max }),
+                    )),
+                    other => DataResult::error(format!("Unknown int provider {}", other)),
+                }
+            },
+        )
+    }
+
+    #[test]
+    fn dispatch_by_type_key() {
+        let uniform = IntProvider::Uniform { min: 1, max: 4 };
+        let tag = int_provider().encode(&uniform).into_result().unwrap();
+        assert_eq!(
+            tag,
+            nbt! { "type": "minecraft:uniform", "min_inclusive": 1, "max_inclusive": 4 }
+        );
+        assert_eq!(int_provider().decode(&tag), DataResult::success(uniform));
+
+        let constant = nbt! { "type": "minecraft:constant", "value": 3 };
+        assert_eq!(
+            int_provider().decode(&constant),
+            DataResult::success(IntProvider::Constant(3))
+        );
+        assert_eq!(
+            int_provider()
+                .decode(&nbt! { "type": "minecraft:biased" })
+                .into_result(),
+            Err("Unknown int provider minecraft:biased".to_string())
+        );
+    }
+
+    #[test]
+    fn unbounded_maps() {
+        let counts = codec::unbounded_map(codec::INT);
+        let tag = nbt! { "stone": 3, "dirt": "lots" };
+        let partial = counts.decode(&tag).result_or_partial(|_| {}).unwrap();
+        assert_eq!(partial.len(), 1);
+        assert_eq!(partial["stone"], 3);
+        assert_eq!(
+            counts.encode(&partial).into_result().unwrap(),
+            nbt! { "stone": 3 }
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod fix {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{
+        DataFixer, DataType, Tag,
+        fix::{FixError, data_version},
+        nbt,
+    };
+
+    fn chunk_1_17() -> Tag {
+        nbt! {
+            "DataVersion": 2730,
+            "Level": {
+                "xPos": 1, "zPos": -2, "Status": "full",
+                "Sections": [
+                    {
+                        "Y": 0i8,
+                        "Palette": [{ "Name": "minecraft:stone" }, { "Name": "minecraft:dirt" }],
+                        "BlockStates": [L; 0x1111, 0x2222],
+                    },
+                    { "Y": 1i8 },
+                ],
+                "TileEntities": [{ "id": "minecraft:chest" }],
+                "TileTicks": [],
+                "LiquidTicks": [],
+                "Structures": { "Starts": {}, "References": {} },
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_1_18_restructure() {
+        let mut chunk = chunk_1_17();
+        let applied = DataFixer::vanilla()
+            .upgrade(DataType::Chunk, &mut chunk, 2860)
+            .unwrap();
+        assert_eq!(applied, 2);
+        assert!(chunk.get("Level").is_none());
+        assert_eq!(chunk["DataVersion"], Tag::new_int("DataVersion", 2860));
+        assert_eq!(chunk["xPos"], Tag::new_int("xPos", 1));
+        assert_eq!(
+            chunk["block_entities"][0]["id"],
+            Tag::new_string("id", "minecraft:chest")
+        );
+        assert_eq!(chunk["block_ticks"].id(), 9);
+        assert_eq!(chunk["fluid_ticks"].id(), 9);
+        assert!(chunk["structures"].get("starts").is_some());
+        assert!(chunk["structures"].get("References").is_some());
+
+        let section = &chunk["sections"][0]["block_states"];
+        assert_eq!(section.name(), Some("block_states"));
+        assert_eq!(
+            section["palette"][1]["Name"],
+            Tag::new_string("Name", "minecraft:dirt")
+        );
+        assert_eq!(
+            section["data"],
+            Tag::new_long_array("data", vec![0x1111, 0x2222])
+        );
+        let empty = &chunk["sections"][1]["block_states"];
+        assert_eq!(
+            empty["palette"][0]["Name"],
+            Tag::new_string("Name", "minecraft:air")
+        );
+        assert!(empty.get("data").is_none());
+    }
+
+    #[test]
+    fn only_fixes_in_range_run() {
+        let fixer = DataFixer::vanilla();
+        let mut chunk = chunk_1_17();
+        // Already past the block state change, so only the renames apply
+        assert_eq!(fixer.update(DataType::Chunk, &mut chunk, 2832, 2842), 1);
+        assert!(chunk["sections"][0].get("Palette").is_some());
+
+        let mut item = nbt! { "id": "minecraft:grass", "Count": 1i8 };
+        assert_eq!(fixer.update(DataType::Item, &mut item, 3600, 3691), 0);
+        fixer.update(DataType::Item, &mut item, 3600, 3700);
+        assert_eq!(item["id"], Tag::new_string("id", "minecraft:short_grass"));
+
+        let mut pigman = nbt! { "id": "minecraft:zombie_pigman" };
+        fixer.update(DataType::Entity, &mut pigman, 2000, 3000);
+        assert_eq!(
+            pigman["id"],
+            Tag::new_string("id", "minecraft:zombified_piglin")
+        );
+    }
+
+    #[test]
+    fn registration_order_and_versions() {
+        let log = Arc::new(AtomicUsize::new(0));
+        let mut fixer = DataFixer::new();
+        for (version, name) in [(20, "b"), (10, "a"), (20, "c")] {
+            let log = log.clone();
+            fixer.register(DataType::Level, version, name, move |tag| {
+                let step = log.fetch_add(1, Ordering::SeqCst);
+                tag.get_path_mut(&"Data".parse().unwrap())
+                    .unwrap()
+                    .insert(format!("step{}", step), Tag::from(name));
+            });
+        }
+        assert_eq!(
+            fixer.fixes(DataType::Level).collect::<Vec<_>>(),
+            [(10, "a"), (20, "b"), (20, "c")]
+        );
+
+        let mut level = nbt! { "Data": { "DataVersion": 5 } };
+        assert_eq!(fixer.upgrade(DataType::Level, &mut level, 30), Ok(3));
+        assert_eq!(level["Data"]["step2"], Tag::new_string("step2", "c"));
+        assert_eq!(data_version(DataType::Level, &level), Some(30));
+        assert_eq!(
+            fixer.upgrade(DataType::Level, &mut level, 25),
+            Err(FixError::Downgrade { from: 30, to: 25 })
+        );
+    }
+
+    #[test]
+    fn unsupported_chunks_are_refused() {
+        let fixer = DataFixer::vanilla();
+        // 1.12: numeric block IDs, no palette
+        let mut chunk_1_12 = nbt! {
+            "DataVersion": 1343,
+            "Level": { "Sections": [{ "Y": 0i8, "Blocks": [B; 1, 2, 3], "Data": [B; 0, 0] }] },
+        };
+        // 1.15: a palette, but entries span longs
+        let mut chunk_1_15 = nbt! {
+            "DataVersion": 2230,
+            "Level": {
+                "Sections": [{
+                    "Y": 0i8,
+                    "Palette": [{ "Name": "minecraft:air" }, { "Name": "minecraft:stone" }],
+                    "BlockStates": [L; 0x1111, 0x2222],
+                }],
+            },
+        };
+        for (chunk, from) in [(&mut chunk_1_12, 1343), (&mut chunk_1_15, 2230)] {
+            let before = chunk.clone();
+            assert_eq!(
+                fixer.upgrade(DataType::Chunk, chunk, 2860),
+                Err(FixError::TooOld {
+                    from: Some(from),
+                    oldest: 2527
+                })
+            );
+            assert_eq!(*chunk, before);
+        }
+
+        let mut unversioned = nbt! { "Level": { "Sections": [] } };
+        let err = fixer
+            .upgrade(DataType::Chunk, &mut unversioned, 2860)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "data without a version is older than the oldest supported, 2527"
+        );
+        assert!(unversioned.get("DataVersion").is_none());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod level {
+    use crate::{FromNbt, LevelData, Tag, ToNbt, nbt};
+
+    fn level_tag() -> Tag {
+        nbt! {
+            "LevelName": "New World",
+            "DataVersion": 3953,
+            "SpawnX": 16, "SpawnY": 70, "SpawnZ": -32, "SpawnAngle": 0.0f32,
+            "Time": 24000i64, "DayTime": 6000i64,
+            "GameType": 0, "Difficulty": 2i8, "hardcore": 0i8, "allowCommands": 1i8,
+            "raining": 0i8, "rainTime": 1200, "thundering": 0i8, "thunderTime": 3000,
+            "clearWeatherTime": 0,
+            "GameRules": { "doDaylightCycle": "true", "randomTickSpeed": "3" },
+            "WorldGenSettings": {
+                "seed": -42i64, "generate_features": 1i8, "bonus_chest": 0i8,
+                "dimensions": { "minecraft:overworld": { "type": "minecraft:overworld" } },
+                "legacy_custom_options": "x",
+            },
+            "DataPacks": { "Enabled": ["vanilla"], "Disabled": ["bundle"] },
+            "Version": { "Id": 3953, "Name": "1.21", "Snapshot": 0i8 },
+            "WanderingTraderSpawnDelay": 24000,
+        }
+    }
+
+    #[test]
+    fn typed_fields() {
+        let level = LevelData::from_nbt(&level_tag()).unwrap();
+        assert_eq!(level.level_name.as_deref(), Some("New World"));
+        assert_eq!(level.spawn, Some([16, 70, -32]));
+        assert_eq!(
+            (level.allow_commands, level.hardcore),
+            (Some(true), Some(false))
+        );
+        assert_eq!(level.game_rules.as_ref().unwrap()["randomTickSpeed"], "3");
+        let settings = level.world_gen_settings.as_ref().unwrap();
+        assert_eq!(settings.seed, Some(-42));
+        assert!(settings.extra.contains_key("legacy_custom_options"));
+        assert_eq!(
+            level.data_packs.as_ref().unwrap().disabled,
+            Some(vec!["bundle".to_string()])
+        );
+        assert_eq!(level.extra.len(), 2);
+        assert!(level.extra.contains_key("Version"));
+    }
+
+    #[test]
+    fn gzip_round_trip_keeps_unknown_keys() {
+        let mut level = LevelData::from_nbt(&level_tag()).unwrap();
+        level.day_time = Some(13000);
+        level
+            .game_rules
+            .as_mut()
+            .unwrap()
+            .insert("keepInventory".into(), "true".into());
+
+        let mut buf = Vec::new();
+        level.write(&mut buf).unwrap();
+        assert_eq!(&buf[..2], &[0x1f, 0x8b]);
+        let back = LevelData::read(&buf[..]).unwrap();
+        assert_eq!(back, level);
+
+        let mut expected = level_tag();
+        expected.insert("DayTime".into(), Tag::new_long("DayTime", 13000));
+        expected
+            .get_path_mut(&"GameRules".parse().unwrap())
+            .unwrap()
+            .insert(
+                "keepInventory".into(),
+                Tag::new_string("keepInventory", "true"),
+            );
+        assert!(back.to_nbt().canonical_eq(&expected));
+    }
+
+    #[test]
+    fn missing_fields_stay_missing_and_bad_types_stay_extra() {
+        let old = LevelData::from_nbt(&nbt! { "LevelName": "Old", "RandomSeed": 7i64 }).unwrap();
+        assert_eq!(old.world_gen_settings, None);
+        assert_eq!(old.spawn, None);
+        assert!(old.extra.contains_key("RandomSeed"));
+        assert_eq!(
+            old.to_nbt(),
+            nbt! { "LevelName": "Old", "RandomSeed": 7i64 }
+        );
+
+        // A partial spawn point is left alone
+        let partial = nbt! { "SpawnX": 1, "SpawnZ": 2, "WorldGenSettings": { "seed": 3i64 } };
+        let level = LevelData::from_nbt(&partial).unwrap();
+        assert_eq!(level.spawn, None);
+        assert_eq!(level.to_nbt(), partial);
+
+        let odd = nbt! {
+            "Difficulty": 2,
+            "SpawnX": 1, "SpawnY": 64i64, "SpawnZ": 2,
+            "GameRules": { "doFireTick": 1i8 },
+            "WorldGenSettings": { "seed": "random", "bonus_chest": 1i8 },
+        };
+        let level = LevelData::from_nbt(&odd).unwrap();
+        assert_eq!(
+            (level.difficulty, level.spawn, &level.game_rules),
+            (None, None, &None)
+        );
+        let settings = level.world_gen_settings.as_ref().unwrap();
+        assert_eq!((settings.seed, settings.bonus_chest), (None, Some(true)));
+        assert_eq!(level.extra.len(), 5);
+        assert_eq!(level.to_nbt(), odd);
+
+        let mut buf = Vec::new();
+        let mut writer = crate::Writer::to_gzip(&mut buf);
+        writer.write_tag(&Tag::new_compound("")).unwrap();
+        writer.into_inner().finish().unwrap();
+        assert!(LevelData::read(&buf[..]).is_err());
+    }
+
+    #[test]
+    fn save_keeps_old_file() {
+        let dir = std::env::temp_dir().join(format!("nbt-level-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("level.dat");
+
+        let mut level = LevelData::from_nbt(&level_tag()).unwrap();
+        level.save(&path).unwrap();
+        level.level_name = Some("Renamed".into());
+        level.save(&path).unwrap();
+        assert_eq!(LevelData::load(&path).unwrap(), level);
+        let old = LevelData::load(dir.join("level.dat_old")).unwrap();
+        assert_eq!(old.level_name.as_deref(), Some("New World"));
+        assert!(!dir.join("level.dat.tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod player {
+    use std::fs;
+
+    use crate::{FromNbt, PlayerData, Tag, ToNbt, nbt};
+
+    fn player_tag() -> Tag {
+        nbt! {
+            "DataVersion": 3953,
+            "Dimension": "minecraft:the_nether",
+            "Pos": [8.5f64, 64.0, -3.25],
+            "Motion": [0.0f64, -0.0784, 0.0],
+            "Rotation": [90.0f32, 12.5],
+            "OnGround": 1i8,
+            "Health": 20.0f32,
+            "foodLevel": 18, "XpLevel": 5, "XpTotal": 90, "playerGameType": 0,
+            "SelectedItemSlot": 2,
+            "Inventory": [
+                { "Slot": 0i8, "id": "minecraft:diamond", "count": 3 },
+                { "Slot": 100i8, "id": "minecraft:iron_boots", "count": 1,
+                  "components": { "minecraft:damage": 12 } },
+            ],
+            "EnderItems": [{ "Slot": 4i8, "id": "minecraft:diamond", "count": 60 }],
+            "abilities": {
+                "flying": 0i8, "flySpeed": 0.05f32, "instabuild": 0i8, "invulnerable": 0i8,
+                "mayBuild": 1i8, "mayfly": 0i8, "walkSpeed": 0.1f32,
+            },
+            "attributes": [{ "id": "minecraft:max_health", "base": 20.0f64 }],
+            "active_effects": [
+                { "id": "minecraft:fire_resistance", "amplifier": 0i8, "duration": 3600,
+                  "show_icon": 1i8 },
+            ],
+            "UUID": [I; 1, 2, 3, 4],
+            "recipeBook": { "recipes": [] },
+        }
+    }
+
+    #[test]
+    fn typed_accessors() {
+        let player = PlayerData::from_nbt(&player_tag()).unwrap();
+        assert_eq!(player.pos, Some([8.5, 64.0, -3.25]));
+        assert_eq!(player.rotation, Some([90.0, 12.5]));
+        assert_eq!(
+            player.inventory_slot(100).unwrap().id,
+            "minecraft:iron_boots"
+        );
+        assert!(player.inventory_slot(1).is_none());
+        assert_eq!(player.count_items("minecraft:diamond"), 63);
+        assert_eq!(
+            player.attribute("minecraft:max_health").unwrap().base,
+            Some(20.0)
+        );
+        assert_eq!(
+            player.effect("minecraft:fire_resistance").unwrap().duration,
+            Some(3600)
+        );
+        assert_eq!(player.abilities.unwrap().may_build, Some(true));
+        assert!(player.extra.contains_key("UUID"));
+    }
+
+    #[test]
+    fn unknown_keys_round_trip() {
+        let player = PlayerData::from_nbt(&player_tag()).unwrap();
+        assert!(player.to_nbt().canonical_eq(&player_tag()));
+
+        let mut buf = Vec::new();
+        player.write(&mut buf).unwrap();
+        assert_eq!(PlayerData::read(&buf[..]).unwrap(), player);
+
+        let err = PlayerData::from_nbt(&nbt! { "Pos": [1.0f64, 2.0] }).unwrap_err();
+        assert_eq!(err.to_string(), "at Pos: expected 3 elements, found 2");
+    }
+
+    #[test]
+    fn missing_keys_stay_missing() {
+        // A pre-1.20.5 item keeps `Count` and gains no `count`
+        let old = nbt! {
+            "Inventory": [{ "Slot": 0i8, "id": "minecraft:stone", "Count": 5i8 }],
+            "abilities": { "flying": 1i8 },
+            "attributes": [{ "id": "minecraft:luck" }],
+            "active_effects": [{ "id": "minecraft:speed" }],
+        };
+        let player = PlayerData::from_nbt(&old).unwrap();
+        assert_eq!(player.health, None);
+        assert_eq!(player.to_nbt(), old);
+
+        let mut player = PlayerData::from_nbt(&nbt! {}).unwrap();
+        assert_eq!(player.to_nbt(), nbt! {});
+        player.health = Some(0.0);
+        assert_eq!(player.to_nbt(), nbt! { "Health": 0.0f32 });
+    }
+
+    #[test]
+    fn save_rotates_backup() {
+        let dir = std::env::temp_dir().join(format!("nbt-player-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("0000-1111.dat");
+        let backup = dir.join("0000-1111.dat_old");
+
+        let mut player = PlayerData::from_nbt(&player_tag()).unwrap();
+        player.save(&path).unwrap();
+        assert!(!backup.exists());
+
+        player.xp_level = Some(6);
+        player.save(&path).unwrap();
+        assert_eq!(PlayerData::load(&path).unwrap().xp_level, Some(6));
+        assert_eq!(PlayerData::load(&backup).unwrap().xp_level, Some(5));
+        assert!(!dir.join("0000-1111.dat.tmp").exists());
+
+        fs::write(&path, b"not gzip").unwrap();
+        assert!(PlayerData::load(&path).is_err());
+        assert_eq!(
+            PlayerData::load_with_backup(&path).unwrap().xp_level,
+            Some(5)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod chunk_section {
+    use crate::{ChunkSection, Layout, PackedArray, Tag, nbt, packed::PackedArrayError};
+
+    #[test]
+    fn aligned_entries_never_span() {
+        let mut array = PackedArray::new(5, 24, Layout::Aligned);
+        assert_eq!(array.as_longs().len(), 2);
+        array.set(11, 0b10101);
+        array.set(12, 31);
+        assert_eq!(array.as_longs(), [0b10101 << 55, 31]);
+        assert_eq!(array.get(11), 0b10101);
+        assert_eq!(array.get(12), 31);
+        assert_eq!(PackedArray::longs_needed(5, 4096, Layout::Aligned), 342);
+    }
+
+    #[test]
+    fn spanning_entries_cross_longs() {
+        let mut array = PackedArray::new(5, 24, Layout::Spanning);
+        array.set(12, 0b10111);
+        // Bits 60..65: the low four bits end one long, the top bit starts the next
+        assert_eq!(array.as_longs(), [(0b0111u64 << 60) as i64, 1]);
+        assert_eq!(array.get(12), 0b10111);
+        array.set(12, 0);
+        assert_eq!(array.as_longs(), [0, 0]);
+
+        let values: Vec<u64> = (0..100).map(|i| i * 7 % 32).collect();
+        let spanning = PackedArray::from_values(&values, 5, Layout::Spanning);
+        let aligned = spanning.repack(5, Layout::Aligned);
+        assert_eq!(aligned.iter().collect::<Vec<_>>(), values);
+        assert_eq!(
+            spanning
+                .repack(9, Layout::Spanning)
+                .iter()
+                .collect::<Vec<_>>(),
+            values
+        );
+        assert_eq!(
+            PackedArray::from_longs(vec![0; 3], 5, 100, Layout::Spanning),
+            Err(PackedArrayError {
+                expected: 8,
+                found: 3
+            })
+        );
+    }
+
+    fn block(name: &str) -> Tag {
+        nbt! { "Name": name }
+    }
+
+    #[test]
+    fn set_block_grows_and_compacts_palette() {
+        let mut section = nbt! {
+            "Y": -4i8,
+            "block_states": { "palette": [{ "Name": "minecraft:air" }] },
+        };
+        let mut view = ChunkSection::new(&mut section, 3953).unwrap();
+        assert_eq!(view.y(), Some(-4));
+        assert_eq!(
+            view.get_block(3, 4, 5)["Name"],
+            Tag::new_string("Name", "minecraft:air")
+        );
+
+        view.set_block(3, 4, 5, block("minecraft:air"));
+        view.set_block(3, 4, 5, block("minecraft:stone"));
+        assert_eq!(
+            view.get_block(3, 4, 5)["Name"],
+            Tag::new_string("Name", "minecraft:stone")
+        );
+        assert_eq!(view.palette_index(0, 0, 0), 0);
+        for i in 0..16 {
+            view.set_block(i, 15, 15, block(&format!("minecraft:wool_{}", i)));
+        }
+        assert_eq!(view.palette().len(), 18);
+        assert_eq!(
+            view.get_block(3, 4, 5)["Name"],
+            Tag::new_string("Name", "minecraft:stone")
+        );
+        assert_eq!(
+            view.get_block(15, 15, 15)["Name"],
+            Tag::new_string("Name", "minecraft:wool_15")
+        );
+        match &section["block_states"]["data"] {
+            Tag::LongArray { value, .. } => assert_eq!(value.len(), 342),
+            other => panic!("expected a long array, got {:?}", other),
+        }
+
+        let mut view = ChunkSection::new(&mut section, 3953).unwrap();
+        for i in 0..16 {
+            view.set_block(i, 15, 15, block("minecraft:air"));
+        }
+        view.compact();
+        assert_eq!(view.palette().len(), 2);
+        assert_eq!(
+            view.get_block(3, 4, 5)["Name"],
+            Tag::new_string("Name", "minecraft:stone")
+        );
+        view.set_block(3, 4, 5, block("minecraft:air"));
+        view.compact();
+        assert!(section["block_states"].get("data").is_none());
+    }
+
+    #[test]
+    fn legacy_sections_use_spanning_data() {
+        let palette: Vec<Tag> = (0..17)
+            .map(|i| block(&format!("minecraft:b{}", i)))
+            .collect();
+        let mut section = nbt! { "Y": 0i8 };
+        section.insert("Palette".into(), Tag::from(palette).with_name("Palette"));
+        let indices: Vec<u64> = (0..4096).map(|i| i % 17).collect();
+        let data = PackedArray::from_values(&indices, 5, Layout::Spanning).into_longs();
+        assert_eq!(data.len(), 320);
+        section.insert(
+            "BlockStates".into(),
+            Tag::new_long_array("BlockStates", data),
+        );
+
+        let view = ChunkSection::new(&mut section, 2230).unwrap();
+        assert_eq!(
+            view.get_block(13, 0, 0)["Name"],
+            Tag::new_string("Name", "minecraft:b13")
+        );
+        assert_eq!(view.palette_index(1, 2, 3), (2 * 256 + 3 * 16 + 1) % 17);
+
+        // The same longs are too few for the aligned layout of a newer version
+        let err = ChunkSection::new(&mut section, 2586).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "at BlockStates: expected 342 longs for a palette of 17, found 320"
+        );
+        assert!(ChunkSection::new(&mut nbt! { "Y": 0i8 }, 3953).is_err());
+    }
+
+    #[test]
+    fn indices_past_the_palette_are_errors() {
+        let mut indices = vec![0u64; 4096];
+        indices[100] = 7;
+        let data = PackedArray::from_values(&indices, 4, Layout::Aligned).into_longs();
+        let mut section = nbt! {
+            "Y": 0i8,
+            "block_states": { "palette": [{ "Name": "minecraft:air" }, { "Name": "minecraft:stone" }] },
+        };
+        section["block_states"].insert("data".into(), Tag::new_long_array("data", data));
+        let err = ChunkSection::new(&mut section, 3953).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "at block_states.data: index 7 is outside the palette of 2"
+        );
+    }
+
+    #[test]
+    fn data_only_omitted_for_single_entry_palettes() {
+        let mut section = nbt! {
+            "Y": 0i8,
+            "block_states": { "palette": [{ "Name": "minecraft:air" }, { "Name": "minecraft:stone" }] },
+        };
+        let err = ChunkSection::new(&mut section, 3953).err().unwrap();
+        assert_eq!(err.to_string(), "at block_states: missing field `data`");
+
+        // 1.13-1.17 sections always pack at 4 bits or more, but a lone entry needs no data
+        let mut legacy = nbt! { "Y": 0i8, "Palette": [{ "Name": "minecraft:air" }] };
+        let mut view = ChunkSection::new(&mut legacy, 2730).unwrap();
+        view.set_block(0, 0, 0, nbt! { "Name": "minecraft:air" });
+        assert!(legacy.get("BlockStates").is_none());
+        let mut view = ChunkSection::new(&mut legacy, 2730).unwrap();
+        view.set_block(1, 0, 0, nbt! { "Name": "minecraft:stone" });
+        assert_eq!(view.palette_index(1, 0, 0), 1);
+        assert_eq!(view.palette_index(2, 0, 0), 0);
+        assert_eq!(legacy["BlockStates"].id(), 12);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod biomes_heightmaps {
+    use crate::{
+        ChunkSection, Layout, PackedArray, Tag,
+        chunk::{Heightmap, HeightmapKind, is_air, recompute_heightmaps},
+        nbt,
+    };
+
+    #[test]
+    fn biome_palette_grows_from_zero_bits() {
+        let mut section = nbt! {
+            "Y": 0i8,
+            "block_states": { "palette": [{ "Name": "minecraft:air" }] },
+            "biomes": { "palette": ["minecraft:plains"] },
+        };
+        let mut view = ChunkSection::new(&mut section, 3953).unwrap();
+        assert_eq!(view.get_biome(3, 3, 3), Some("minecraft:plains"));
+        view.set_biome(1, 2, 3, "minecraft:river");
+        view.set_biome(0, 0, 0, "minecraft:beach");
+        assert_eq!(view.get_biome(1, 2, 3), Some("minecraft:river"));
+        let biomes = view.biomes().unwrap();
+        assert_eq!(biomes.len(), 64);
+        assert_eq!(biomes[0], "minecraft:beach");
+        assert_eq!(biomes[2 << 4 | 3 << 2 | 1], "minecraft:river");
+        // Three biomes need 2 bits: 32 per long, two longs
+        match &section["biomes"]["data"] {
+            Tag::LongArray { value, .. } => assert_eq!(value.len(), 2),
+            other => panic!("expected a long array, got {:?}", other),
+        }
+
+        let mut legacy =
+            nbt! { "Y": 0i8, "block_states": { "palette": [{ "Name": "minecraft:air" }] } };
+        let mut view = ChunkSection::new(&mut legacy, 3953).unwrap();
+        assert_eq!(view.get_biome(0, 0, 0), None);
+        view.set_biome(0, 0, 0, "minecraft:desert");
+        assert_eq!(view.get_biome(2, 2, 2), Some("minecraft:desert"));
+        assert!(legacy["biomes"].get("data").is_none());
+    }
+
+    #[test]
+    fn heightmap_round_trip() {
+        let mut map = Heightmap::new(384);
+        map.set(0, 0, 384);
+        map.set(15, 15, 129);
+        let tag = map.encode(Layout::Aligned);
+        match &tag {
+            // 7 entries of 9 bits per long
+            Tag::LongArray { value, .. } => assert_eq!(value.len(), 37),
+            other => panic!("expected a long array, got {:?}", other),
+        }
+        let back = Heightmap::decode(&tag, 384, Layout::Aligned).unwrap();
+        assert_eq!(back, map);
+        assert_eq!(back.top_y(15, 15, -64), 65);
+        assert_eq!(
+            Heightmap::decode(&tag, 256, Layout::Spanning)
+                .unwrap_err()
+                .to_string(),
+            "expected 36 longs of packed data, found 37"
+        );
+    }
+
+    #[test]
+    fn recompute_from_blocks() {
+        // Stone up to y=2 of the section at Y=-4, a single leaves block above x=1 z=0
+        let mut indices = vec![0u64; 4096];
+        indices[..3 * 256].fill(1);
+        indices[5 << 8 | 1] = 2;
+        let data = PackedArray::from_values(&indices, 4, Layout::Aligned).into_longs();
+        let mut section = nbt! {
+            "Y": -4i8,
+            "block_states": {
+                "palette": [
+                    { "Name": "minecraft:air" },
+                    { "Name": "minecraft:stone" },
+                    { "Name": "minecraft:oak_leaves" },
+                ],
+            },
+        };
+        section["block_states"].insert("data".into(), Tag::new_long_array("data", data));
+        let mut chunk = nbt! { "DataVersion": 3953, "yPos": -4, "sections": [] };
+        chunk["sections"].push(section);
+        chunk["sections"].push(nbt! { "Y": -5i8, "SkyLight": [B; 0] });
+
+        recompute_heightmaps(
+            &mut chunk,
+            3953,
+            -64,
+            384,
+            &HeightmapKind::SAVED,
+            |kind, state| {
+                let leaves = state["Name"] == Tag::new_string("Name", "minecraft:oak_leaves");
+                match kind {
+                    HeightmapKind::MotionBlockingNoLeaves => !is_air(state) && !leaves,
+                    _ => !is_air(state),
+                }
+            },
+        )
+        .unwrap();
+
+        let decode =
+            |key: &str| Heightmap::decode(&chunk["Heightmaps"][key], 384, Layout::Aligned).unwrap();
+        let surface = decode("WORLD_SURFACE");
+        assert_eq!(surface.get(0, 0), 3);
+        assert_eq!(surface.top_y(0, 0, -64), -61);
+        assert_eq!(surface.get(1, 0), 6);
+        assert_eq!(decode("MOTION_BLOCKING_NO_LEAVES").get(1, 0), 3);
+        assert_eq!(
+            chunk["Heightmaps"]["OCEAN_FLOOR"].name(),
+            Some("OCEAN_FLOOR")
+        );
+    }
+
+    #[test]
+    fn recompute_rejects_corrupt_sections() {
+        let mut indices = vec![0u64; 4096];
+        indices[4095] = 9;
+        let data = PackedArray::from_values(&indices, 4, Layout::Aligned).into_longs();
+        let mut section = nbt! {
+            "Y": 0i8,
+            "block_states": { "palette": [{ "Name": "minecraft:air" }, { "Name": "minecraft:stone" }] },
+        };
+        section["block_states"].insert("data".into(), Tag::new_long_array("data", data));
+        let mut chunk = nbt! { "sections": [] };
+        chunk["sections"].push(section);
+        let before = chunk.clone();
+
+        let err =
+            recompute_heightmaps(&mut chunk, 3953, -64, 384, &HeightmapKind::SAVED, |_, _| {
+                true
+            })
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at sections[0].block_states.data: index 9 is outside the palette of 2"
+        );
+        assert_eq!(chunk, before);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod nibble {
+    use crate::{NibbleArray, Tag, nibble::NIBBLE_ARRAY_LEN};
+
+    #[test]
+    fn vanilla_nibble_order() {
+        let mut light = NibbleArray::new();
+        light.set(0, 0, 0, 0xa);
+        light.set(1, 0, 0, 0x5);
+        light.set(0, 1, 0, 0xf);
+        light.set(3, 0, 2, 0x17);
+        let bytes = light.as_bytes();
+        // Even index in the low nibble, odd in the high one
+        assert_eq!(bytes[0], 0x5a);
+        assert_eq!(bytes[128], 0x0f);
+        assert_eq!(bytes[(2 << 4 | 3) >> 1], 0x70);
+        assert_eq!(light.get(3, 0, 2), 0x7);
+        assert_eq!(light.iter().filter(|&v| v != 0).count(), 4);
+        assert_eq!(NibbleArray::filled(15).get(15, 15, 15), 15);
+    }
+
+    #[test]
+    fn wraps_tags_without_copying() {
+        let mut tag = Tag::new_byte_array("SkyLight", vec![0; NIBBLE_ARRAY_LEN]);
+        let ptr = <&[u8]>::try_from(&tag).unwrap().as_ptr();
+        {
+            let mut light = NibbleArray::from_tag_mut(&mut tag).unwrap();
+            light.set(2, 3, 4, 9);
+            assert_eq!(light.as_bytes().as_ptr(), ptr);
+        }
+        let view = NibbleArray::from_tag(&tag).unwrap();
+        assert_eq!(view.get(2, 3, 4), 9);
+        assert_eq!(view.to_owned_array().get(2, 3, 4), 9);
+
+        let owned = NibbleArray::try_from(tag).unwrap();
+        assert_eq!(owned.as_bytes().as_ptr(), ptr);
+        let back = Tag::from(owned);
+        assert_eq!(back.name(), None);
+        assert_eq!(<&[u8]>::try_from(&back).unwrap().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn rejects_wrong_shapes() {
+        let short = Tag::new_byte_array("Data", vec![0; 16]);
+        assert_eq!(
+            NibbleArray::from_tag(&short).unwrap_err().to_string(),
+            "expected 2048 bytes of nibbles, found 16"
+        );
+        assert!(NibbleArray::try_from(Tag::from(3)).is_err());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod uuid {
+    use crate::{
+        DataFixer, DataType, FromNbt, PlayerData, Tag, UuidForm, nbt,
+        uuid::{format_uuid, parse_uuid, uuid_from_ints, uuid_of, uuid_to_ints},
+    };
+
+    // Notch's UUID, in all of the forms vanilla has used
+    const UUID: u128 = 0x069a79f444e94726a5befca90e38aaf5;
+    const INTS: [i32; 4] = [110_787_060, 1_156_138_790, -1_514_210_135, 238_594_805];
+
+    #[test]
+    fn conversions() {
+        assert_eq!(uuid_to_ints(UUID), INTS);
+        assert_eq!(uuid_from_ints(INTS), UUID);
+        assert_eq!(format_uuid(UUID), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(
+            parse_uuid("069A79F4-44E9-4726-A5BE-FCA90E38AAF5"),
+            Some(UUID)
+        );
+        assert_eq!(parse_uuid("069a79f444e94726a5befca90e38aaf5"), None);
+        assert_eq!(parse_uuid("069a79f4-44e9-4726-a5be-fca90e38aaf"), None);
+        assert_eq!(uuid_of(&Tag::from(vec![1, 2, 3])), None);
+    }
+
+    #[test]
+    fn read_and_write_every_form() {
+        let mut tag = nbt! {
+            "UUIDMost": 0x069a79f444e94726u64 as i64,
+            "UUIDLeast": 0xa5befca90e38aaf5u64 as i64,
+        };
+        assert_eq!(tag.get_uuid("UUID"), Some(UUID));
+
+        tag.set_uuid("UUID", UUID, UuidForm::IntArray);
+        assert_eq!(
+            tag,
+            nbt! { "UUID": [I; INTS[0], INTS[1], INTS[2], INTS[3]] }
+        );
+        assert_eq!(tag["UUID"].name(), Some("UUID"));
+        assert_eq!(tag.get_uuid("UUID"), Some(UUID));
+
+        tag.set_uuid("UUID", UUID, UuidForm::String);
+        assert_eq!(tag, nbt! { "UUID": "069a79f4-44e9-4726-a5be-fca90e38aaf5" });
+        assert_eq!(tag.get_uuid("UUID"), Some(UUID));
+
+        tag.set_uuid("UUID", UUID, UuidForm::MostLeast);
+        assert!(tag.get("UUID").is_none());
+        assert_eq!(tag.remove_uuid("UUID"), Some(UUID));
+        assert_eq!(tag, nbt! {});
+    }
+
+    #[test]
+    fn legacy_pairs_are_fixed() {
+        let (most, least) = crate::uuid::uuid_to_longs(UUID);
+        let mut wolf = nbt! {
+            "id": "minecraft:wolf",
+            "OwnerUUID": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+            "Leash": { "UUIDMost": 1i64, "UUIDLeast": 2i64 },
+            "Attributes": [
+                { "Name": "generic.maxHealth", "Modifiers": [{ "UUIDMost": most, "UUIDLeast": least }] },
+            ],
+            "Passengers": [{ "id": "minecraft:pig", "UUIDMost": most, "UUIDLeast": least }],
+        };
+        wolf.set_uuid("UUID", UUID, UuidForm::MostLeast);
+        let fixer = DataFixer::vanilla();
+        fixer.update(DataType::Entity, &mut wolf, 2500, 2586);
+        assert_eq!(wolf["UUID"], Tag::new_int_array("UUID", INTS.to_vec()));
+        assert!(wolf.get("UUIDMost").is_none());
+        assert_eq!(wolf.get_uuid("Owner"), Some(UUID));
+        assert!(wolf.get("OwnerUUID").is_none());
+        assert_eq!(wolf["Leash"].get_uuid("UUID"), Some(1 << 64 | 2));
+        assert_eq!(wolf["Leash"]["UUID"].id(), 11);
+        assert_eq!(wolf["Attributes"][0]["Modifiers"][0]["UUID"].id(), 11);
+        assert_eq!(
+            wolf["Passengers"][0]["UUID"],
+            Tag::new_int_array("UUID", INTS.to_vec())
+        );
+
+        let mut head = nbt! {
+            "id": "minecraft:player_head",
+            "tag": { "SkullOwner": { "Id": "069a79f4-44e9-4726-a5be-fca90e38aaf5" } },
+        };
+        fixer.update(DataType::Item, &mut head, 2500, 2586);
+        assert_eq!(head["tag"]["SkullOwner"]["Id"].id(), 11);
+
+        let mut player = nbt! { "DataVersion": 2230, "UUIDMost": most, "UUIDLeast": least };
+        assert_eq!(PlayerData::from_nbt(&player).unwrap().uuid(), Some(UUID));
+        fixer.upgrade(DataType::Player, &mut player, 2586).unwrap();
+        assert_eq!(PlayerData::from_nbt(&player).unwrap().uuid(), Some(UUID));
+        assert_eq!(player["UUID"].id(), 11);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod numeric {
+    use crate::{FromNbt, Tag, ToNbt, nbt, player::ItemStack};
+
+    #[test]
+    fn integers_wrap_when_narrowed() {
+        let tag = Tag::from(0x1234_5678_9abc_def0i64);
+        assert_eq!(tag.as_long(), Some(0x1234_5678_9abc_def0));
+        assert_eq!(tag.as_int(), Some(0x9abc_def0u32 as i32));
+        assert_eq!(tag.as_short(), Some(0xdef0u16 as i16));
+        assert_eq!(tag.as_byte(), Some(0xf0u8 as i8));
+        assert_eq!(Tag::from(300).as_byte(), Some(44));
+        assert_eq!(Tag::from(-1i8).as_long(), Some(-1));
+        assert_eq!(Tag::from(7i16).as_double(), Some(7.0));
+    }
+
+    #[test]
+    fn floats_floor_like_vanilla() {
+        assert_eq!(Tag::from(-1.5f64).as_int(), Some(-2));
+        assert_eq!(Tag::from(-1.5f64).as_long(), Some(-2));
+        // FloatTag.getAsLong truncates rather than floors
+        assert_eq!(Tag::from(-1.5f32).as_long(), Some(-1));
+        assert_eq!(Tag::from(-1.5f32).as_int(), Some(-2));
+        assert_eq!(Tag::from(1e10f64).as_int(), Some(i32::MAX));
+        assert_eq!(Tag::from(f64::NAN).as_int(), Some(0));
+        assert_eq!(Tag::from(257.9f64).as_byte(), Some(1));
+        assert_eq!(Tag::from(0.1f64).as_float(), Some(0.1f32));
+    }
+
+    #[test]
+    fn booleans_and_non_numbers() {
+        assert_eq!(Tag::from(true).as_bool(), Some(true));
+        assert_eq!(Tag::from(2).as_bool(), Some(true));
+        assert_eq!(Tag::from(256).as_bool(), Some(false));
+        assert_eq!(Tag::from(0.5f32).as_bool(), Some(false));
+        assert!(Tag::from(1.0f32).is_numeric());
+        assert!(!Tag::from("1").is_numeric());
+        assert_eq!(Tag::from("1").as_int(), None);
+        assert_eq!(Tag::from(vec![1, 2]).as_bool(), None);
+        // The strict conversions are unchanged
+        assert!(i8::try_from(&Tag::from(1)).is_err());
+        assert!(bool::try_from(&Tag::from(1)).is_err());
+    }
+
+    #[test]
+    fn item_counts_accept_any_number() {
+        let item = nbt! { "Slot": 3, "id": "minecraft:stone", "count": 64i8 };
+        let item = ItemStack::from_nbt(&item).unwrap();
+        assert_eq!((item.slot, item.count), (Some(3), Some(64)));
+
+        let item = nbt! { "id": "minecraft:stone", "count": "64" };
+        let err = ItemStack::from_nbt(&item).unwrap_err();
+        assert!(err.to_string().contains("count"), "{}", err);
+    }
+
+    #[test]
+    fn item_numbers_keep_their_type_unless_edited() {
+        let tag = nbt! { "Slot": 3i16, "id": "minecraft:stone", "count": 64i8 };
+        let mut item = ItemStack::from_nbt(&tag).unwrap();
+        assert_eq!((item.slot, item.count), (Some(3), Some(64)));
+        assert_eq!(item.to_nbt(), tag);
+
+        item.slot = Some(4);
+        item.count = None;
+        let saved = item.to_nbt();
+        assert_eq!(saved["Slot"], Tag::new_byte("Slot", 4));
+        assert_eq!(saved["count"], Tag::End);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod mixed_lists {
+    use crate::{Reader, Tag, Writer, nbt};
+
+    fn roundtrip(tag: &Tag, unwrap: bool) -> Tag {
+        let bytes = tag.to_bytes().unwrap();
+        assert_eq!(bytes.len(), tag.encoded_len().unwrap());
+        Reader::new(&bytes[..])
+            .unwrap_mixed_lists(unwrap)
+            .read_tag()
+            .unwrap()
+    }
+
+    #[test]
+    fn building() {
+        let list = Tag::from(vec![Tag::from(1), Tag::from("two")]);
+        assert!(list.is_mixed_list());
+        assert!(matches!(list, Tag::List { element_id: 10, .. }));
+        assert!(!Tag::from(vec![Tag::from(1), Tag::from(2)]).is_mixed_list());
+        assert!(!Tag::from(1).is_mixed_list());
+
+        let mut list = Tag::from(vec![Tag::from(1)]);
+        list.push(Tag::from(2));
+        assert!(matches!(list, Tag::List { element_id: 3, .. }));
+        list.push(Tag::from(3.0f32));
+        assert!(matches!(list, Tag::List { element_id: 10, .. }));
+        assert_eq!(list.to_string(), "[1,2,3.0f]");
+    }
+
+    #[test]
+    fn end_elements_are_rejected() {
+        for elements in [vec![Tag::from(1), Tag::End], vec![Tag::End, Tag::End]] {
+            let list = Tag::from(elements).with_name("");
+            let err = list.to_bytes().unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert_eq!(list.encoded_len().unwrap_err().kind(), err.kind());
+        }
+    }
+
+    #[test]
+    fn written_with_wrappers() {
+        let list = Tag::from(vec![Tag::from(1i8), nbt! { "a": 2i8 }]).with_name("");
+        let bytes = list.to_bytes().unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            9, 0, 0, 10, 0, 0, 0, 2,
+            1, 0, 0, 1, 0, // {"": 1b}
+            1, 0, 1, b'a', 2, 0, // {a: 2b}
+        ];
+        assert_eq!(bytes, expected);
+
+        // Without the flag the wrappers are kept
+        let read = roundtrip(&list, false);
+        assert!(!read.is_mixed_list());
+        assert_eq!(read[0], nbt! { "": 1i8 });
+        assert_eq!(read[1], nbt! { "a": 2i8 });
+    }
+
+    #[test]
+    fn unwrapped_on_read() {
+        let mixed = vec![
+            Tag::from(1),
+            Tag::from("two"),
+            nbt! { "three": 3 },
+            Tag::from(vec![Tag::from(4i64)]),
+        ];
+        let tag = nbt! {
+            "mixed": Tag::from(mixed),
+            "plain": [{ "a": 1 }, { "b": 2 }],
+            "empty": [],
+        }
+        .with_name("");
+        let read = roundtrip(&tag, true);
+        assert_eq!(read, tag);
+        assert!(read["mixed"].is_mixed_list());
+
+        let read = roundtrip(&tag, false);
+        assert_eq!(read["mixed"][1], nbt! { "": "two" });
+    }
+
+    #[test]
+    fn compound_lists_written_as_is() {
+        // A list of only wrappers can't be told apart from a wrapped list, as in vanilla
+        let tag = nbt! { "wrapper": [{ "": 1 }, { "": 2 }] }.with_name("");
+        assert_eq!(roundtrip(&tag, false), tag);
+        assert_eq!(
+            roundtrip(&tag, true)["wrapper"],
+            Tag::from(vec![Tag::from(1), Tag::from(2)]).with_name("wrapper")
+        );
+    }
+
+    #[test]
+    fn vanilla_bytes_survive_either_read() {
+        // `{l: [1, "a"]}` as written by 1.21.5
+        #[rustfmt::skip]
+        let vanilla = [
+            10, 0, 0,
+            9, 0, 1, b'l', 10, 0, 0, 0, 2,
+            3, 0, 0, 0, 0, 0, 1, 0,
+            8, 0, 0, 0, 1, b'a', 0,
+            0,
+        ];
+        for unwrap in [false, true] {
+            let tag = Reader::new(&vanilla[..])
+                .unwrap_mixed_lists(unwrap)
+                .read_tag()
+                .unwrap();
+            assert_eq!(tag.to_bytes().unwrap(), vanilla);
+            assert_eq!(tag.encoded_len().unwrap(), vanilla.len());
+        }
+    }
+
+    #[test]
+    fn written_the_same_by_either_writer() {
+        let list = vec![Tag::from(1i8), Tag::from(2i16), Tag::from("x")];
+        let tag = nbt! { "list": Tag::from(list) };
+        let mut buf = Vec::new();
+        Writer::new(&mut buf).write_network_tag(&tag).unwrap();
+        assert_eq!(buf.len(), tag.network_encoded_len().unwrap());
+        assert_eq!(buf, tag.to_network_bytes().unwrap());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod empty_lists {
+    use crate::{Reader, Tag, diff, nbt};
+
+    // `{Passengers: []}` as vanilla writes it: an empty list of End
+    #[rustfmt::skip]
+    const VANILLA: [u8; 22] = [
+        10, 0, 0,
+        9, 0, 10, b'P', b'a', b's', b's', b'e', b'n', b'g', b'e', b'r', b's', 0, 0, 0, 0, 0,
+        0,
+    ];
+
+    fn read(bytes: &[u8]) -> Tag {
+        Reader::new(bytes).read_tag().unwrap()
+    }
+
+    #[test]
+    fn read_as_end_lists() {
+        // Older versions wrote the list's original type for empty lists
+        let mut old = VANILLA;
+        old[16] = 10;
+        let expected = nbt! { "Passengers": [] }.with_name("");
+        assert_eq!(read(&old), expected);
+        assert_eq!(read(&VANILLA), expected);
+        assert_eq!(
+            read(&old)["Passengers"],
+            Tag::new_list("Passengers", 10, vec![])
+        );
+    }
+
+    #[test]
+    fn written_as_end_lists() {
+        let mut root = Tag::new_compound("");
+        let list = Tag::List {
+            name: Some("Passengers".to_string()),
+            element_id: 10,
+            elements: vec![],
+        };
+        root.insert("Passengers".to_string(), list);
+        assert_eq!(root.to_bytes().unwrap(), VANILLA);
+        assert!(root.canonical_eq(&nbt! { "Passengers": [] }));
+        assert!(diff(&root, &nbt! { "Passengers": [] }).is_empty());
+    }
+
+    #[test]
+    fn push_adopts_the_first_type() {
+        let mut list = Tag::new_list("", 0, vec![]);
+        list.push(Tag::from(1));
+        list.push(Tag::from(2));
+        assert_eq!(
+            list,
+            Tag::from(vec![Tag::from(1), Tag::from(2)]).with_name("")
+        );
+        #[rustfmt::skip]
+        let expected = [9, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2];
+        assert_eq!(list.to_bytes().unwrap(), expected);
+    }
+
+    #[test]
+    fn mismatched_element_ids_are_errors() {
+        for element_id in [0, 3] {
+            let list = Tag::List {
+                name: Some(String::new()),
+                element_id,
+                elements: vec![Tag::from(7i8)],
+            };
+            let err = list.to_bytes().unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "list element_id {} doesn't match its elements of type 1",
+                    element_id
+                )
+            );
+            assert!(list.encoded_len().is_err());
+        }
+
+        let list = Tag::new_list("", 1, vec![Tag::from(7i8)]);
+        assert_eq!(list.to_bytes().unwrap(), [9, 0, 0, 1, 0, 0, 0, 1, 7]);
+        assert_eq!(list.encoded_len().unwrap(), 9);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod snbt_parse {
+    use crate::{Tag, nbt};
+
+    fn parse(s: &str) -> Tag {
+        s.parse().unwrap_or_else(|e| panic!("{}: {}", s, e))
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(parse("1b"), Tag::from(1i8));
+        assert_eq!(parse("-2S"), Tag::from(-2i16));
+        assert_eq!(parse("3"), Tag::from(3));
+        assert_eq!(parse("4L"), Tag::from(4i64));
+        assert_eq!(parse("1.5f"), Tag::from(1.5f32));
+        assert_eq!(parse("1.5"), Tag::from(1.5f64));
+        assert_eq!(parse("2d"), Tag::from(2.0f64));
+        assert_eq!(parse("1e3"), Tag::from(1000.0f64));
+        assert_eq!(parse("true"), Tag::from(1i8));
+        assert!("minecraft:stone".parse::<Tag>().is_err());
+        assert_eq!(parse("stone"), Tag::from("stone"));
+        assert_eq!(parse("'it\\'s'"), Tag::from("it's"));
+        assert_eq!(parse(r#""tab\thereé""#), Tag::from("tab\there\u{e9}"));
+        assert!(
+            "1.0.0"
+                .parse::<Tag>()
+                .is_ok_and(|t| t == Tag::from("1.0.0"))
+        );
+    }
+
+    #[test]
+    fn containers() {
+        let tag = parse(
+            r#"{ Count: 1b, id: "minecraft:stone", "odd key": [1, 2],
+                 arrays: [[B; 1b, -2], [I;], [L; 5L]], empty: {} }"#,
+        );
+        let expected = nbt! {
+            "Count": 1i8,
+            "id": "minecraft:stone",
+            "odd key": [1, 2],
+            "arrays": [[B; 1, -2], [I;], [L; 5]],
+            "empty": {},
+        };
+        assert_eq!(tag, expected);
+        assert!(parse("[1, \"a\"]").is_mixed_list());
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        let tag = nbt! {
+            "a": [1.0f32, -0.5],
+            "b": { "c": [L; 1, 2], "d": "x\"y\\z" },
+            "e": [{ "f": 1i16 }, { "g": [B; 3] }],
+            "h": 1e-7f64,
+            "": { "": 1i8 },
+            "mixed": Tag::from(vec![Tag::from(1), Tag::from("two")]),
+        };
+        assert_eq!(parse(&tag.to_string()), tag);
+        assert_eq!(parse(&format!("{:#}", tag)), tag);
+    }
+
+    #[test]
+    fn errors() {
+        let err = "{a: 1".parse::<Tag>().unwrap_err();
+        assert_eq!(err.position, 5);
+        assert_eq!(err.to_string(), "expected ',' or '}' at position 5");
+        assert!("[B; 300]".parse::<Tag>().is_err());
+        assert!("128b".parse::<Tag>().is_err());
+        assert!("{a: 1} x".parse::<Tag>().is_err());
+        assert!("\"open".parse::<Tag>().is_err());
+        assert!("".parse::<Tag>().is_err());
+        assert!("{:1}".parse::<Tag>().is_err());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod json {
+    use crate::{JsonMode, Tag, nbt};
+
+    #[test]
+    fn natural_output() {
+        let tag = nbt! {
+            "b": 1i8,
+            "l": 5i64,
+            "d": 1.0f64,
+            "s": "q\"\n",
+            "arr": [I; 1, 2],
+            "list": [{ "x": 1 }],
+            "nan": f32::NAN,
+        };
+        assert_eq!(
+            tag.to_json(JsonMode::Natural),
+            r#"{"arr":[1,2],"b":1,"d":1.0,"l":5,"list":[{"x":1}],"nan":null,"s":"q\"\n"}"#
+        );
+        assert_eq!(
+            nbt! { "a": [[1]] }.to_json_pretty(JsonMode::Natural),
+            "{\n  \"a\": [\n    [1]\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn natural_input() {
+        let tag = Tag::from_json(
+            r#" {"i": 1, "big": 5000000000, "f": 1.5, "t": true, "s": "é😀",
+                 "list": [1, 2], "nested": {"empty": []}} "#,
+            JsonMode::Natural,
+        )
+        .unwrap();
+        let expected = nbt! {
+            "i": 1,
+            "big": 5_000_000_000i64,
+            "f": 1.5,
+            "t": 1i8,
+            "s": "\u{e9}\u{1f600}",
+            "list": [1, 2],
+            "nested": { "empty": [] },
+        };
+        assert_eq!(tag, expected);
+        // Only the byte doesn't survive the trip back
+        let back = Tag::from_json(&expected.to_json(JsonMode::Natural), JsonMode::Natural).unwrap();
+        assert_eq!(back["t"], Tag::new_int("t", 1));
+        assert_eq!(back["f"], expected["f"]);
+        assert_eq!(back["s"], expected["s"]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Tag::from_json(r#"{"a": null}"#, JsonMode::Natural).unwrap_err();
+        assert_eq!(err.to_string(), "null has no NBT equivalent at position 6");
+        assert!(Tag::from_json("{a: 1}", JsonMode::Natural).is_err());
+        assert!(Tag::from_json("[1,]", JsonMode::Natural).is_err());
+        assert!(Tag::from_json(r#""\ud800""#, JsonMode::Natural).is_err());
+    }
+
+    #[test]
+    fn typed_output() {
+        let tag = nbt! { "b": 1i8, "l": 5i64, "list": [1.5f32], "arr": [L; 2] }.with_name("");
+        assert_eq!(
+            tag.to_json(JsonMode::Typed),
+            concat!(
+                r#"{"type":"compound","name":"","value":{"#,
+                r#""arr":{"type":"long_array","value":["2"]},"#,
+                r#""b":{"type":"byte","value":1},"#,
+                r#""l":{"type":"long","value":"5"},"#,
+                r#""list":{"type":"list","element":"float","value":[{"type":"float","value":1.5}]}"#,
+                r#"}}"#
+            )
+        );
+        assert_eq!(
+            nbt! { "a": [1] }.to_json_pretty(JsonMode::Typed),
+            "{\"type\": \"compound\", \"value\": {\n  \"a\": {\"type\": \"list\", \
+             \"element\": \"int\", \"value\": [\n    {\"type\": \"int\", \"value\": 1}\n  ]}\n}}"
+        );
+    }
+
+    #[test]
+    fn typed_roundtrip() {
+        let mut tag = nbt! {
+            "b": -1i8,
+            "s": 300i16,
+            "i": 1,
+            "l": i64::MIN,
+            "f": 0.1f32,
+            "d": -0.0f64,
+            "str": "q\"\n\u{1f600}",
+            "bytes": [B; -1, 2],
+            "ints": [I; 3],
+            "longs": [L; 9_007_199_254_740_993],
+            "list": [[1i8], []],
+            "compound": { "nested": { "x": 1i16 } },
+        }
+        .with_name("root");
+        let Tag::Compound { entries, .. } = &mut tag else {
+            unreachable!()
+        };
+        entries.insert(
+            "mixed".into(),
+            Tag::from(vec![Tag::from(1), Tag::from("a")]).with_name("mixed"),
+        );
+        entries.insert(
+            "odd".into(),
+            Tag::List {
+                name: Some("other".into()),
+                element_id: 3,
+                elements: vec![],
+            },
+        );
+        entries.insert("unnamed".into(), Tag::from(2));
+
+        for json in [
+            tag.to_json(JsonMode::Typed),
+            tag.to_json_pretty(JsonMode::Typed),
+        ] {
+            assert_eq!(Tag::from_json(&json, JsonMode::Typed).unwrap(), tag);
+        }
+    }
+
+    #[test]
+    fn typed_float_bits() {
+        let nan = f32::from_bits(0x7fc0_0001);
+        let tag = Tag::from(vec![Tag::from(nan), Tag::from(f32::INFINITY)]);
+        let json = tag.to_json(JsonMode::Typed);
+        assert!(json.contains(r#"{"type":"float","bits":"7fc00001"}"#));
+        let back = Tag::from_json(&json, JsonMode::Typed).unwrap();
+        let Tag::List { elements, .. } = back else {
+            panic!("not a list")
+        };
+        let bits: Vec<u32> = elements
+            .iter()
+            .map(|e| f32::try_from(e).unwrap().to_bits())
+            .collect();
+        assert_eq!(bits, [0x7fc0_0001, 0x7f80_0000]);
+
+        let back = Tag::from_json(
+            &Tag::from(f64::NEG_INFINITY).to_json(JsonMode::Typed),
+            JsonMode::Typed,
+        );
+        assert_eq!(back.unwrap(), Tag::from(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn typed_errors() {
+        let err = |s| Tag::from_json(s, JsonMode::Typed).unwrap_err().to_string();
+        assert_eq!(err("1"), "expected a {\"type\": ...} object at position 0");
+        assert_eq!(
+            err(r#"{"type": "char", "value": 1}"#),
+            "unknown type at position 9"
+        );
+        assert_eq!(
+            err(r#"{"type": "byte", "value": 300}"#),
+            "number out of range at position 26"
+        );
+        assert_eq!(err(r#"{"type": "int"}"#), "missing value at position 0");
+        assert_eq!(
+            err(r#"{"type": "string", "bits": "0"}"#),
+            "only floats and doubles have bits at position 27"
+        );
+        // Plain JSON isn't accepted in typed mode
+        assert!(Tag::from_json(r#"{"a": 1}"#, JsonMode::Typed).is_err());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod compression {
+    use crate::{Compression, Reader, Writer, nbt};
+
+    #[test]
+    fn detect_and_roundtrip() {
+        let tag = nbt! { "a": 1 }.with_name("");
+        let raw = tag.to_bytes().unwrap();
+        assert_eq!(Compression::detect(&raw), Compression::None);
+
+        let mut writer = Writer::to_zlib(Vec::new());
+        writer.write_tag(&tag).unwrap();
+        let zlib = writer.into_inner().finish().unwrap();
+        assert_eq!(Compression::detect(&zlib), Compression::Zlib);
+        assert_eq!(Reader::from_zlib(&zlib[..]).read_tag().unwrap(), tag);
+
+        let mut writer = Writer::to_gzip(Vec::new());
+        writer.write_tag(&tag).unwrap();
+        let gzip = writer.into_inner().finish().unwrap();
+        assert_eq!(Compression::detect(&gzip), Compression::Gzip);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod region {
+    use std::io::{Cursor, ErrorKind};
+
+    use crate::{Region, Tag, nbt, region::SECTOR_LEN};
+
+    fn chunk(x: i32, filler: usize) -> Tag {
+        // Random-ish bytes so zlib can't shrink the filler away
+        let mut state = 0x2545_f491u32;
+        let noise: Vec<u8> = (0..filler)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        nbt! { "xPos": x, "noise": noise }.with_name("")
+    }
+
+    fn set_location(bytes: &mut [u8], i: usize, sector: u32, count: u8) {
+        let location = sector << 8 | count as u32;
+        bytes[i * 4..i * 4 + 4].copy_from_slice(&location.to_be_bytes());
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let mut region = Region::new(Cursor::new(Vec::new())).unwrap();
+        assert!(region.chunk_positions().next().is_none());
+        region.write_chunk(3, 4, &chunk(3, 10)).unwrap();
+        // World coordinates map into the region
+        region.write_chunk(-1, 33, &chunk(-1, 10)).unwrap();
+
+        let bytes = region.into_inner().into_inner();
+        assert_eq!(bytes.len(), 4 * SECTOR_LEN);
+        let mut region = Region::new(Cursor::new(bytes)).unwrap();
+        let positions: Vec<_> = region.chunk_positions().collect();
+        assert_eq!(positions, [(31, 1), (3, 4)]);
+        assert_eq!(region.read_chunk(35, 4).unwrap(), Some(chunk(3, 10)));
+        assert_eq!(region.read_chunk(31, 1).unwrap(), Some(chunk(-1, 10)));
+        assert_eq!(region.read_chunk(0, 0).unwrap(), None);
+
+        let info = region.info(3, 4).unwrap().unwrap();
+        assert_eq!((info.x, info.z, info.sector, info.sectors), (3, 4, 2, 1));
+        assert_eq!(info.compression, 2);
+        assert!(info.timestamp > 1_600_000_000);
+        assert!(region.check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn chunks_grow_move_and_reuse_space() {
+        let mut region = Region::new(Cursor::new(Vec::new())).unwrap();
+        region.write_chunk(0, 0, &chunk(0, 10)).unwrap();
+        region.write_chunk(1, 0, &chunk(1, 10)).unwrap();
+        // Too big for its single sector, so it moves to the end
+        region.write_chunk(0, 0, &chunk(0, 6000)).unwrap();
+        let info = region.info(0, 0).unwrap().unwrap();
+        assert_eq!((info.sector, info.sectors), (4, 2));
+
+        // Sector 2 is free again and gets reused
+        region.write_chunk(2, 0, &chunk(2, 10)).unwrap();
+        assert_eq!(region.info(2, 0).unwrap().unwrap().sector, 2);
+
+        // Shrinking rewrites in place
+        region.write_chunk(0, 0, &chunk(0, 10)).unwrap();
+        let info = region.info(0, 0).unwrap().unwrap();
+        assert_eq!((info.sector, info.sectors), (4, 1));
+
+        assert!(region.delete_chunk(1, 0).unwrap());
+        assert!(!region.delete_chunk(1, 0).unwrap());
+        assert!(!region.contains(1, 0));
+        assert_eq!(region.read_chunk(2, 0).unwrap(), Some(chunk(2, 10)));
+        assert!(region.check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_reports_bad_entries() {
+        let mut region = Region::new(Cursor::new(Vec::new())).unwrap();
+        region.write_chunk(0, 0, &chunk(0, 10)).unwrap();
+        let mut bytes = region.into_inner().into_inner();
+        set_location(&mut bytes, 1, 2, 1); // shares chunk 0's sector
+        set_location(&mut bytes, 2, 1, 1); // inside the header
+        set_location(&mut bytes, 3, 9, 2); // past the end
+        let mut region = Region::new(Cursor::new(bytes.clone())).unwrap();
+        let problems: Vec<String> = region
+            .check()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "chunk 1, 0: overlaps chunk 0, 0",
+                "chunk 2, 0: starts at sector 1, inside the header",
+                "chunk 3, 0: sectors 9..11 run past the end of the file (3 sectors)",
+            ]
+        );
+
+        // A length that doesn't fit the sectors, and data that doesn't decompress
+        bytes[2 * SECTOR_LEN..2 * SECTOR_LEN + 4].copy_from_slice(&5000u32.to_be_bytes());
+        let mut region = Region::new(Cursor::new(bytes.clone())).unwrap();
+        assert!(region.read_chunk(0, 0).is_err());
+        bytes[2 * SECTOR_LEN..2 * SECTOR_LEN + 4].copy_from_slice(&20u32.to_be_bytes());
+        bytes[2 * SECTOR_LEN + 5] ^= 0xff;
+        let mut region = Region::new(Cursor::new(bytes)).unwrap();
+        let problems = region.check().unwrap();
+        assert_eq!((problems[0].x, problems[0].z), (0, 0));
+    }
+
+    #[test]
+    fn entries_without_sectors_are_errors() {
+        let mut region = Region::new(Cursor::new(Vec::new())).unwrap();
+        region.write_chunk(0, 0, &chunk(0, 10)).unwrap();
+        let mut bytes = region.into_inner().into_inner();
+        set_location(&mut bytes, 1, 2, 0);
+        set_location(&mut bytes, 2, 0, 1);
+        let mut region = Region::new(Cursor::new(bytes)).unwrap();
+        for x in [1, 2] {
+            let err = region.read_chunk_raw(x, 0).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn check_skips_newer_compressions() {
+        let mut region = Region::new(Cursor::new(Vec::new())).unwrap();
+        region.write_chunk(0, 0, &chunk(0, 10)).unwrap();
+        let mut bytes = region.into_inner().into_inner();
+        for (compression, healthy) in [(4, true), (127, true), (9, false)] {
+            bytes[2 * SECTOR_LEN + 4] = compression;
+            let mut region = Region::new(Cursor::new(bytes.clone())).unwrap();
+            assert_eq!(region.check().unwrap().is_empty(), healthy);
+            assert!(region.read_chunk(0, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn truncated_header_is_an_error() {
+        assert!(Region::new(Cursor::new(vec![0u8; 100])).is_err());
+    }
+}
+
+// ---------------------------------------------------------------------------------
+mod raw {
+    use crate::{RawString, RawTag, Reader, Tag, Writer, nbt};
+
+    // A compound holding, in this order: `z: []` written as an empty list of Int, a
+    // string with a modified-UTF-8 NUL and a stray 0xff, a NaN with payload bits, and
+    // `z` again as a byte
+    #[rustfmt::skip]
+    const QUIRKY: [u8; 37] = [
+        10, 0, 1, b'r',
+        9, 0, 1, b'z', 3, 0, 0, 0, 0,
+        8, 0, 1, b's', 0, 4, b'a', 0xc0, 0x80, 0xff,
+        5, 0, 1, b'f', 0x7f, 0xc0, 0, 1,
+        1, 0, 1, b'z', 7,
+        0,
+    ];
+
+    fn roundtrip(bytes: &[u8]) -> Vec<u8> {
+        let (name, tag) = Reader::new(bytes).read_raw_tag().unwrap();
+        let mut writer = Writer::new(Vec::new());
+        writer.write_raw_tag(&name, &tag).unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn quirks_survive() {
+        assert_eq!(roundtrip(&QUIRKY), QUIRKY);
+        assert!(Reader::new(&QUIRKY[..]).read_tag().is_err());
+
+        let (name, tag) = Reader::new(&QUIRKY[..]).read_raw_tag().unwrap();
+        assert_eq!(name, RawString::from("r"));
+        let RawTag::Compound(entries) = &tag else {
+            panic!("not a compound")
+        };
+        let keys: Vec<_> = entries.iter().map(|(k, _)| k.as_str().unwrap()).collect();
+        assert_eq!(keys, ["z", "s", "f", "z"]);
+        assert_eq!(
+            entries[0].1,
+            RawTag::List {
+                element_id: 3,
+                elements: vec![],
+                negative_len: None,
+            }
+        );
+        assert_eq!(entries[2].1, RawTag::Float(0x7fc0_0001));
+    }
+
+    #[test]
+    fn negative_list_lengths_survive() {
+        #[rustfmt::skip]
+        let bytes = [10, 0, 0, 9, 0, 1, b'n', 1, 0xff, 0xff, 0xff, 0xfe, 0];
+        assert_eq!(roundtrip(&bytes), bytes);
+        assert!(Reader::new(&bytes[..]).read_tag().is_err());
+
+        let (_, tag) = Reader::new(&bytes[..]).read_raw_tag().unwrap();
+        assert_eq!(tag.to_tag(None), nbt! { "n": [] });
+
+        let bad = RawTag::List {
+            element_id: 1,
+            elements: vec![RawTag::Byte(1)],
+            negative_len: Some(-1),
+        };
+        let err = Writer::new(Vec::new())
+            .write_raw_tag(&RawString::default(), &bad)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn huge_length_prefixes_fail_without_allocating() {
+        for input in super::bulk_io::huge_prefixes() {
+            let err = Reader::new(&input[..]).read_raw_tag().unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn normal_files_are_unchanged() {
+        let tag = nbt! {
+            "list": [1.5f64, -0.0],
+            "nested": { "ints": [I; 1, 2], "s": "\u{e9}" },
+        }
+        .with_name("");
+        let bytes = tag.to_bytes().unwrap();
+        assert_eq!(roundtrip(&bytes), bytes);
+        assert_eq!(roundtrip(&[0]), [0]);
+    }
+
+    #[test]
+    fn lossy_view() {
+        let (name, tag) = Reader::new(&QUIRKY[..]).read_raw_tag().unwrap();
+        let tag = tag.to_tag(Some(name.to_string_lossy()));
+        assert_eq!(tag.name(), Some("r"));
+        assert_eq!(tag["s"], Tag::new_string("s", "a\0\u{fffd}"));
+        // The later of the duplicate keys wins
+        assert_eq!(tag["z"], Tag::new_byte("z", 7));
+        let Tag::Float { value, .. } = tag["f"] else {
+            panic!("not a float")
+        };
+        assert_eq!(value.to_bits(), 0x7fc0_0001);
+    }
+
+    #[test]
+    fn modified_utf8() {
+        let decode = |bytes: &[u8]| RawString(bytes.to_vec()).to_string_lossy();
+        // U+1F600 as Java writes it: two 3-byte surrogates
+        assert_eq!(decode(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]), "\u{1f600}");
+        assert_eq!(decode("\u{1f600}".as_bytes()), "\u{1f600}");
+        assert_eq!(decode(&[b'x', 0xc0, 0x80]), "x\0");
+        assert_eq!(decode(&[0xe0, b'a']), "\u{fffd}a");
+        assert_eq!(format!("{:?}", RawString(vec![0xff])), "RawString([255])");
+    }
+
+    #[test]
+    fn end_lists_with_elements_are_rejected() {
+        let bytes = [9, 0, 0, 0, 0x7f, 0xff, 0xff, 0xff];
+        assert!(Reader::new(&bytes[..]).read_raw_tag().is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Tag, TagTypeError,
+    path::{NbtPath, PathSegment},
+};
+
+/// Conversion of a Rust value into a tag. Derivable with `#[derive(ToNbt)]` (feature
+/// `derive`); structs become compounds, see [`FromNbt`] for the field attributes.
+pub trait ToNbt {
+    fn to_nbt(&self) -> Tag;
+}
+
+/// Conversion of a tag back into a Rust value. Derivable with `#[derive(FromNbt)]`.
+///
+/// Field attributes understood by the derives:
+/// - `#[nbt(rename = "Key")]` uses a different compound key
+/// - `#[nbt(default)]` / `#[nbt(default = "path::to::fn")]` fills missing keys
+/// - `#[nbt(skip)]` never writes the field and reads it as `Default::default()`
+/// - `#[nbt(flatten)]` inlines a compound-shaped field into the parent; on read it sees
+///   only the keys no other field claimed, so a `HashMap<String, Tag>` keeps unknown keys
+/// - `#[nbt(byte_array)]`, `#[nbt(int_array)]`, `#[nbt(long_array)]` store a `Vec` as
+///   the typed array tag instead of a List
+/// - `Option<T>` fields are omitted when `None` and read as `None` when missing
+///
+/// Enums with only unit variants are stored as their variant name in a String tag.
+/// Other enums are compounds holding the variant name under `"type"` (change it with
+/// `#[nbt(tag = "...")]` on the enum) next to the variant's fields.
+pub trait FromNbt: Sized {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError>;
+}
+
+/// What went wrong in [`FromNbt::from_nbt`]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FromNbtErrorKind {
+    WrongType(TagTypeError),
+    MissingField(String),
+    UnknownVariant(String),
+    Custom(String),
+}
+
+/// Error from [`FromNbt::from_nbt`], with the path of the offending tag
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct FromNbtError {
+    pub path: NbtPath,
+    pub kind: FromNbtErrorKind,
+}
+
+impl FromNbtError {
+    pub fn new(kind: FromNbtErrorKind) -> Self {
+        FromNbtError {
+            path: NbtPath::root(),
+            kind,
+        }
+    }
+
+    pub fn wrong_type(expected: u8, found: &Tag) -> Self {
+        FromNbtError::new(FromNbtErrorKind::WrongType(TagTypeError {
+            expected,
+            found: found.id(),
+        }))
+    }
+
+    pub fn missing_field(key: &str) -> Self {
+        FromNbtError::new(FromNbtErrorKind::MissingField(key.to_string()))
+    }
+
+    pub fn custom(message: impl fmt::Display) -> Self {
+        FromNbtError::new(FromNbtErrorKind::Custom(message.to_string()))
+    }
+
+    /// Prefix the error path with the compound key the failing value sat under
+    pub fn at_key(mut self, key: &str) -> Self {
+        self.path
+            .segments
+            .insert(0, PathSegment::Key(key.to_string()));
+        self
+    }
+
+    /// Prefix the error path with the list index the failing value sat at
+    pub fn at_index(mut self, index: usize) -> Self {
+        self.path.segments.insert(0, PathSegment::Index(index));
+        self
+    }
+}
+
+impl From<TagTypeError> for FromNbtError {
+    fn from(e: TagTypeError) -> Self {
+        FromNbtError::new(FromNbtErrorKind::WrongType(e))
+    }
+}
+
+impl fmt::Display for FromNbtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_root() {
+            write!(f, "at {}: ", self.path)?;
+        }
+        match &self.kind {
+            FromNbtErrorKind::WrongType(e) => e.fmt(f),
+            FromNbtErrorKind::MissingField(key) => write!(f, "missing field `{}`", key),
+            FromNbtErrorKind::UnknownVariant(name) => write!(f, "unknown variant `{}`", name),
+            FromNbtErrorKind::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for FromNbtError {}
+
+macro_rules! impl_scalar {
+    ($($t:ty),*) => {$(
+        impl ToNbt for $t {
+            fn to_nbt(&self) -> Tag {
+                Tag::from(self.clone())
+            }
+        }
+        impl FromNbt for $t {
+            fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+                Ok(<$t>::try_from(tag)?)
+            }
+        }
+    )*};
+}
+impl_scalar!(i8, i16, i32, i64, f32, f64, bool, String);
+
+impl ToNbt for str {
+    fn to_nbt(&self) -> Tag {
+        Tag::from(self)
+    }
+}
+
+impl ToNbt for Tag {
+    fn to_nbt(&self) -> Tag {
+        self.clone()
+    }
+}
+
+impl FromNbt for Tag {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        Ok(tag.clone())
+    }
+}
+
+impl<T: ToNbt + ?Sized> ToNbt for &T {
+    fn to_nbt(&self) -> Tag {
+        (**self).to_nbt()
+    }
+}
+
+impl<T: ToNbt + ?Sized> ToNbt for Box<T> {
+    fn to_nbt(&self) -> Tag {
+        (**self).to_nbt()
+    }
+}
+
+impl<T: FromNbt> FromNbt for Box<T> {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        T::from_nbt(tag).map(Box::new)
+    }
+}
+
+/// Vecs are Lists; use the array attributes for ByteArray/IntArray/LongArray
+impl<T: ToNbt> ToNbt for Vec<T> {
+    fn to_nbt(&self) -> Tag {
+        Tag::from(self.iter().map(ToNbt::to_nbt).collect::<Vec<_>>())
+    }
+}
+
+impl<T: FromNbt> FromNbt for Vec<T> {
+    fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+        match tag {
+            Tag::List { elements, .. } => elements
+                .iter()
+                .enumerate()
+                .map(|(i, e)| T::from_nbt(e).map_err(|err| err.at_index(i)))
+                .collect(),
+            other => Err(FromNbtError::wrong_type(9, other)),
+        }
+    }
+}
+
+macro_rules! impl_map {
+    ($($map:ident),*) => {$(
+        impl<T: ToNbt> ToNbt for $map<String, T> {
+            fn to_nbt(&self) -> Tag {
+                Tag::from(
+                    self.iter()
+                        .map(|(k, v)| (k.clone(), v.to_nbt()))
+                        .collect::<HashMap<_, _>>(),
+                )
+            }
+        }
+        impl<T: FromNbt> FromNbt for $map<String, T> {
+            fn from_nbt(tag: &Tag) -> Result<Self, FromNbtError> {
+                match tag {
+                    Tag::Compound { entries, .. } => entries
+                        .iter()
+                        .map(|(k, v)| Ok((k.clone(), T::from_nbt(v).map_err(|e| e.at_key(k))?)))
+                        .collect(),
+                    other => Err(FromNbtError::wrong_type(10, other)),
+                }
+            }
+        }
+    )*};
+}
+impl_map!(HashMap, BTreeMap);
+
+/// Remove `key` from `entries` and convert it, so what's left over is the unknown keys.
+/// Used by the hand-written models that keep unmodelled keys in an `extra` map.
+pub(crate) fn take<T: FromNbt>(
+    entries: &mut HashMap<String, Tag>,
+    key: &str,
+) -> Result<Option<T>, FromNbtError> {
+    entries
+        .remove(key)
+        .map(|tag| T::from_nbt(&tag).map_err(|e| e.at_key(key)))
+        .transpose()
+}
+
+/// [`take`] for models that read leniently: a value of an unexpected type stays in
+/// `entries`, and so in `extra`, instead of failing the whole read
+pub(crate) fn take_valid<T: FromNbt>(entries: &mut HashMap<String, Tag>, key: &str) -> Option<T> {
+    let value = T::from_nbt(entries.get(key)?).ok()?;
+    entries.remove(key);
+    Some(value)
+}
+
+pub(crate) fn entries_of(tag: &Tag) -> Result<HashMap<String, Tag>, FromNbtError> {
+    match tag {
+        Tag::Compound { entries, .. } => Ok(entries.clone()),
+        other => Err(FromNbtError::wrong_type(10, other)),
+    }
+}
+
+pub(crate) fn put(entries: &mut HashMap<String, Tag>, key: &str, value: impl ToNbt) {
+    entries.insert(key.to_string(), value.to_nbt().with_name(key));
+}
+
+/// [`put`] for fields modelling optional keys: `None` leaves the entries alone
+pub(crate) fn put_some<T: ToNbt>(entries: &mut HashMap<String, Tag>, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        put(entries, key, value);
+    }
+}
+
+/// Save the way vanilla does: write a temporary file next to `path`, move the current
+/// file to its `_old` backup, then move the new file into place. A crash mid-save leaves
+/// either the old file or its backup intact.
+pub(crate) fn save_with_backup(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        write(&mut file)?;
+        file.into_inner()?.sync_all()?;
+    }
+    if path.exists() {
+        let backup = backup_path(path);
+        // Renaming over an existing file fails on Windows
+        if backup.exists() {
+            fs::remove_file(&backup)?;
+        }
+        fs::rename(path, backup)?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// `level.dat` becomes `level.dat_old`, `<uuid>.dat` becomes `<uuid>.dat_old`
+pub(crate) fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push("_old");
+    PathBuf::from(backup)
+}
+
+/// Support code for the derive macros; not a stable API
+#[doc(hidden)]
+pub mod __private {
+    use std::collections::HashMap;
+
+    use super::{FromNbt, FromNbtError, FromNbtErrorKind};
+    use crate::Tag;
+
+    pub fn entries(tag: &Tag) -> Result<&HashMap<String, Tag>, FromNbtError> {
+        match tag {
+            Tag::Compound { entries, .. } => Ok(entries),
+            other => Err(FromNbtError::wrong_type(10, other)),
+        }
+    }
+
+    pub fn field<T: FromNbt>(
+        entries: &HashMap<String, Tag>,
+        key: &str,
+    ) -> Result<Option<T>, FromNbtError> {
+        entries
+            .get(key)
+            .map(|tag| T::from_nbt(tag).map_err(|e| e.at_key(key)))
+            .transpose()
+    }
+
+    pub fn array_field<T>(
+        entries: &HashMap<String, Tag>,
+        key: &str,
+        convert: fn(&Tag) -> Result<T, FromNbtError>,
+    ) -> Result<Option<T>, FromNbtError> {
+        entries
+            .get(key)
+            .map(|tag| convert(tag).map_err(|e| e.at_key(key)))
+            .transpose()
+    }
+
+    /// The compound minus the keys claimed by named fields, for `flatten` fields
+    pub fn rest(entries: &HashMap<String, Tag>, claimed: &[&str]) -> Tag {
+        Tag::from(
+            entries
+                .iter()
+                .filter(|(k, _)| !claimed.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    /// Merge a flattened field's compound into the parent without overriding its keys
+    pub fn flatten_into(entries: &mut HashMap<String, Tag>, tag: Tag) {
+        if let Tag::Compound { entries: inner, .. } = tag {
+            for (k, v) in inner {
+                entries.entry(k).or_insert(v);
+            }
+        }
+    }
+
+    pub fn variant_name<'a>(tag: &'a Tag, key: &str) -> Result<&'a str, FromNbtError> {
+        let name = match tag {
+            Tag::Compound { entries, .. } => match entries.get(key) {
+                Some(t) => t,
+                None => return Err(FromNbtError::missing_field(key)),
+            },
+            other => other,
+        };
+        <&str>::try_from(name).map_err(|e| {
+            let err = FromNbtError::from(e);
+            if matches!(tag, Tag::Compound { .. }) {
+                err.at_key(key)
+            } else {
+                err
+            }
+        })
+    }
+
+    pub fn unknown_variant(name: &str) -> FromNbtError {
+        FromNbtError::new(FromNbtErrorKind::UnknownVariant(name.to_string()))
+    }
+
+    pub fn byte_array_to_nbt(v: &[u8]) -> Tag {
+        Tag::from(v)
+    }
+    pub fn int_array_to_nbt(v: &[i32]) -> Tag {
+        Tag::from(v)
+    }
+    pub fn long_array_to_nbt(v: &[i64]) -> Tag {
+        Tag::from(v)
+    }
+    pub fn byte_array_from_nbt(tag: &Tag) -> Result<Vec<u8>, FromNbtError> {
+        Ok(Vec::<u8>::try_from(tag)?)
+    }
+    pub fn int_array_from_nbt(tag: &Tag) -> Result<Vec<i32>, FromNbtError> {
+        Ok(Vec::<i32>::try_from(tag)?)
+    }
+    pub fn long_array_from_nbt(tag: &Tag) -> Result<Vec<i64>, FromNbtError> {
+        Ok(Vec::<i64>::try_from(tag)?)
+    }
+}
@@ -0,0 +1,134 @@
+//! UUIDs as vanilla stores them. They are handled as `u128`, most significant bits
+//! first, so no UUID crate is needed; convert with `Uuid::from_u128` if you use one.
+
+use crate::Tag;
+
+/// How a UUID is stored in a compound
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum UuidForm {
+    /// An IntArray of four ints, most significant first (1.16+)
+    IntArray,
+    /// Two Longs named `<key>Most` and `<key>Least` (before 1.16)
+    MostLeast,
+    /// A hyphenated hex string, like `OwnerUUID` before 1.16
+    String,
+}
+
+/// Split into the four ints of the IntArray form
+pub fn uuid_to_ints(uuid: u128) -> [i32; 4] {
+    [
+        (uuid >> 96) as i32,
+        (uuid >> 64) as i32,
+        (uuid >> 32) as i32,
+        uuid as i32,
+    ]
+}
+
+pub fn uuid_from_ints(ints: [i32; 4]) -> u128 {
+    ints.iter()
+        .fold(0, |uuid, &int| uuid << 32 | int as u32 as u128)
+}
+
+/// Split into the most and least significant longs
+pub fn uuid_to_longs(uuid: u128) -> (i64, i64) {
+    ((uuid >> 64) as i64, uuid as i64)
+}
+
+pub fn uuid_from_longs(most: i64, least: i64) -> u128 {
+    (most as u64 as u128) << 64 | least as u64 as u128
+}
+
+/// Lower-case and hyphenated: `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`
+pub fn format_uuid(uuid: u128) -> String {
+    let hex = format!("{:032x}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Parse the hyphenated form, in either case
+pub fn parse_uuid(s: &str) -> Option<u128> {
+    let groups: Vec<&str> = s.split('-').collect();
+    let lens = groups.iter().map(|g| g.len()).collect::<Vec<_>>();
+    if lens != [8, 4, 4, 4, 12]
+        || !groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+    u128::from_str_radix(&groups.concat(), 16).ok()
+}
+
+/// The UUID held by a tag on its own: a 4-int IntArray or a hyphenated String
+pub fn uuid_of(tag: &Tag) -> Option<u128> {
+    match tag {
+        Tag::IntArray { value, .. } => Some(uuid_from_ints(value.as_slice().try_into().ok()?)),
+        Tag::String { value, .. } => parse_uuid(value),
+        _ => None,
+    }
+}
+
+/// An unnamed tag holding `uuid` in a single-tag form. Panics for [`UuidForm::MostLeast`],
+/// which needs two entries; use [`Tag::set_uuid`] for that.
+pub fn uuid_tag(uuid: u128, form: UuidForm) -> Tag {
+    match form {
+        UuidForm::IntArray => Tag::from(uuid_to_ints(uuid).to_vec()),
+        UuidForm::String => Tag::from(format_uuid(uuid)),
+        UuidForm::MostLeast => panic!("a Most/Least UUID is two tags"),
+    }
+}
+
+fn pair_keys(key: &str) -> (String, String) {
+    (format!("{}Most", key), format!("{}Least", key))
+}
+
+/// [`Tag::get_uuid`] over any map of entries, for types that keep theirs outside a tag
+pub(crate) fn uuid_in<'a>(get: impl Fn(&str) -> Option<&'a Tag>, key: &str) -> Option<u128> {
+    if let Some(uuid) = get(key).and_then(uuid_of) {
+        return Some(uuid);
+    }
+    let (most, least) = pair_keys(key);
+    let most = i64::try_from(get(&most)?).ok()?;
+    let least = i64::try_from(get(&least)?).ok()?;
+    Some(uuid_from_longs(most, least))
+}
+
+impl Tag {
+    /// Read the UUID stored under `key` in a compound in any of the [`UuidForm`]s
+    pub fn get_uuid(&self, key: &str) -> Option<u128> {
+        uuid_in(|k| self.get(k), key)
+    }
+
+    /// Store `uuid` under `key` in a compound in `form`, removing any other form of it.
+    /// Panics on non-compounds, like [`Tag::insert`].
+    pub fn set_uuid(&mut self, key: &str, uuid: u128, form: UuidForm) {
+        self.remove_uuid(key);
+        match form {
+            UuidForm::MostLeast => {
+                let (most_key, least_key) = pair_keys(key);
+                let (most, least) = uuid_to_longs(uuid);
+                self.insert(most_key.clone(), Tag::new_long(most_key, most));
+                self.insert(least_key.clone(), Tag::new_long(least_key, least));
+            }
+            form => self.insert(key.to_string(), uuid_tag(uuid, form).with_name(key)),
+        }
+    }
+
+    /// Remove every form of the UUID under `key`, returning it if one was readable
+    pub fn remove_uuid(&mut self, key: &str) -> Option<u128> {
+        let uuid = self.get_uuid(key);
+        if let Tag::Compound { entries, .. } = self {
+            let (most, least) = pair_keys(key);
+            for key in [key, &most, &least] {
+                entries.remove(key);
+            }
+        }
+        uuid
+    }
+}
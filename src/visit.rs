@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use crate::{
+    Tag, TagId,
+    path::{NbtPath, PathSegment},
+};
+
+/// What a visitor callback wants the traversal to do next
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Flow {
+    Continue,
+    /// Don't descend into this list or compound (same as Continue for other tags)
+    Skip,
+    /// Abort the whole traversal
+    Stop,
+}
+
+/// Read-only callbacks for [`Tag::walk`]. Every method defaults to `Flow::Continue`,
+/// so implementors only override the tags they care about. `path` is the location of
+/// the tag being visited; compound entries are visited in unspecified order.
+#[allow(unused_variables)]
+pub trait Visitor {
+    fn visit_byte(&mut self, path: &NbtPath, value: i8) -> Flow {
+        Flow::Continue
+    }
+    fn visit_short(&mut self, path: &NbtPath, value: i16) -> Flow {
+        Flow::Continue
+    }
+    fn visit_int(&mut self, path: &NbtPath, value: i32) -> Flow {
+        Flow::Continue
+    }
+    fn visit_long(&mut self, path: &NbtPath, value: i64) -> Flow {
+        Flow::Continue
+    }
+    fn visit_float(&mut self, path: &NbtPath, value: f32) -> Flow {
+        Flow::Continue
+    }
+    fn visit_double(&mut self, path: &NbtPath, value: f64) -> Flow {
+        Flow::Continue
+    }
+    fn visit_byte_array(&mut self, path: &NbtPath, value: &[u8]) -> Flow {
+        Flow::Continue
+    }
+    fn visit_string(&mut self, path: &NbtPath, value: &str) -> Flow {
+        Flow::Continue
+    }
+    fn visit_int_array(&mut self, path: &NbtPath, value: &[i32]) -> Flow {
+        Flow::Continue
+    }
+    fn visit_long_array(&mut self, path: &NbtPath, value: &[i64]) -> Flow {
+        Flow::Continue
+    }
+    /// Called before the elements of a list; `Flow::Skip` leaves them unvisited
+    fn enter_list(&mut self, path: &NbtPath, element_id: TagId, elements: &[Tag]) -> Flow {
+        Flow::Continue
+    }
+    /// Called after the elements of a list that wasn't skipped
+    fn leave_list(&mut self, path: &NbtPath) -> Flow {
+        Flow::Continue
+    }
+    /// Called before the entries of a compound; `Flow::Skip` leaves them unvisited
+    fn enter_compound(&mut self, path: &NbtPath, entries: &HashMap<String, Tag>) -> Flow {
+        Flow::Continue
+    }
+    /// Called after the entries of a compound that wasn't skipped
+    fn leave_compound(&mut self, path: &NbtPath) -> Flow {
+        Flow::Continue
+    }
+}
+
+/// Mutable counterpart of [`Visitor`] for [`Tag::walk_mut`]. Containers are handed to
+/// `enter_*` before their children are walked, so entries added there are visited too.
+#[allow(unused_variables)]
+pub trait VisitorMut {
+    fn visit_byte(&mut self, path: &NbtPath, value: &mut i8) -> Flow {
+        Flow::Continue
+    }
+    fn visit_short(&mut self, path: &NbtPath, value: &mut i16) -> Flow {
+        Flow::Continue
+    }
+    fn visit_int(&mut self, path: &NbtPath, value: &mut i32) -> Flow {
+        Flow::Continue
+    }
+    fn visit_long(&mut self, path: &NbtPath, value: &mut i64) -> Flow {
+        Flow::Continue
+    }
+    fn visit_float(&mut self, path: &NbtPath, value: &mut f32) -> Flow {
+        Flow::Continue
+    }
+    fn visit_double(&mut self, path: &NbtPath, value: &mut f64) -> Flow {
+        Flow::Continue
+    }
+    fn visit_byte_array(&mut self, path: &NbtPath, value: &mut Vec<u8>) -> Flow {
+        Flow::Continue
+    }
+    fn visit_string(&mut self, path: &NbtPath, value: &mut String) -> Flow {
+        Flow::Continue
+    }
+    fn visit_int_array(&mut self, path: &NbtPath, value: &mut Vec<i32>) -> Flow {
+        Flow::Continue
+    }
+    fn visit_long_array(&mut self, path: &NbtPath, value: &mut Vec<i64>) -> Flow {
+        Flow::Continue
+    }
+    fn enter_list(
+        &mut self,
+        path: &NbtPath,
+        element_id: &mut TagId,
+        elements: &mut Vec<Tag>,
+    ) -> Flow {
+        Flow::Continue
+    }
+    fn leave_list(&mut self, path: &NbtPath) -> Flow {
+        Flow::Continue
+    }
+    fn enter_compound(&mut self, path: &NbtPath, entries: &mut HashMap<String, Tag>) -> Flow {
+        Flow::Continue
+    }
+    fn leave_compound(&mut self, path: &NbtPath) -> Flow {
+        Flow::Continue
+    }
+}
+
+impl Tag {
+    /// Depth-first traversal calling `visitor` for every tag. Returns `Flow::Stop` if
+    /// the visitor stopped early, `Flow::Continue` otherwise.
+    pub fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V) -> Flow {
+        walk(self, visitor, &mut NbtPath::root())
+    }
+
+    /// Depth-first traversal handing `visitor` mutable access to every tag
+    pub fn walk_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) -> Flow {
+        walk_mut(self, visitor, &mut NbtPath::root())
+    }
+}
+
+fn walk<V: Visitor + ?Sized>(tag: &Tag, v: &mut V, path: &mut NbtPath) -> Flow {
+    let flow = match tag {
+        Tag::End => Flow::Continue,
+        Tag::Byte { value, .. } => v.visit_byte(path, *value),
+        Tag::Short { value, .. } => v.visit_short(path, *value),
+        Tag::Int { value, .. } => v.visit_int(path, *value),
+        Tag::Long { value, .. } => v.visit_long(path, *value),
+        Tag::Float { value, .. } => v.visit_float(path, *value),
+        Tag::Double { value, .. } => v.visit_double(path, *value),
+        Tag::ByteArray { value, .. } => v.visit_byte_array(path, value),
+        Tag::String { value, .. } => v.visit_string(path, value),
+        Tag::IntArray { value, .. } => v.visit_int_array(path, value),
+        Tag::LongArray { value, .. } => v.visit_long_array(path, value),
+        Tag::List {
+            element_id,
+            elements,
+            ..
+        } => match v.enter_list(path, *element_id, elements) {
+            Flow::Continue => {
+                for (i, elem) in elements.iter().enumerate() {
+                    path.segments.push(PathSegment::Index(i));
+                    let flow = walk(elem, v, path);
+                    path.segments.pop();
+                    if flow == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+                v.leave_list(path)
+            }
+            flow => flow,
+        },
+        Tag::Compound { entries, .. } => match v.enter_compound(path, entries) {
+            Flow::Continue => {
+                for (key, entry) in entries {
+                    path.segments.push(PathSegment::Key(key.clone()));
+                    let flow = walk(entry, v, path);
+                    path.segments.pop();
+                    if flow == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+                v.leave_compound(path)
+            }
+            flow => flow,
+        },
+    };
+    match flow {
+        Flow::Stop => Flow::Stop,
+        _ => Flow::Continue,
+    }
+}
+
+fn walk_mut<V: VisitorMut + ?Sized>(tag: &mut Tag, v: &mut V, path: &mut NbtPath) -> Flow {
+    let flow = match tag {
+        Tag::End => Flow::Continue,
+        Tag::Byte { value, .. } => v.visit_byte(path, value),
+        Tag::Short { value, .. } => v.visit_short(path, value),
+        Tag::Int { value, .. } => v.visit_int(path, value),
+        Tag::Long { value, .. } => v.visit_long(path, value),
+        Tag::Float { value, .. } => v.visit_float(path, value),
+        Tag::Double { value, .. } => v.visit_double(path, value),
+        Tag::ByteArray { value, .. } => v.visit_byte_array(path, value),
+        Tag::String { value, .. } => v.visit_string(path, value),
+        Tag::IntArray { value, .. } => v.visit_int_array(path, value),
+        Tag::LongArray { value, .. } => v.visit_long_array(path, value),
+        Tag::List {
+            element_id,
+            elements,
+            ..
+        } => match v.enter_list(path, element_id, elements) {
+            Flow::Continue => {
+                for (i, elem) in elements.iter_mut().enumerate() {
+                    path.segments.push(PathSegment::Index(i));
+                    let flow = walk_mut(elem, v, path);
+                    path.segments.pop();
+                    if flow == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+                v.leave_list(path)
+            }
+            flow => flow,
+        },
+        Tag::Compound { entries, .. } => match v.enter_compound(path, entries) {
+            Flow::Continue => {
+                for (key, entry) in entries.iter_mut() {
+                    path.segments.push(PathSegment::Key(key.clone()));
+                    let flow = walk_mut(entry, v, path);
+                    path.segments.pop();
+                    if flow == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+                v.leave_compound(path)
+            }
+            flow => flow,
+        },
+    };
+    match flow {
+        Flow::Stop => Flow::Stop,
+        _ => Flow::Continue,
+    }
+}